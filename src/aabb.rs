@@ -12,12 +12,13 @@ impl Aabb {
         Self { minimum: a, maximum: b }
     }
 
-    pub fn min(&self) -> Point3 {
-        self.minimum
+    pub fn centroid(&self) -> Point3 {
+        0.5 * (self.minimum + self.maximum)
     }
 
-    pub fn max(&self) -> Point3 {
-        self.maximum
+    pub fn surface_area(&self) -> f64 {
+        let d = self.maximum - self.minimum;
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
     }
 }
 
@@ -49,6 +50,28 @@ impl Aabb {
         true
     }
 
+    // The same slab test with the reciprocal ray direction computed once by
+    // the caller, for walking many boxes with one ray.
+    pub fn hit_with_inverse(&self, origin: &Point3, inv_dir: &[f64; 3], mut t_min: f64, mut t_max: f64) -> bool {
+        for a in 0..3 {
+            let mut t0 = (self.minimum[a] - origin[a]) * inv_dir[a];
+            let mut t1 = (self.maximum[a] - origin[a]) * inv_dir[a];
+
+            if inv_dir[a] < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+
+            if t_max <= t_min {
+                return false;
+            }
+        }
+
+        true
+    }
+
     pub fn surrounding_box(box0: &Aabb, box1: &Aabb) -> Aabb {
         let small = Point3::new(
             box0.minimum.x.min(box1.minimum.x),
@@ -1,205 +0,0 @@
-use std::sync::Arc;
-
-use crate::{aabb::Aabb, hittable::{HitRecord, Hittable}, material::Material, ray::Ray, rtweekend::{random_double_range, INFINITY}, vec3::{Point3, Vec3}};
-
-
-pub struct XYRect {
-    pub x0: f64,
-    pub x1: f64,
-    pub y0: f64,
-    pub y1: f64,
-    pub k: f64,
-    pub material: Arc<dyn Material>,
-}
-
-pub struct XZRect {
-    pub x0: f64,
-    pub x1: f64,
-    pub z0: f64,
-    pub z1: f64,
-    pub k: f64,
-    pub material: Arc<dyn Material>,
-}
-
-pub struct YZRect {
-    pub y0: f64,
-    pub y1: f64,
-    pub z0: f64,
-    pub z1: f64,
-    pub k: f64,
-    pub material: Arc<dyn Material>,
-}
-
-impl XYRect {
-    pub fn new(x0: f64, x1: f64, y0: f64, y1: f64, k: f64, material: Arc<dyn Material>) -> Self {
-        Self { x0, x1, y0, y1, k, material }
-    }
-}
-
-impl XZRect {
-    pub fn new(x0: f64, x1: f64, z0: f64, z1: f64, k: f64, material: Arc<dyn Material>) -> Self {
-        Self { x0, x1, z0, z1, k, material }
-    }
-}
-
-impl YZRect {
-    pub fn new(y0: f64, y1: f64, z0: f64, z1: f64, k: f64, material: Arc<dyn Material>) -> Self {
-        Self { y0, y1, z0, z1, k, material }
-    }
-}
-
-
-impl Hittable for XYRect {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let t = (self.k - r.origin.z) / r.direction.z;
-        if t < t_min || t > t_max {
-            return None;
-        }
-
-        let x = r.origin.x + t * r.direction.x;
-        let y = r.origin.y + t * r.direction.y;
-
-        if x < self.x0 || x > self.x1 || y < self.y0 || y > self.y1 {
-            return None;
-        }
-
-        let u = (x - self.x0) / (self.x1 - self.x0);
-        let v = (y - self.y0) / (self.y1 - self.y0);
-        let p = r.at(t);
-        let outward_normal = Vec3::new(0.0, 0.0, 1.0);
-
-        let mut rec = HitRecord {
-            t,
-            p,
-            u,
-            v,
-            normal: Vec3::ZERO,
-            front_face: false,
-            material: Arc::clone(&self.material)
-        };
-
-        rec.set_face_normal(r, outward_normal);
-
-        Some(rec)
-    }
-
-    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
-        // The bounding box must have non-zero width in each dimension, so pad the Z
-        // dimension a small amount.
-       Some(Aabb::new(
-            Point3::new(self.x0, self.y0, self.k - 0.0001),
-            Point3::new(self.x1, self.y1, self.k + 0.0001),
-        ))
-    }
-}
-
-impl Hittable for XZRect {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let t = (self.k - r.origin.y) / r.direction.y;
-
-        if t < t_min || t > t_max {
-            return None;
-        }
-
-        let x = r.origin.x + t * r.direction.x;
-        let z = r.origin.z + t * r.direction.z;
-
-        if x < self.x0 || x > self.x1 || z < self.z0 || z > self.z1 {
-            return None;
-        }
-
-        let u = (x - self.x0) / (self.x1 - self.x0);
-        let v = (z - self.z0) / (self.z1 - self.z0);
-        let p = r.at(t);
-        let outward_normal = Vec3::new(0.0, 1.0, 0.0);
-
-        let mut rec = HitRecord {
-            t,
-            p,
-            u,
-            v,
-            normal: Vec3::ZERO,
-            front_face: false,
-            material: Arc::clone(&self.material)
-        };
-
-        rec.set_face_normal(r, outward_normal);
-
-        Some(rec)
-    }
-
-    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
-        // The bounding box must have non-zero width in each dimension, so pad the Z
-        // dimension a small amount.
-       Some(Aabb::new(
-            Point3::new(self.x0, self.k - 0.0001, self.z0),
-            Point3::new(self.x1, self.k + 0.0001, self.z1,),
-        ))
-    }
-
-    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
-        let ray = Ray::with_time(*origin, *direction, 0.0);
-        if let Some(rec) = self.hit(&ray, 0.001, INFINITY) {
-            let area = (self.x1 - self.x0) * (self.z1 - self.z0);
-            let distance_squared = rec.t * rec.t * direction.length_squared();
-            let cosine = (direction.dot(&rec.normal)).abs() / direction.length();
-            return distance_squared / (cosine * area);
-        }
-
-        0.0
-    }
-
-    fn random(&self, origin: &Point3) -> Vec3 {
-        let random_point = Point3::new(
-            random_double_range(self.x0, self.x1),
-            self.k,
-            random_double_range(self.z0, self.z1),
-        );
-        random_point - *origin
-    }
-}
-
-impl Hittable for YZRect {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let t = (self.k - r.origin.x) / r.direction.x;
-
-        if t < t_min || t > t_max {
-            return None;
-        }
-
-        let y = r.origin.y + t * r.direction.y;
-        let z = r.origin.z + t * r.direction.z;
-
-        if y < self.y0 || y > self.y1 || z < self.z0 || z > self.z1 {
-            return None;
-        }
-
-        let u = (y - self.y0) / (self.y1 - self.y0);
-        let v = (z - self.z0) / (self.z1 - self.z0);
-        let p = r.at(t);
-        let outward_normal = Vec3::new(1.0, 0.0, 0.0);
-
-        let mut rec = HitRecord {
-            t,
-            p,
-            u,
-            v,
-            normal: Vec3::ZERO,
-            front_face: false,
-            material: Arc::clone(&self.material)
-        };
-
-        rec.set_face_normal(r, outward_normal);
-
-        Some(rec)
-    }
-
-    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
-        // The bounding box must have non-zero width in each dimension, so pad the Z
-        // dimension a small amount.
-       Some(Aabb::new(
-            Point3::new(self.k - 0.0001, self.y0, self.z0),
-            Point3::new(self.k + 0.0001, self.y1, self.z1),
-        ))
-    }
-}
@@ -0,0 +1,225 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    matrix::Quat,
+    ray::Ray,
+    rtweekend::{INFINITY, Sampler},
+    vec3::{Point3, Vec3},
+};
+
+// Poses sampled per keyframe segment when bounding the swept volume.
+const SWEEP_STEPS: usize = 16;
+
+// The object's pose at one moment: scaled, then rotated, then translated.
+#[derive(Clone, Copy)]
+pub struct Keyframe {
+    pub time: f64,
+    pub translate: Vec3,
+    pub rotate: Quat,
+    pub scale: Vec3,
+}
+
+impl Keyframe {
+    pub fn new(time: f64, translate: Vec3, rotate: Quat, scale: Vec3) -> Self {
+        Self { time, translate, rotate, scale }
+    }
+
+    fn apply(&self, p: Point3) -> Point3 {
+        self.translate + self.rotate.rotate(p * self.scale)
+    }
+}
+
+// An object that moves over the shutter interval. Translation and scale are
+// interpolated linearly between keyframes and rotation is slerped, so two
+// keys more than half a turn apart take the shorter way round. Before the
+// first key and after the last the object holds still.
+pub struct AnimatedTransform {
+    pub ptr: Arc<dyn Hittable>,
+    keys: Vec<Keyframe>,
+}
+
+impl AnimatedTransform {
+    pub fn new(ptr: Arc<dyn Hittable>, mut keys: Vec<Keyframe>) -> Self {
+        assert!(!keys.is_empty(), "Animation has no keyframes");
+        keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+        assert!(
+            keys.iter().all(|k| k.scale.x != 0.0 && k.scale.y != 0.0 && k.scale.z != 0.0),
+            "Animation scale is zero"
+        );
+        assert!(
+            keys.windows(2).all(|w| (0..3).all(|a| w[0].scale[a].signum() == w[1].scale[a].signum())),
+            "Animation scale changes sign between keyframes"
+        );
+        Self { ptr, keys }
+    }
+
+    fn pose(&self, time: f64) -> Keyframe {
+        let i = self.keys.partition_point(|k| k.time <= time);
+        if i == 0 {
+            return self.keys[0];
+        }
+        if i == self.keys.len() {
+            return self.keys[i - 1];
+        }
+
+        let (a, b) = (&self.keys[i - 1], &self.keys[i]);
+        let t = (time - a.time) / (b.time - a.time);
+        Keyframe {
+            time,
+            translate: a.translate + t * (b.translate - a.translate),
+            rotate: a.rotate.slerp(&b.rotate, t),
+            scale: a.scale + t * (b.scale - a.scale),
+        }
+    }
+}
+
+impl Hittable for AnimatedTransform {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, sampler: &mut Sampler) -> Option<HitRecord> {
+        let pose = self.pose(r.time);
+        let inverse_rotate = pose.rotate.conjugate();
+        let inverse_scale = Vec3::new(1.0 / pose.scale.x, 1.0 / pose.scale.y, 1.0 / pose.scale.z);
+
+        // As with Transform, the direction keeps its length so t carries over.
+        let local_r = Ray::with_time(
+            inverse_rotate.rotate(r.origin - pose.translate) * inverse_scale,
+            inverse_rotate.rotate(r.direction) * inverse_scale,
+            r.time,
+        );
+
+        let mut rec = self.ptr.hit(&local_r, t_min, t_max, sampler)?;
+        rec.p = pose.apply(rec.p);
+        rec.normal = pose.rotate.rotate(rec.normal * inverse_scale).unit_vector();
+        Some(rec)
+    }
+
+    // Poses are sampled across the interval and every sample is padded by
+    // how far a corner can stray from the straight line between samples:
+    // at most |p''| h^2 / 8, where the rotation contributes w^2 |S c| and
+    // the rotating, changing scale 2 w |S' c|.
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+        let bbox = self.ptr.bounding_box(time0, time1)?;
+        let corners: Vec<Point3> = (0..8)
+            .map(|i| Point3::new(
+                if i & 1 == 0 { bbox.minimum.x } else { bbox.maximum.x },
+                if i & 2 == 0 { bbox.minimum.y } else { bbox.maximum.y },
+                if i & 4 == 0 { bbox.minimum.z } else { bbox.maximum.z },
+            ))
+            .collect();
+        let reach = corners.iter().map(|c| c.length()).fold(0.0, f64::max);
+
+        // The shutter ends and every key between them, so each window lies
+        // within one keyframe segment.
+        let first = self.keys[0].time;
+        let last = self.keys[self.keys.len() - 1].time;
+        let (lo, hi) = (time0.min(time1).clamp(first, last), time0.max(time1).clamp(first, last));
+        let mut times = vec![lo];
+        times.extend(self.keys.iter().map(|k| k.time).filter(|&t| t > lo && t < hi));
+        times.push(hi);
+
+        let largest = |v: Vec3| v.x.abs().max(v.y.abs()).max(v.z.abs());
+        let mut min = Point3::new(INFINITY, INFINITY, INFINITY);
+        let mut max = Point3::new(-INFINITY, -INFINITY, -INFINITY);
+        for window in times.windows(2) {
+            let (a, b) = (self.pose(window[0]), self.pose(window[1]));
+            let angle = a.rotate.angle_to(&b.rotate);
+            let curvature = angle * angle * reach * largest(a.scale).max(largest(b.scale))
+                + 2.0 * angle * reach * largest(b.scale - a.scale);
+            let pad = curvature / (8.0 * (SWEEP_STEPS * SWEEP_STEPS) as f64);
+
+            for step in 0..=SWEEP_STEPS {
+                let time = window[0] + (window[1] - window[0]) * step as f64 / SWEEP_STEPS as f64;
+                let pose = self.pose(time);
+                for corner in &corners {
+                    let p = pose.apply(*corner);
+                    for axis in 0..3 {
+                        min[axis] = min[axis].min(p[axis] - pad);
+                        max[axis] = max[axis].max(p[axis] + pad);
+                    }
+                }
+            }
+        }
+        Some(Aabb::new(min, max))
+    }
+
+    // No pdf_value or random: they take no time, so a moving emitter can't
+    // be sampled where it is for a given ray. It is still found by BSDF
+    // sampling, which is why is_emissive stays false and keeps it off the
+    // lights list.
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cuboid::Cuboid, material::Lambertian, vec3::Color};
+
+    // A box well off the origin, so rotation swings its corners wide.
+    fn spinning_box(keys: Vec<Keyframe>) -> AnimatedTransform {
+        let material = Arc::new(Lambertian::new_from_color(Color::new(0.5, 0.5, 0.5)));
+        let cuboid = Cuboid::new(Point3::new(2.0, -0.5, 1.0), Point3::new(4.0, 0.5, 1.5), material);
+        AnimatedTransform::new(Arc::new(cuboid), keys)
+    }
+
+    fn assert_bounds_every_pose(animated: &AnimatedTransform, time0: f64, time1: f64) {
+        let bbox = animated.bounding_box(time0, time1).unwrap();
+        let child = animated.ptr.bounding_box(time0, time1).unwrap();
+        let steps = 20_000;
+        for step in 0..=steps {
+            let pose = animated.pose(time0 + (time1 - time0) * step as f64 / steps as f64);
+            for i in 0..8 {
+                let corner = Point3::new(
+                    if i & 1 == 0 { child.minimum.x } else { child.maximum.x },
+                    if i & 2 == 0 { child.minimum.y } else { child.maximum.y },
+                    if i & 4 == 0 { child.minimum.z } else { child.maximum.z },
+                );
+                let p = pose.apply(corner);
+                for axis in 0..3 {
+                    assert!(
+                        p[axis] >= bbox.minimum[axis] - 1e-9 && p[axis] <= bbox.maximum[axis] + 1e-9,
+                        "corner {:?} at time {} leaves the box on axis {}",
+                        p,
+                        pose.time,
+                        axis
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn box_holds_every_pose_of_a_wide_rotation() {
+        let up = Vec3::new(0.0, 1.0, 0.0);
+        let tilted = Vec3::new(1.0, 2.0, -0.5);
+        let animated = spinning_box(vec![
+            Keyframe::new(0.0, Vec3::new(0.0, 0.0, 0.0), Quat::IDENTITY, Vec3::new(1.0, 1.0, 1.0)),
+            Keyframe::new(0.5, Vec3::new(1.0, 2.0, 0.0), Quat::from_axis_angle(up, 170.0), Vec3::new(2.0, 0.5, 1.0)),
+            Keyframe::new(1.0, Vec3::new(-1.0, 0.0, 3.0), Quat::from_axis_angle(tilted, 120.0), Vec3::new(0.5, 1.5, 3.0)),
+        ]);
+
+        assert_bounds_every_pose(&animated, 0.0, 1.0);
+        assert_bounds_every_pose(&animated, 0.1, 0.4);
+        assert_bounds_every_pose(&animated, 0.3, 0.8);
+        // Past the last key the pose holds still.
+        assert_bounds_every_pose(&animated, 0.9, 1.5);
+    }
+
+    #[test]
+    fn box_holds_a_half_turn_in_one_segment() {
+        // Just short of a half turn about a diagonal axis, with no keys in
+        // between to split the sweep.
+        let axis = Vec3::new(1.0, 1.0, 1.0);
+        let animated = spinning_box(vec![
+            Keyframe::new(0.0, Vec3::new(0.0, 0.0, 0.0), Quat::IDENTITY, Vec3::new(1.0, 1.0, 1.0)),
+            Keyframe::new(1.0, Vec3::new(0.0, 0.0, 0.0), Quat::from_axis_angle(axis, 179.0), Vec3::new(1.0, 1.0, 1.0)),
+        ]);
+        assert_bounds_every_pose(&animated, 0.0, 1.0);
+
+        // Padding keeps the box from being much bigger than the swept corners.
+        let bbox = animated.bounding_box(0.0, 1.0).unwrap();
+        let reach = Point3::new(4.0, 0.5, 1.5).length();
+        for axis in 0..3 {
+            assert!(bbox.maximum[axis] - bbox.minimum[axis] <= 2.0 * reach * 1.05);
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    bvh::{BvhNode, LinearBvh, SplitMethod},
+    hittable::Hittable,
+    ray::Ray,
+    rtweekend::{INFINITY, Sampler},
+    scene::Scene,
+    vec3::random_unit_vector,
+};
+
+// Times closest-hit queries through a recursive BvhNode over the scene's
+// objects against the same tree flattened into a LinearBvh. The rays are
+// one camera ray per pixel plus a diffuse bounce from wherever each of them
+// lands, so both coherent and incoherent rays are measured.
+pub fn bench_bvh(scene: &Scene, method: SplitMethod, sampler: &mut Sampler) {
+    let mut objects = scene.world.objects().to_vec();
+    let tree = BvhNode::with_split(&mut objects, scene.camera.time0, scene.camera.time1, method, sampler);
+    let linear = LinearBvh::from_tree(&tree);
+
+    let cam = scene.camera();
+    let width = scene.render.image_width;
+    let height = scene.render.image_height();
+    let mut rays = Vec::with_capacity(2 * (width * height) as usize);
+    for j in 0..height {
+        for i in 0..width {
+            let u = (i as f64 + sampler.random_double()) / (width - 1) as f64;
+            let v = (j as f64 + sampler.random_double()) / (height - 1) as f64;
+            let r = cam.get_ray(u, v, sampler);
+            if let Some(rec) = linear.hit(&r, 0.001, INFINITY, sampler) {
+                rays.push(Ray::with_time(rec.p, rec.normal + random_unit_vector(sampler), r.time));
+            }
+            rays.push(r);
+        }
+    }
+
+    let (tree_hits, tree_time) = trace_all(&tree, &rays);
+    let (linear_hits, linear_time) = trace_all(&linear, &rays);
+
+    println!("{} rays, {} x {} camera rays plus bounces", rays.len(), width, height);
+    println!("BvhNode    {:>8.3} Mrays/s  {}", mrays(rays.len(), tree_time), tree.stats());
+    println!("LinearBvh  {:>8.3} Mrays/s  {}", mrays(rays.len(), linear_time), linear.stats());
+    println!("speedup    {:>8.2}x", tree_time.as_secs_f64() / linear_time.as_secs_f64());
+    if tree_hits != linear_hits {
+        println!("warning: the trees disagree ({} vs {} hits)", tree_hits, linear_hits);
+    }
+}
+
+// Repeats the whole ray set until at least a second has passed.
+fn trace_all(bvh: &dyn Hittable, rays: &[Ray]) -> (usize, Duration) {
+    let start = Instant::now();
+    let mut passes = 0;
+    let mut hits = 0;
+    while passes == 0 || start.elapsed() < Duration::from_secs(1) {
+        // Both trees see the same draws, e.g. for scattering in media.
+        let sampler = &mut Sampler::new(0);
+        hits = rays.iter().filter(|r| bvh.hit(r, 0.001, INFINITY, sampler).is_some()).count();
+        passes += 1;
+    }
+    (hits, start.elapsed() / passes)
+}
+
+fn mrays(count: usize, time: Duration) -> f64 {
+    count as f64 / time.as_secs_f64() / 1e6
+}
@@ -1,23 +1,77 @@
-use std::{cmp::Ordering, sync::Arc};
+use std::{cmp::Ordering, fmt, sync::Arc};
 
-use rand::Rng;
+use clap::ValueEnum;
 
-use crate::{aabb::Aabb, hittable::{HitRecord, Hittable}, rtweekend::random_int};
+use crate::{aabb::Aabb, hittable::{HitRecord, Hittable}, ray::Ray, rtweekend::Sampler, vec3::{Point3, Vec3}};
 
+// How a BVH node divides its objects between its two children.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub enum SplitMethod {
+    // Sort along a random axis and split at the median, one or two objects per leaf.
+    Median,
+    // Binned surface area heuristic over all three axes, up to MAX_LEAF_SIZE
+    // objects per leaf.
+    Sah,
+}
+
+// SAH costs are relative to intersecting one object.
+const TRAVERSAL_COST: f64 = 1.0;
+const INTERSECTION_COST: f64 = 1.0;
+const SAH_BINS: usize = 12;
+const MAX_LEAF_SIZE: usize = 4;
 
 pub struct BvhNode {
-    left: Arc<dyn Hittable>,
-    right: Arc<dyn Hittable>,
     bbox: Aabb,
+    contents: BvhContents,
+}
+
+enum BvhContents {
+    Leaf(Vec<Arc<dyn Hittable>>),
+    // The axis the children were split along, left holding the lower side.
+    Interior { left: Box<BvhNode>, right: Box<BvhNode>, axis: usize },
+}
+
+// An object with its bounds cached for the build.
+struct BuildItem {
+    object: Arc<dyn Hittable>,
+    bbox: Aabb,
+    centroid: Point3,
 }
 
 impl BvhNode {
-    pub fn new(
+    // The median split picks its axes with `sampler`; SAH builds draw nothing.
+    pub fn with_split(
         src_objects: &mut [Arc<dyn Hittable>],
         time0: f64,
-        time1: f64
+        time1: f64,
+        method: SplitMethod,
+        sampler: &mut Sampler,
     ) -> Self {
-        let axis = random_int(0, 2);
+        assert!(!src_objects.is_empty(), "BVH needs at least one object");
+
+        match method {
+            SplitMethod::Median => Self::build_median(src_objects, time0, time1, sampler),
+            SplitMethod::Sah => Self::sah(src_objects, time0, time1),
+        }
+    }
+
+    fn sah(src_objects: &[Arc<dyn Hittable>], time0: f64, time1: f64) -> Self {
+        assert!(!src_objects.is_empty(), "BVH needs at least one object");
+
+        let mut items: Vec<BuildItem> = src_objects
+            .iter()
+            .map(|object| {
+                let bbox = object
+                    .bounding_box(time0, time1)
+                    .expect("No bounding box in BVH node");
+                BuildItem { object: Arc::clone(object), bbox, centroid: bbox.centroid() }
+            })
+            .collect();
+        Self::build_sah(&mut items)
+    }
+
+    fn build_median(src_objects: &mut [Arc<dyn Hittable>], time0: f64, time1: f64, sampler: &mut Sampler) -> Self {
+        let axis = sampler.random_int(0, 2);
         let comparator = match axis {
             0 => box_compare_x,
             1 => box_compare_y,
@@ -26,44 +80,216 @@ impl BvhNode {
 
         let object_span = src_objects.len();
 
-        let (left, right): (Arc<dyn Hittable>, Arc<dyn Hittable>) = match object_span {
-            1 => {
-                let object = Arc::clone(&src_objects[0]);
-                (object.clone(), object)
-            }
+        let contents = match object_span {
+            1 => BvhContents::Leaf(vec![Arc::clone(&src_objects[0])]),
             2 => {
                 let (a, b) = (&src_objects[0], &src_objects[1]);
                 if comparator(a, b) == Ordering::Less {
-                    (Arc::clone(a), Arc::clone(b))
+                    BvhContents::Leaf(vec![Arc::clone(a), Arc::clone(b)])
                 } else {
-                    (Arc::clone(b), Arc::clone(a))
+                    BvhContents::Leaf(vec![Arc::clone(b), Arc::clone(a)])
                 }
             }
             _ => {
-                src_objects.sort_by(|a, b| comparator(a, b));
+                src_objects.sort_by(comparator);
                 let mid = object_span / 2;
-                let left: Arc<dyn Hittable> = Arc::new(BvhNode::new(&mut src_objects[..mid], time0, time1));
-                let right: Arc<dyn Hittable>  = Arc::new(BvhNode::new(&mut src_objects[mid..], time0, time1));
-                (left, right)
+                let left = Self::build_median(&mut src_objects[..mid], time0, time1, sampler);
+                let right = Self::build_median(&mut src_objects[mid..], time0, time1, sampler);
+                BvhContents::Interior { left: Box::new(left), right: Box::new(right), axis: axis as usize }
+            }
+        };
+
+        let bbox = match &contents {
+            BvhContents::Leaf(objects) => objects
+                .iter()
+                .map(|object| object.bounding_box(time0, time1).expect("No bounding box in BVH node"))
+                .reduce(|a, b| Aabb::surrounding_box(&a, &b))
+                .unwrap(),
+            BvhContents::Interior { left, right, .. } => Aabb::surrounding_box(&left.bbox, &right.bbox),
+        };
+
+        BvhNode { bbox, contents }
+    }
+
+    // Bins the centroids along each axis and takes the cheapest plane
+    // between bins. A leaf is kept when it is small enough and no split is
+    // cheaper than intersecting everything in it.
+    fn build_sah(items: &mut [BuildItem]) -> Self {
+        let bbox = items.iter().map(|item| item.bbox).reduce(|a, b| Aabb::surrounding_box(&a, &b)).unwrap();
+        let leaf = |items: &[BuildItem]| BvhNode {
+            bbox,
+            contents: BvhContents::Leaf(items.iter().map(|item| Arc::clone(&item.object)).collect()),
+        };
+
+        if items.len() == 1 {
+            return leaf(items);
+        }
+
+        let centroid_bounds = items
+            .iter()
+            .map(|item| Aabb::new(item.centroid, item.centroid))
+            .reduce(|a, b| Aabb::surrounding_box(&a, &b))
+            .unwrap();
+
+        let mut best: Option<(f64, usize, usize)> = None; // (cost, axis, bins on the left)
+        for axis in 0..3 {
+            let lo = centroid_bounds.minimum[axis];
+            let extent = centroid_bounds.maximum[axis] - lo;
+            if extent <= 0.0 {
+                continue;
+            }
+            let bin_of = |item: &BuildItem| (((item.centroid[axis] - lo) / extent * SAH_BINS as f64) as usize).min(SAH_BINS - 1);
+
+            let mut counts = [0usize; SAH_BINS];
+            let mut bounds: [Option<Aabb>; SAH_BINS] = [None; SAH_BINS];
+            for item in items.iter() {
+                let b = bin_of(item);
+                counts[b] += 1;
+                bounds[b] = Some(bounds[b].map_or(item.bbox, |bb| Aabb::surrounding_box(&bb, &item.bbox)));
+            }
+
+            // Sweep from the right to get the area and count of every suffix.
+            let mut right_area = [0.0; SAH_BINS];
+            let mut right_count = [0usize; SAH_BINS];
+            let mut acc: Option<Aabb> = None;
+            let mut count = 0;
+            for b in (1..SAH_BINS).rev() {
+                acc = merge(acc, bounds[b]);
+                count += counts[b];
+                right_area[b] = acc.map_or(0.0, |a| a.surface_area());
+                right_count[b] = count;
+            }
+
+            let mut acc: Option<Aabb> = None;
+            let mut count = 0;
+            for split in 1..SAH_BINS {
+                acc = merge(acc, bounds[split - 1]);
+                count += counts[split - 1];
+                if count == 0 || right_count[split] == 0 {
+                    continue;
+                }
+                let left_area = acc.map_or(0.0, |a| a.surface_area());
+                let cost = TRAVERSAL_COST
+                    + INTERSECTION_COST * (left_area * count as f64 + right_area[split] * right_count[split] as f64)
+                        / bbox.surface_area();
+                if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                    best = Some((cost, axis, split));
+                }
+            }
+        }
+
+        let leaf_cost = INTERSECTION_COST * items.len() as f64;
+        let (mid, axis) = match best {
+            Some((cost, _, _)) if items.len() <= MAX_LEAF_SIZE && leaf_cost <= cost => return leaf(items),
+            Some((_, axis, split)) => {
+                let lo = centroid_bounds.minimum[axis];
+                let extent = centroid_bounds.maximum[axis] - lo;
+                let mid = partition(items, |item| {
+                    (((item.centroid[axis] - lo) / extent * SAH_BINS as f64) as usize).min(SAH_BINS - 1) < split
+                });
+                (mid, axis)
             }
+            // Every centroid is in the same place, so no plane separates them.
+            None if items.len() <= MAX_LEAF_SIZE => return leaf(items),
+            None => (items.len() / 2, 0),
         };
 
-        let box_left = left
-            .bounding_box(time0, time1)
-            .expect("No bounding box in BVH node (left)");
+        let (left, right) = items.split_at_mut(mid);
+        BvhNode {
+            bbox,
+            contents: BvhContents::Interior {
+                left: Box::new(Self::build_sah(left)),
+                right: Box::new(Self::build_sah(right)),
+                axis,
+            },
+        }
+    }
+
+    pub fn stats(&self) -> BvhStats {
+        let mut stats = BvhStats {
+            nodes: 0,
+            leaves: 0,
+            objects: 0,
+            max_depth: 0,
+            sah_cost: 0.0,
+        };
+        stats.sah_cost = self.gather_stats(&mut stats, 1);
+        stats
+    }
+
+    // Returns the SAH cost of the subtree, relative to its own box.
+    fn gather_stats(&self, stats: &mut BvhStats, depth: usize) -> f64 {
+        stats.nodes += 1;
+        stats.max_depth = stats.max_depth.max(depth);
+        match &self.contents {
+            BvhContents::Leaf(objects) => {
+                stats.leaves += 1;
+                stats.objects += objects.len();
+                INTERSECTION_COST * objects.len() as f64
+            }
+            BvhContents::Interior { left, right, .. } => {
+                let area = self.bbox.surface_area();
+                let left_cost = left.gather_stats(stats, depth + 1);
+                let right_cost = right.gather_stats(stats, depth + 1);
+                if area > 0.0 {
+                    TRAVERSAL_COST
+                        + (left.bbox.surface_area() * left_cost + right.bbox.surface_area() * right_cost) / area
+                } else {
+                    TRAVERSAL_COST + left_cost + right_cost
+                }
+            }
+        }
+    }
+}
+
+// Tree-quality summary for comparing builds. The SAH cost is the expected
+// cost of tracing a ray through the tree that hits its root box, in units
+// of one object intersection.
+pub struct BvhStats {
+    pub nodes: usize,
+    pub leaves: usize,
+    pub objects: usize,
+    pub max_depth: usize,
+    pub sah_cost: f64,
+}
 
-        let box_right = right
-            .bounding_box(time0, time1)
-            .expect("No bounding box in BVH node (right)");
+impl fmt::Display for BvhStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} nodes, {} leaves, {} objects ({:.2} per leaf), depth {}, SAH cost {:.2}",
+            self.nodes,
+            self.leaves,
+            self.objects,
+            self.objects as f64 / self.leaves as f64,
+            self.max_depth,
+            self.sah_cost,
+        )
+    }
+}
 
-        let bbox = Aabb::surrounding_box(&box_left, &box_right);
+fn merge(a: Option<Aabb>, b: Option<Aabb>) -> Option<Aabb> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(Aabb::surrounding_box(&a, &b)),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
 
-        BvhNode { left, right, bbox }
+// Moves the items satisfying `left` to the front and returns how many there are.
+fn partition(items: &mut [BuildItem], left: impl Fn(&BuildItem) -> bool) -> usize {
+    let mut mid = 0;
+    for i in 0..items.len() {
+        if left(&items[i]) {
+            items.swap(i, mid);
+            mid += 1;
+        }
     }
+    mid
 }
 
 impl Hittable for BvhNode {
-    fn hit(&self, r: &crate::ray::Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, sampler: &mut Sampler) -> Option<HitRecord> {
        if !self.bbox.hit(r, t_min, t_max) {
             return None;
         }
@@ -71,23 +297,196 @@ impl Hittable for BvhNode {
         let mut hit_record = None;
         let mut closest_so_far = t_max;
 
-        if let Some(hit) = self.left.hit(r, t_min, closest_so_far) {
-            closest_so_far = hit.t;
-            hit_record = Some(hit);
-        }
+        match &self.contents {
+            BvhContents::Leaf(objects) => {
+                for object in objects {
+                    if let Some(hit) = object.hit(r, t_min, closest_so_far, sampler) {
+                        closest_so_far = hit.t;
+                        hit_record = Some(hit);
+                    }
+                }
+            }
+            BvhContents::Interior { left, right, .. } => {
+                if let Some(hit) = left.hit(r, t_min, closest_so_far, sampler) {
+                    closest_so_far = hit.t;
+                    hit_record = Some(hit);
+                }
 
-        if let Some(hit) = self.right.hit(r, t_min, closest_so_far) {
-            hit_record = Some(hit);
+                if let Some(hit) = right.hit(r, t_min, closest_so_far, sampler) {
+                    hit_record = Some(hit);
+                }
+            }
         }
 
         hit_record
     }
 
-    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
         Some(self.bbox)
     }
 }
 
+// A BvhNode tree flattened into one array in depth-first order, so a node's
+// first child directly follows it. Leaves refer to a range of one shared
+// object list instead of owning their objects, and hits walk the array with
+// an explicit stack, visiting the child on the ray's side of the split first.
+pub struct LinearBvh {
+    nodes: Vec<LinearNode>,
+    objects: Vec<Arc<dyn Hittable>>,
+    stats: BvhStats,
+}
+
+#[derive(Clone, Copy)]
+struct LinearNode {
+    bbox: Aabb,
+    // Leaves: objects[offset..offset + count]. Interior nodes: the first
+    // child is the next node and the second is nodes[offset].
+    offset: u32,
+    count: u16, // zero for interior nodes
+    axis: u8,
+}
+
+// Entries of the traversal stack; a deeper tree finishes its far subtrees
+// recursively once the stack is full.
+const STACK_SIZE: usize = 64;
+
+impl LinearBvh {
+    pub fn new(src_objects: &mut [Arc<dyn Hittable>], time0: f64, time1: f64) -> Self {
+        Self::from_tree(&BvhNode::sah(src_objects, time0, time1))
+    }
+
+    pub fn with_split(
+        src_objects: &mut [Arc<dyn Hittable>],
+        time0: f64,
+        time1: f64,
+        method: SplitMethod,
+        sampler: &mut Sampler,
+    ) -> Self {
+        Self::from_tree(&BvhNode::with_split(src_objects, time0, time1, method, sampler))
+    }
+
+    pub fn from_tree(root: &BvhNode) -> Self {
+        let mut bvh = LinearBvh {
+            nodes: Vec::new(),
+            objects: Vec::new(),
+            stats: root.stats(),
+        };
+        bvh.flatten(root);
+        bvh
+    }
+
+    fn flatten(&mut self, node: &BvhNode) -> usize {
+        let index = self.nodes.len();
+        self.nodes.push(LinearNode { bbox: node.bbox, offset: 0, count: 0, axis: 0 });
+
+        match &node.contents {
+            BvhContents::Leaf(objects) => {
+                self.nodes[index].offset = self.objects.len() as u32;
+                self.nodes[index].count = objects.len() as u16;
+                self.objects.extend(objects.iter().map(Arc::clone));
+            }
+            BvhContents::Interior { left, right, axis } => {
+                self.flatten(left);
+                let second = self.flatten(right);
+                self.nodes[index].offset = second as u32;
+                self.nodes[index].axis = *axis as u8;
+            }
+        }
+        index
+    }
+
+    pub fn stats(&self) -> &BvhStats {
+        &self.stats
+    }
+
+    // Every object in the tree, in leaf order.
+    pub fn objects(&self) -> &[Arc<dyn Hittable>] {
+        &self.objects
+    }
+
+    fn traverse(
+        &self,
+        root: usize,
+        r: &Ray,
+        t_min: f64,
+        closest: &mut f64,
+        hit_record: &mut Option<HitRecord>,
+        sampler: &mut Sampler,
+    ) {
+        let inv_dir = [1.0 / r.direction.x, 1.0 / r.direction.y, 1.0 / r.direction.z];
+        let mut stack = [0usize; STACK_SIZE];
+        let mut stack_len = 0;
+        let mut index = root;
+
+        loop {
+            let node = &self.nodes[index];
+            if node.bbox.hit_with_inverse(&r.origin, &inv_dir, t_min, *closest) {
+                if node.count > 0 {
+                    let start = node.offset as usize;
+                    for object in &self.objects[start..start + node.count as usize] {
+                        if let Some(hit) = object.hit(r, t_min, *closest, sampler) {
+                            *closest = hit.t;
+                            *hit_record = Some(hit);
+                        }
+                    }
+                } else {
+                    // The left child holds the lower side of the split, so a
+                    // ray going down the axis meets the right one first.
+                    let (near, far) = if inv_dir[node.axis as usize] < 0.0 {
+                        (node.offset as usize, index + 1)
+                    } else {
+                        (index + 1, node.offset as usize)
+                    };
+                    if stack_len < STACK_SIZE {
+                        stack[stack_len] = far;
+                        stack_len += 1;
+                        index = near;
+                    } else {
+                        self.traverse(near, r, t_min, closest, hit_record, sampler);
+                        index = far;
+                    }
+                    continue;
+                }
+            }
+
+            if stack_len == 0 {
+                break;
+            }
+            stack_len -= 1;
+            index = stack[stack_len];
+        }
+    }
+}
+
+impl Hittable for LinearBvh {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, sampler: &mut Sampler) -> Option<HitRecord> {
+        let mut closest = t_max;
+        let mut hit_record = None;
+        self.traverse(0, r, t_min, &mut closest, &mut hit_record, sampler);
+        hit_record
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        Some(self.nodes[0].bbox)
+    }
+
+    // Sampled like a HittableList, so an instanced multi-part prototype
+    // with an emissive override can be a light.
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, sampler: &mut Sampler) -> f64 {
+        let weight = 1.0 / self.objects.len() as f64;
+        self.objects.iter().map(|object| weight * object.pdf_value(origin, direction, sampler)).sum()
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut Sampler) -> Vec3 {
+        let idx = sampler.random_int(0, self.objects.len() as i32 - 1) as usize;
+        self.objects[idx].random(origin, sampler)
+    }
+
+    fn can_sample(&self) -> bool {
+        !self.objects.is_empty() && self.objects.iter().all(|object| object.can_sample())
+    }
+}
+
 fn box_compare(a: &Arc<dyn Hittable>, b: &Arc<dyn Hittable>, axis: usize) -> Ordering {
     let box_a = a
         .bounding_box(0.0, 0.0)
@@ -112,3 +511,142 @@ pub fn box_compare_y(a: &Arc<dyn Hittable>, b: &Arc<dyn Hittable>) -> Ordering {
 pub fn box_compare_z(a: &Arc<dyn Hittable>, b: &Arc<dyn Hittable>) -> Ordering {
     box_compare(a, b, 2)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        material::Lambertian,
+        rtweekend::{INFINITY, Sampler},
+        sphere::Sphere,
+        vec3::{random_unit_vector, Color, Vec3},
+    };
+
+    fn random_point(extent: f64, sampler: &mut Sampler) -> Point3 {
+        Point3::new(
+            sampler.random_double_range(-extent, extent),
+            sampler.random_double_range(-extent, extent),
+            sampler.random_double_range(-extent, extent),
+        )
+    }
+
+    fn spheres(n: usize, sampler: &mut Sampler) -> Vec<Arc<dyn Hittable>> {
+        let material = Arc::new(Lambertian::new_from_color(Color::new(0.5, 0.5, 0.5)));
+        (0..n)
+            .map(|_| Arc::new(Sphere::new(random_point(10.0, sampler), sampler.random_double_range(0.1, 0.8), material.clone())) as Arc<dyn Hittable>)
+            .collect()
+    }
+
+    #[test]
+    fn split_methods_find_the_same_hits() {
+        let mut sampler = Sampler::new(1);
+        let objects = spheres(300, &mut sampler);
+        let sah = BvhNode::with_split(&mut objects.clone(), 0.0, 1.0, SplitMethod::Sah, &mut sampler);
+        let median = BvhNode::with_split(&mut objects.clone(), 0.0, 1.0, SplitMethod::Median, &mut sampler);
+
+        let rays = (0..5000).map(|_| Ray::new(random_point(15.0, &mut sampler), random_unit_vector(&mut sampler)));
+        assert!(assert_same_hits(&sah, &median, rays) > 500);
+    }
+
+    fn assert_same_hits(a: &dyn Hittable, b: &dyn Hittable, rays: impl Iterator<Item = Ray>) -> usize {
+        let mut sampler = Sampler::new(0);
+        let mut hits = 0;
+        for r in rays {
+            match (a.hit(&r, 0.001, INFINITY, &mut sampler), b.hit(&r, 0.001, INFINITY, &mut sampler)) {
+                (Some(a), Some(b)) => {
+                    assert_eq!(a.t, b.t);
+                    assert_eq!(a.normal, b.normal);
+                    hits += 1;
+                }
+                (None, None) => {}
+                _ => panic!("only one tree was hit"),
+            }
+        }
+        hits
+    }
+
+    #[test]
+    fn flattened_tree_matches_the_recursive_one() {
+        let mut sampler = Sampler::new(3);
+        let mut objects = spheres(300, &mut sampler);
+        for method in [SplitMethod::Sah, SplitMethod::Median] {
+            let tree = BvhNode::with_split(&mut objects, 0.0, 1.0, method, &mut sampler);
+            let linear = LinearBvh::from_tree(&tree);
+            let rays = (0..5000).map(|_| Ray::new(random_point(15.0, &mut sampler), random_unit_vector(&mut sampler)));
+            assert!(assert_same_hits(&tree, &linear, rays) > 500);
+        }
+    }
+
+    // A chain far deeper than the traversal stack: each level splits off
+    // one sphere on its lower side, so a ray going down x meets the rest of
+    // the chain first and leaves every sphere on the stack.
+    fn chain(objects: &[Arc<dyn Hittable>]) -> BvhNode {
+        let leaf = |object: &Arc<dyn Hittable>| BvhNode {
+            bbox: object.bounding_box(0.0, 1.0).unwrap(),
+            contents: BvhContents::Leaf(vec![Arc::clone(object)]),
+        };
+        if objects.len() == 1 {
+            return leaf(&objects[0]);
+        }
+        let left = leaf(&objects[0]);
+        let right = chain(&objects[1..]);
+        BvhNode {
+            bbox: Aabb::surrounding_box(&left.bbox, &right.bbox),
+            contents: BvhContents::Interior { left: Box::new(left), right: Box::new(right), axis: 0 },
+        }
+    }
+
+    #[test]
+    fn deep_trees_fall_back_to_recursion() {
+        let mut sampler = Sampler::new(4);
+        let material = Arc::new(Lambertian::new_from_color(Color::new(0.5, 0.5, 0.5)));
+        let objects: Vec<Arc<dyn Hittable>> = (0..4 * STACK_SIZE)
+            .map(|i| Arc::new(Sphere::new(Point3::new(i as f64, 0.0, 0.0), 0.3, material.clone())) as Arc<dyn Hittable>)
+            .collect();
+        let tree = chain(&objects);
+        assert!(tree.stats().max_depth > 2 * STACK_SIZE);
+        let linear = LinearBvh::from_tree(&tree);
+
+        // Going up x each level's sphere is visited first and the stack
+        // stays short; going down it overflows.
+        let up = Ray::new(Point3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!((linear.hit(&up, 0.001, INFINITY, &mut sampler).unwrap().t - 4.7).abs() < 1e-9);
+        let down = Ray::new(Point3::new(1000.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        let last = (4 * STACK_SIZE - 1) as f64;
+        assert!((linear.hit(&down, 0.001, INFINITY, &mut sampler).unwrap().t - (1000.0 - last - 0.3)).abs() < 1e-9);
+
+        // Rays coming down the axis toward spheres anywhere along it.
+        let rays = (0..2000).map(|_| {
+            let origin = Point3::new(400.0, sampler.random_double_range(-0.2, 0.2), sampler.random_double_range(-0.2, 0.2));
+            let target = Point3::new(sampler.random_double_range(0.0, 300.0), sampler.random_double_range(-0.5, 0.5), sampler.random_double_range(-0.5, 0.5));
+            Ray::new(origin, target - origin)
+        });
+        assert!(assert_same_hits(&tree, &linear, rays) > 1000);
+    }
+
+    #[test]
+    fn stats_describe_the_tree() {
+        let mut sampler = Sampler::new(2);
+        let objects = spheres(500, &mut sampler);
+        for method in [SplitMethod::Sah, SplitMethod::Median] {
+            let stats = BvhNode::with_split(&mut objects.clone(), 0.0, 1.0, method, &mut sampler).stats();
+            assert_eq!(stats.objects, 500);
+            // Every interior node has two children.
+            assert_eq!(stats.nodes, 2 * stats.leaves - 1);
+            assert!(stats.max_depth as f64 >= (stats.leaves as f64).log2() + 1.0);
+            assert!(stats.max_depth < 500);
+            assert!(stats.sah_cost > INTERSECTION_COST && stats.sah_cost < 500.0);
+        }
+
+        // Median leaves hold one or two objects, SAH leaves up to MAX_LEAF_SIZE.
+        let median = BvhNode::with_split(&mut objects.clone(), 0.0, 1.0, SplitMethod::Median, &mut sampler).stats();
+        assert!(median.leaves >= 250);
+        let sah = BvhNode::with_split(&mut objects.clone(), 0.0, 1.0, SplitMethod::Sah, &mut sampler).stats();
+        assert!(sah.leaves >= 500 / MAX_LEAF_SIZE);
+        assert!(sah.sah_cost <= median.sah_cost, "{} vs {}", sah.sah_cost, median.sah_cost);
+
+        let single = BvhNode::with_split(&mut objects[..1].to_vec(), 0.0, 1.0, SplitMethod::Sah, &mut sampler).stats();
+        assert_eq!((single.nodes, single.leaves, single.objects, single.max_depth), (1, 1, 1, 1));
+        assert_eq!(single.sah_cost, INTERSECTION_COST);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{ray::Ray, rtweekend::{degrees_to_radians, random_double_range}, vec3::{cross, random_in_unit_disk, unit_vector, Point3, Vec3}};
+use crate::{ray::Ray, rtweekend::{degrees_to_radians, Sampler}, vec3::{cross, random_in_unit_disk, unit_vector, Point3, Vec3}};
 
 pub struct Camera {
     origin: Point3,
@@ -55,11 +55,11 @@ impl Camera {
         }
     }
 
-    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
-        let rd: Vec3 = self.lens_radius * random_in_unit_disk();
+    pub fn get_ray(&self, s: f64, t: f64, sampler: &mut Sampler) -> Ray {
+        let rd: Vec3 = self.lens_radius * random_in_unit_disk(sampler);
         let offset: Vec3 = self.u * rd.x + self.v * rd.y;
 
-        let time = random_double_range(self.time0, self.time1);
+        let time = sampler.random_double_range(self.time0, self.time1);
 
         Ray::with_time(
             self.origin + offset,
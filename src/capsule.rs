@@ -0,0 +1,190 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    material::Material,
+    onb::Onb,
+    ray::Ray,
+    rtweekend::{PI, Sampler},
+    vec3::{dot, Point3, Vec3},
+};
+
+// Every point within `radius` of the segment from p0 to p1: a cylinder
+// with a hemisphere on each end. Hits are found with p0 at the origin and
+// the axis along local z. u goes around the axis and v runs the length of
+// the profile from the pole below p0 to the one above p1.
+pub struct Capsule {
+    pub p0: Point3,
+    pub p1: Point3,
+    pub radius: f64,
+    pub material: Arc<dyn Material>,
+    uvw: Onb,
+    length: f64,
+}
+
+impl Capsule {
+    pub fn new(p0: Point3, p1: Point3, radius: f64, material: Arc<dyn Material>) -> Self {
+        Self {
+            p0,
+            p1,
+            radius,
+            material,
+            uvw: Onb::build_from_w(p1 - p0),
+            length: (p1 - p0).length(),
+        }
+    }
+}
+
+// Both crossings of a sphere centered on the axis at height z.
+fn sphere_roots(o: Vec3, d: Vec3, z: f64, radius: f64) -> Option<[f64; 2]> {
+    let oc = o - Vec3::new(0.0, 0.0, z);
+    let a = dot(&d, &d);
+    let half_b = dot(&oc, &d);
+    let c = dot(&oc, &oc) - radius * radius;
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let sqrt_d = discriminant.sqrt();
+    Some([(-half_b - sqrt_d) / a, (-half_b + sqrt_d) / a])
+}
+
+impl Hittable for Capsule {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, _sampler: &mut Sampler) -> Option<HitRecord> {
+        let o = self.uvw.to_local(r.origin - self.p0);
+        let d = self.uvw.to_local(r.direction);
+        let z_at = |t: f64| o.z + t * d.z;
+
+        // Each piece only counts on its own side of the segment's ends.
+        let mut candidates: Vec<f64> = Vec::with_capacity(6);
+        let a = d.x * d.x + d.y * d.y;
+        if a > 1e-12 {
+            let half_b = o.x * d.x + o.y * d.y;
+            let c = o.x * o.x + o.y * o.y - self.radius * self.radius;
+            let discriminant = half_b * half_b - a * c;
+            if discriminant >= 0.0 {
+                let sqrt_d = discriminant.sqrt();
+                for t in [(-half_b - sqrt_d) / a, (-half_b + sqrt_d) / a] {
+                    if (0.0..=self.length).contains(&z_at(t)) {
+                        candidates.push(t);
+                    }
+                }
+            }
+        }
+        if let Some(roots) = sphere_roots(o, d, 0.0, self.radius) {
+            candidates.extend(roots.into_iter().filter(|&t| z_at(t) <= 0.0));
+        }
+        if let Some(roots) = sphere_roots(o, d, self.length, self.radius) {
+            candidates.extend(roots.into_iter().filter(|&t| z_at(t) >= self.length));
+        }
+
+        let t = candidates
+            .into_iter()
+            .filter(|&t| t >= t_min && t <= t_max)
+            .min_by(|a, b| a.total_cmp(b))?;
+
+        // The normal points away from the nearest point of the segment.
+        let p = o + t * d;
+        let nearest = Vec3::new(0.0, 0.0, p.z.clamp(0.0, self.length));
+        let outward_normal = (p - nearest) / self.radius;
+
+        let quarter = PI * self.radius / 2.0;
+        let along = if p.z < 0.0 {
+            self.radius * (-outward_normal.z).clamp(-1.0, 1.0).acos()
+        } else if p.z > self.length {
+            quarter + self.length + self.radius * outward_normal.z.clamp(-1.0, 1.0).asin()
+        } else {
+            quarter + p.z
+        };
+
+        let mut rec = HitRecord {
+            t,
+            p: r.at(t),
+            u: (p.y.atan2(p.x) + PI) / (2.0 * PI),
+            v: along / (2.0 * quarter + self.length),
+            normal: Vec3::ZERO,
+            front_face: false,
+            color: None,
+            material: Arc::clone(&self.material),
+        };
+        rec.set_face_normal(r, self.uvw.local_vec(outward_normal).unit_vector());
+
+        Some(rec)
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        let extent = Vec3::new(self.radius, self.radius, self.radius);
+        Some(Aabb::new(
+            Point3::new(self.p0.x.min(self.p1.x), self.p0.y.min(self.p1.y), self.p0.z.min(self.p1.z)) - extent,
+            Point3::new(self.p0.x.max(self.p1.x), self.p0.y.max(self.p1.y), self.p0.z.max(self.p1.z)) + extent,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Lambertian, rtweekend::INFINITY, vec3::Color};
+
+    // Upright from y = 0 to y = 2 with radius 1, so it spans y = -1 to 3.
+    fn capsule() -> Capsule {
+        let material = Arc::new(Lambertian::new_from_color(Color::new(0.5, 0.5, 0.5)));
+        Capsule::new(Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 2.0, 0.0), 1.0, material)
+    }
+
+    fn across_at(y: f64) -> Ray {
+        Ray::new(Point3::new(-5.0, y, 0.0), Vec3::new(1.0, 0.0, 0.0))
+    }
+
+    #[test]
+    fn hits_the_body_and_both_caps() {
+        let mut sampler = Sampler::new(0);
+        let rec = capsule().hit(&across_at(1.0), 0.001, INFINITY, &mut sampler).unwrap();
+        assert!((rec.t - 4.0).abs() < 1e-9);
+        assert!((rec.normal - Vec3::new(-1.0, 0.0, 0.0)).length() < 1e-9);
+        assert!((rec.v - 0.5).abs() < 1e-9);
+
+        let down = Ray::new(Point3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let rec = capsule().hit(&down, 0.001, INFINITY, &mut sampler).unwrap();
+        assert!((rec.t - 2.0).abs() < 1e-9);
+        assert!((rec.normal - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-9);
+        assert!((rec.v - 1.0).abs() < 1e-9);
+
+        let up = Ray::new(Point3::new(0.0, -5.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let rec = capsule().hit(&up, 0.001, INFINITY, &mut sampler).unwrap();
+        assert!((rec.t - 4.0).abs() < 1e-9);
+        assert!(rec.v.abs() < 1e-9);
+    }
+
+    #[test]
+    fn hemisphere_hits_off_the_axis() {
+        let mut sampler = Sampler::new(0);
+        // At y = 2.5 the top cap is sqrt(0.75) wide.
+        let rec = capsule().hit(&across_at(2.5), 0.001, INFINITY, &mut sampler).unwrap();
+        assert!((rec.t - (5.0 - 0.75_f64.sqrt())).abs() < 1e-9);
+        assert!(rec.normal.y > 0.0 && rec.front_face);
+    }
+
+    #[test]
+    fn inside_hits_are_back_faces() {
+        let mut sampler = Sampler::new(0);
+        let r = Ray::new(Point3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let rec = capsule().hit(&r, 0.001, INFINITY, &mut sampler).unwrap();
+        assert!((rec.t - 2.0).abs() < 1e-9);
+        assert!(!rec.front_face);
+    }
+
+    #[test]
+    fn misses_and_grazing() {
+        let mut sampler = Sampler::new(0);
+        assert!(capsule().hit(&across_at(3.01), 0.001, INFINITY, &mut sampler).is_none());
+        assert!(capsule().hit(&across_at(-1.01), 0.001, INFINITY, &mut sampler).is_none());
+        // Tangent to the body at x = 1 and just outside it.
+        let tangent = Ray::new(Point3::new(1.0, 1.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let rec = capsule().hit(&tangent, 0.001, INFINITY, &mut sampler).unwrap();
+        assert!((rec.t - 5.0).abs() < 1e-6);
+        let outside = Ray::new(Point3::new(1.0 + 1e-6, 1.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(capsule().hit(&outside, 0.001, INFINITY, &mut sampler).is_none());
+    }
+}
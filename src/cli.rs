@@ -0,0 +1,202 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use crate::{bvh::SplitMethod, color::ToneMap, output::BitDepth, scene::Scene, vec3::Vec3};
+
+#[derive(Parser)]
+#[command(about = "Ray tracer from the Ray Tracing in One Weekend series")]
+pub struct Cli {
+    /// Built-in scene to render (see --list-scenes)
+    #[arg(long, default_value = "cornell_box", conflicts_with = "scene_file")]
+    pub scene: String,
+
+    /// Render a TOML scene description instead of a built-in scene
+    #[arg(long, value_name = "PATH")]
+    pub scene_file: Option<PathBuf>,
+
+    /// Print the built-in scenes and exit
+    #[arg(long)]
+    pub list_scenes: bool,
+
+    /// Image width in pixels
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub width: Option<u32>,
+
+    /// Image height in pixels; together with --width this sets the aspect ratio
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub height: Option<u32>,
+
+    /// Samples per pixel
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub spp: Option<u32>,
+
+    /// Hard cap on the number of ray bounces
+    #[arg(long)]
+    pub max_depth: Option<u32>,
+
+    /// Bounces before Russian roulette may end a path
+    #[arg(long)]
+    pub min_bounces: Option<u32>,
+
+    /// Tone mapping operator for display formats
+    #[arg(long, value_enum)]
+    pub tone_map: Option<ToneMap>,
+
+    /// Exposure adjustment in stops
+    #[arg(long, allow_hyphen_values = true)]
+    pub exposure: Option<f64>,
+
+    /// Luminance that maps to white with reinhard-extended
+    #[arg(long)]
+    pub white_point: Option<f64>,
+
+    /// Output image path; the extension picks png, jpg, tif, exr, hdr or ppm
+    /// (PPM on stdout when omitted)
+    #[arg(long, short)]
+    pub output: Option<PathBuf>,
+
+    /// Bits per channel for PNG and TIFF output (JPEG only takes 8)
+    #[arg(long, value_enum, default_value = "8")]
+    pub bit_depth: BitDepth,
+
+    /// Number of render threads (all cores when omitted)
+    #[arg(long)]
+    pub threads: Option<usize>,
+
+    /// Seed for the random number generator; the same seed renders the same image
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+
+    /// How the scene BVH splits its objects
+    #[arg(long, value_enum, default_value = "sah")]
+    pub bvh: SplitMethod,
+
+    /// Print node count, depth and SAH cost of the scene BVH before rendering
+    #[arg(long)]
+    pub bvh_stats: bool,
+
+    /// Compare ray throughput of the flattened and recursive BVHs on the
+    /// scene instead of rendering it
+    #[arg(long)]
+    pub bench_bvh: bool,
+
+    /// Camera position as x,y,z
+    #[arg(long, value_parser = parse_vec3, allow_hyphen_values = true)]
+    pub lookfrom: Option<Vec3>,
+
+    /// Camera target as x,y,z
+    #[arg(long, value_parser = parse_vec3, allow_hyphen_values = true)]
+    pub lookat: Option<Vec3>,
+
+    /// Vertical field of view in degrees
+    #[arg(long)]
+    pub vfov: Option<f64>,
+
+    /// Lens aperture
+    #[arg(long)]
+    pub aperture: Option<f64>,
+
+    /// Focus distance
+    #[arg(long)]
+    pub focus_dist: Option<f64>,
+}
+
+impl Cli {
+    // Command-line values win over the defaults the scene was built with.
+    // Fails when the resulting image would have no pixels.
+    pub fn apply(&self, scene: &mut Scene) -> Result<(), String> {
+        let render = &mut scene.render;
+        match (self.width, self.height) {
+            (Some(width), Some(height)) => {
+                render.image_width = width as i32;
+                render.aspect_ratio = width as f64 / height as f64;
+            }
+            (Some(width), None) => render.image_width = width as i32,
+            (None, Some(height)) => render.image_width = (height as f64 * render.aspect_ratio) as i32,
+            (None, None) => {}
+        }
+        if render.image_width < 1 || render.image_height() < 1 {
+            return Err(format!(
+                "a {}x{} image has no pixels; adjust --width or --height",
+                render.image_width,
+                render.image_height()
+            ));
+        }
+        if let Some(spp) = self.spp {
+            render.samples_per_pixel = spp;
+        }
+        if let Some(max_depth) = self.max_depth {
+            render.max_depth = max_depth;
+        }
+        if let Some(min_bounces) = self.min_bounces {
+            render.min_bounces = min_bounces;
+        }
+        if let Some(tone_map) = self.tone_map {
+            render.display.tone_map = tone_map;
+        }
+        if let Some(exposure) = self.exposure {
+            render.display.exposure = exposure;
+        }
+        if let Some(white_point) = self.white_point {
+            render.display.white_point = white_point;
+        }
+
+        let camera = &mut scene.camera;
+        if let Some(lookfrom) = self.lookfrom {
+            camera.lookfrom = lookfrom;
+        }
+        if let Some(lookat) = self.lookat {
+            camera.lookat = lookat;
+        }
+        if let Some(vfov) = self.vfov {
+            camera.vfov = vfov;
+        }
+        if let Some(aperture) = self.aperture {
+            camera.aperture = aperture;
+        }
+        if let Some(focus_dist) = self.focus_dist {
+            camera.focus_dist = focus_dist;
+        }
+        Ok(())
+    }
+}
+
+fn parse_vec3(s: &str) -> Result<Vec3, String> {
+    let parts: Vec<&str> = s.split(',').collect();
+    if parts.len() != 3 {
+        return Err(format!("expected x,y,z but got '{}'", s));
+    }
+
+    let mut v = Vec3::ZERO;
+    for (i, part) in parts.iter().enumerate() {
+        v[i] = part.trim().parse().map_err(|_| format!("'{}' is not a number", part))?;
+    }
+    Ok(v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rtweekend::Sampler, scenes};
+
+    #[test]
+    fn sizes_and_sample_counts_must_be_positive() {
+        for arg in ["--width=0", "--width=-8", "--height=0", "--spp=0"] {
+            assert!(Cli::try_parse_from(["render", arg]).is_err());
+        }
+        assert!(Cli::try_parse_from(["render", "--width", "1", "--height", "1", "--spp", "1"]).is_ok());
+    }
+
+    #[test]
+    fn a_derived_size_of_zero_is_rejected() {
+        let mut scene = scenes::cornell_box(&mut Sampler::new(0));
+        scene.render.aspect_ratio = 2.0;
+        let cli = Cli::try_parse_from(["render", "--width", "1"]).unwrap();
+        assert!(cli.apply(&mut scene).is_err());
+
+        let cli = Cli::try_parse_from(["render", "--width", "1", "--height", "1"]).unwrap();
+        assert!(cli.apply(&mut scene).is_ok());
+        assert_eq!((scene.render.image_width, scene.render.image_height()), (1, 1));
+    }
+}
@@ -1,23 +1,187 @@
-use crate::{rtweekend::clamp, vec3::Color};
+use crate::{rtweekend::clamp, vec3::{dot, Color}};
+use clap::ValueEnum;
+use serde::Deserialize;
 use std::io::{self, Write};
 
-pub fn write_color(mut out: impl Write, pixel_color: Color, samples_per_pixel: u32) -> io::Result<()> {
-    let mut r = pixel_color.x;
-    let mut g = pixel_color.y;
-    let mut b = pixel_color.z;
+#[derive(Clone, Copy, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ToneMap {
+    Clamp,
+    Reinhard,
+    ReinhardExtended,
+    Aces,
+    Agx,
+}
+
+// How linear radiance is turned into a displayable color.
+#[derive(Clone, Copy)]
+pub struct DisplaySettings {
+    pub tone_map: ToneMap,
+    pub exposure: f64,    // in stops
+    pub white_point: f64, // luminance mapped to white by extended Reinhard
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        Self {
+            tone_map: ToneMap::Clamp,
+            exposure: 0.0,
+            white_point: 4.0,
+        }
+    }
+}
+
+// Divide the color by number of samples, apply exposure and tone mapping and
+// encode with the sRGB transfer function, clamped to [0, 1].
+pub fn encoded_color(pixel_color: Color, samples_per_pixel: u32, display: &DisplaySettings) -> Color {
+    let scale = 2.0_f64.powf(display.exposure) / samples_per_pixel as f64;
+    let mapped = tone_map(scale * pixel_color, display);
+
+    Color::new(
+        clamp(linear_to_srgb(mapped.x), 0.0, 1.0),
+        clamp(linear_to_srgb(mapped.y), 0.0, 1.0),
+        clamp(linear_to_srgb(mapped.z), 0.0, 1.0),
+    )
+}
 
-    // Divide the color by number of samples and gamma-correct for gamma=2.0.
-    let scale = 1.0 / samples_per_pixel as f64;
+// The encoded color clamped to [0, 0.999] so it scales by 256 straight to
+// 8-bit channel values.
+pub fn display_color(pixel_color: Color, samples_per_pixel: u32, display: &DisplaySettings) -> Color {
+    let c = encoded_color(pixel_color, samples_per_pixel, display);
+    Color::new(c.x.min(0.999), c.y.min(0.999), c.z.min(0.999))
+}
 
-    r = (scale * r).sqrt();
-    g = (scale * g).sqrt();
-    b = (scale * b).sqrt();
+pub fn write_color(
+    mut out: impl Write,
+    pixel_color: Color,
+    samples_per_pixel: u32,
+    display: &DisplaySettings,
+) -> io::Result<()> {
+    let c = display_color(pixel_color, samples_per_pixel, display);
 
     writeln!(
         out,
         "{} {} {}",
-        (256.0 * clamp(r, 0.0, 0.999)) as i32,
-        (256.0 * clamp(g, 0.0, 0.999)) as i32,
-        (256.0 * clamp(b, 0.0, 0.999)) as i32,
+        (256.0 * c.x) as i32,
+        (256.0 * c.y) as i32,
+        (256.0 * c.z) as i32,
+    )
+}
+
+pub fn luminance(c: Color) -> f64 {
+    dot(&c, &Color::new(0.2126, 0.7152, 0.0722))
+}
+
+// Piecewise sRGB OETF (IEC 61966-2-1).
+pub fn linear_to_srgb(x: f64) -> f64 {
+    if x <= 0.0031308 {
+        12.92 * x
+    } else {
+        1.055 * x.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// Inverse of linear_to_srgb, for decoding 8-bit color textures.
+pub fn srgb_to_linear(x: f64) -> f64 {
+    if x <= 0.04045 {
+        x / 12.92
+    } else {
+        ((x + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// Maps linear radiance to linear display values in [0, 1].
+pub fn tone_map(c: Color, display: &DisplaySettings) -> Color {
+    // NaNs and negative values from bad samples would poison every operator.
+    let c = Color::new(sanitize(c.x), sanitize(c.y), sanitize(c.z));
+
+    match display.tone_map {
+        ToneMap::Clamp => c,
+        ToneMap::Reinhard => scale_luminance(c, |l| l / (1.0 + l)),
+        ToneMap::ReinhardExtended => {
+            let white_sq = display.white_point * display.white_point;
+            scale_luminance(c, |l| l * (1.0 + l / white_sq) / (1.0 + l))
+        }
+        ToneMap::Aces => aces_fitted(c),
+        ToneMap::Agx => agx(c),
+    }
+}
+
+fn sanitize(x: f64) -> f64 {
+    if x.is_nan() { 0.0 } else { x.max(0.0) }
+}
+
+fn scale_luminance(c: Color, curve: impl Fn(f64) -> f64) -> Color {
+    let l = luminance(c);
+    if l <= 0.0 {
+        return Color::ZERO;
+    }
+    (curve(l) / l) * c
+}
+
+fn mul3(m: &[[f64; 3]; 3], c: Color) -> Color {
+    Color::new(
+        m[0][0] * c.x + m[0][1] * c.y + m[0][2] * c.z,
+        m[1][0] * c.x + m[1][1] * c.y + m[1][2] * c.z,
+        m[2][0] * c.x + m[2][1] * c.y + m[2][2] * c.z,
+    )
+}
+
+// Stephen Hill's fit of the ACES RRT + sRGB ODT.
+fn aces_fitted(c: Color) -> Color {
+    const ACES_INPUT: [[f64; 3]; 3] = [
+        [0.59719, 0.35458, 0.04823],
+        [0.07600, 0.90834, 0.01566],
+        [0.02840, 0.13383, 0.83777],
+    ];
+    const ACES_OUTPUT: [[f64; 3]; 3] = [
+        [1.60475, -0.53108, -0.07367],
+        [-0.10208, 1.10813, -0.00605],
+        [-0.00327, -0.07276, 1.07602],
+    ];
+
+    let rrt_and_odt_fit = |v: f64| {
+        let a = v * (v + 0.0245786) - 0.000090537;
+        let b = v * (0.983729 * v + 0.4329510) + 0.238081;
+        a / b
+    };
+
+    let v = mul3(&ACES_INPUT, c);
+    let v = Color::new(rrt_and_odt_fit(v.x), rrt_and_odt_fit(v.y), rrt_and_odt_fit(v.z));
+    let v = mul3(&ACES_OUTPUT, v);
+    Color::new(clamp(v.x, 0.0, 1.0), clamp(v.y, 0.0, 1.0), clamp(v.z, 0.0, 1.0))
+}
+
+// Minimal AgX with the default contrast curve. The curve produces display
+// encoded values, so they are linearized with the 2.2 reference EOTF before
+// the sRGB encode that follows every operator.
+fn agx(c: Color) -> Color {
+    const AGX_INSET: [[f64; 3]; 3] = [
+        [0.842479062253094, 0.0784335999999992, 0.0792237451477643],
+        [0.0423282422610123, 0.878468636469772, 0.0791661274605434],
+        [0.0423756549057051, 0.0784336, 0.879142973793104],
+    ];
+    const AGX_OUTSET: [[f64; 3]; 3] = [
+        [1.19687900512017, -0.0980208811401368, -0.0990297440797205],
+        [-0.0528968517574562, 1.15190312990417, -0.0989611768448433],
+        [-0.0529716355144438, -0.0980434501171241, 1.15107367264116],
+    ];
+    const MIN_EV: f64 = -12.47393;
+    const MAX_EV: f64 = 4.026069;
+
+    let contrast = |v: f64| {
+        let v = (clamp(v.max(1e-10).log2(), MIN_EV, MAX_EV) - MIN_EV) / (MAX_EV - MIN_EV);
+        let v2 = v * v;
+        let v4 = v2 * v2;
+        15.5 * v4 * v2 - 40.14 * v4 * v + 31.96 * v4 - 6.868 * v2 * v + 0.4298 * v2 + 0.1191 * v - 0.00232
+    };
+
+    let v = mul3(&AGX_INSET, c);
+    let v = Color::new(contrast(v.x), contrast(v.y), contrast(v.z));
+    let v = mul3(&AGX_OUTSET, v);
+    Color::new(
+        clamp(v.x, 0.0, 1.0).powf(2.2),
+        clamp(v.y, 0.0, 1.0).powf(2.2),
+        clamp(v.z, 0.0, 1.0).powf(2.2),
     )
 }
@@ -0,0 +1,262 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    material::Material,
+    onb::Onb,
+    ray::Ray,
+    rtweekend::{PI, Sampler},
+    vec3::{Point3, Vec3},
+};
+
+// A truncated cone from a circle of radius0 around p0 to one of radius1
+// around p1; a zero radius makes a point. Ends with a radius are capped
+// unless the cone is open. Hits are found with p0 at the origin and the
+// axis along local z. UVs follow Cylinder.
+pub struct Cone {
+    pub p0: Point3,
+    pub p1: Point3,
+    pub radius0: f64,
+    pub radius1: f64,
+    pub open: bool,
+    pub material: Arc<dyn Material>,
+    uvw: Onb,
+    height: f64,
+}
+
+impl Cone {
+    pub fn new(p0: Point3, radius0: f64, p1: Point3, radius1: f64, material: Arc<dyn Material>) -> Self {
+        Self {
+            p0,
+            p1,
+            radius0,
+            radius1,
+            open: false,
+            material,
+            uvw: Onb::build_from_w(p1 - p0),
+            height: (p1 - p0).length(),
+        }
+    }
+
+    // Change in radius per unit along the axis.
+    fn slope(&self) -> f64 {
+        (self.radius1 - self.radius0) / self.height
+    }
+
+    fn side_hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<f64> {
+        let (o, d) = (r.origin, r.direction);
+        let k = self.slope();
+        let rho = self.radius0 + k * o.z;
+
+        // x^2 + y^2 = (radius0 + k z)^2 along the ray.
+        let a = d.x * d.x + d.y * d.y - k * k * d.z * d.z;
+        let half_b = o.x * d.x + o.y * d.y - k * d.z * rho;
+        let c = o.x * o.x + o.y * o.y - rho * rho;
+
+        let mut roots = [f64::NAN; 2];
+        if a.abs() < 1e-12 {
+            // Parallel to a generating line: one crossing at most.
+            if half_b.abs() > 1e-12 {
+                roots[0] = -c / (2.0 * half_b);
+            }
+        } else {
+            let discriminant = half_b * half_b - a * c;
+            if discriminant < 0.0 {
+                return None;
+            }
+            let sqrt_d = discriminant.sqrt();
+            roots = [(-half_b - sqrt_d) / a, (-half_b + sqrt_d) / a];
+            if roots[0] > roots[1] {
+                roots.swap(0, 1);
+            }
+        }
+
+        // The quadric is a double cone; keep the part between the ends.
+        roots.into_iter().find(|&t| {
+            let z = o.z + t * d.z;
+            t >= t_min && t <= t_max && (0.0..=self.height).contains(&z)
+        })
+    }
+}
+
+enum HitSurface {
+    Side,
+    TopCap,
+    BottomCap,
+}
+
+impl Hittable for Cone {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, _sampler: &mut Sampler) -> Option<HitRecord> {
+        let local_r = Ray::with_time(self.uvw.to_local(r.origin - self.p0), self.uvw.to_local(r.direction), r.time);
+        let mut hits: Vec<(f64, HitSurface)> = Vec::new();
+
+        if !self.open {
+            if let Some(t) = cap_hit(&local_r, self.height, self.radius1, t_min, t_max) {
+                hits.push((t, HitSurface::TopCap));
+            }
+            if let Some(t) = cap_hit(&local_r, 0.0, self.radius0, t_min, t_max) {
+                hits.push((t, HitSurface::BottomCap));
+            }
+        }
+        if let Some(t) = self.side_hit(&local_r, t_min, t_max) {
+            hits.push((t, HitSurface::Side));
+        }
+
+        let &(t, ref surface) = hits.iter().min_by(|a, b| a.0.total_cmp(&b.0))?;
+        let p = local_r.at(t);
+        let (outward_normal, u, v) = match surface {
+            HitSurface::TopCap => (
+                Vec3::new(0.0, 0.0, 1.0),
+                0.5 + p.x / (2.0 * self.radius1),
+                0.5 + p.y / (2.0 * self.radius1),
+            ),
+            HitSurface::BottomCap => (
+                Vec3::new(0.0, 0.0, -1.0),
+                0.5 + p.x / (2.0 * self.radius0),
+                0.5 - p.y / (2.0 * self.radius0),
+            ),
+            HitSurface::Side => {
+                // Gradient of x^2 + y^2 - rho(z)^2; at a point of the cone
+                // rho is zero and the axis direction is the best we have.
+                let rho = self.radius0 + self.slope() * p.z;
+                let gradient = Vec3::new(p.x, p.y, -self.slope() * rho);
+                let normal = if gradient.near_zero() {
+                    Vec3::new(0.0, 0.0, if self.radius1 < self.radius0 { 1.0 } else { -1.0 })
+                } else {
+                    gradient.unit_vector()
+                };
+                (normal, (p.y.atan2(p.x) + PI) / (2.0 * PI), p.z / self.height)
+            }
+        };
+
+        let mut rec = HitRecord {
+            t,
+            p: r.at(t),
+            u,
+            v,
+            normal: Vec3::ZERO,
+            front_face: false,
+            color: None,
+            material: Arc::clone(&self.material),
+        };
+        rec.set_face_normal(r, self.uvw.local_vec(outward_normal));
+
+        Some(rec)
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        // The union of the two end circles' boxes; see Cylinder.
+        let w = self.uvw.w();
+        let reach = Vec3::new(
+            (1.0 - w.x * w.x).max(0.0).sqrt(),
+            (1.0 - w.y * w.y).max(0.0).sqrt(),
+            (1.0 - w.z * w.z).max(0.0).sqrt(),
+        );
+        let (e0, e1) = (self.radius0 * reach, self.radius1 * reach);
+        let (a0, a1) = (self.p0 - e0, self.p1 - e1);
+        let (b0, b1) = (self.p0 + e0, self.p1 + e1);
+        Some(Aabb::new(
+            Point3::new(a0.x.min(a1.x), a0.y.min(a1.y), a0.z.min(a1.z)),
+            Point3::new(b0.x.max(b1.x), b0.y.max(b1.y), b0.z.max(b1.z)),
+        ))
+    }
+}
+
+fn cap_hit(r: &Ray, z: f64, radius: f64, t_min: f64, t_max: f64) -> Option<f64> {
+    // No radius, or a ray running parallel to the cap.
+    if radius <= 0.0 || r.direction.z.abs() < 1e-12 {
+        return None;
+    }
+    let t = (z - r.origin.z) / r.direction.z;
+    if !(t_min..=t_max).contains(&t) {
+        return None;
+    }
+
+    let x = r.origin.x + t * r.direction.x;
+    let y = r.origin.y + t * r.direction.y;
+
+    // epsilon accounting for floating point precision errors
+    if x * x + y * y > radius * radius + 1e-8 {
+        return None;
+    }
+    Some(t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Lambertian, rtweekend::INFINITY, vec3::Color};
+
+    // Upright, radius 2 at y = 0 narrowing to 1 at y = 2.
+    fn cone() -> Cone {
+        let material = Arc::new(Lambertian::new_from_color(Color::new(0.5, 0.5, 0.5)));
+        Cone::new(Point3::new(0.0, 0.0, 0.0), 2.0, Point3::new(0.0, 2.0, 0.0), 1.0, material)
+    }
+
+    fn across_at(y: f64) -> Ray {
+        Ray::new(Point3::new(-5.0, y, 0.0), Vec3::new(1.0, 0.0, 0.0))
+    }
+
+    #[test]
+    fn side_hit_and_normal() {
+        let mut sampler = Sampler::new(0);
+        let rec = cone().hit(&across_at(1.0), 0.001, INFINITY, &mut sampler).unwrap();
+        // Radius 1.5 halfway up.
+        assert!((rec.t - 3.5).abs() < 1e-9);
+        assert!(rec.front_face);
+        // The side leans inward by 1 over 2, so the normal tips up.
+        let expected = Vec3::new(-2.0, 1.0, 0.0).unit_vector();
+        assert!((rec.normal - expected).length() < 1e-9);
+        assert!((rec.v - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn caps_and_open_mode() {
+        let mut sampler = Sampler::new(0);
+        let down = Ray::new(Point3::new(0.5, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let rec = cone().hit(&down, 0.001, INFINITY, &mut sampler).unwrap();
+        assert!((rec.t - 3.0).abs() < 1e-9);
+        assert!((rec.normal - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-9);
+
+        // Without caps the ray goes straight through the middle.
+        let mut open = cone();
+        open.open = true;
+        assert!(open.hit(&down, 0.001, INFINITY, &mut sampler).is_none());
+    }
+
+    #[test]
+    fn inside_hits_are_back_faces() {
+        let mut sampler = Sampler::new(0);
+        let r = Ray::new(Point3::new(0.0, 1.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let rec = cone().hit(&r, 0.001, INFINITY, &mut sampler).unwrap();
+        assert!((rec.t - 1.5).abs() < 1e-9);
+        assert!(!rec.front_face);
+    }
+
+    #[test]
+    fn misses_and_grazing() {
+        let mut sampler = Sampler::new(0);
+        assert!(cone().hit(&across_at(2.5), 0.001, INFINITY, &mut sampler).is_none());
+        assert!(cone().hit(&across_at(-0.5), 0.001, INFINITY, &mut sampler).is_none());
+        // Past the rim of the top cap, but still inside the wider base.
+        let r = Ray::new(Point3::new(1.0 + 1e-6, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let rec = cone().hit(&r, 0.001, INFINITY, &mut sampler).unwrap();
+        assert!(rec.t > 3.0);
+        // Just inside the top rim.
+        let r = Ray::new(Point3::new(1.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!((cone().hit(&r, 0.001, INFINITY, &mut sampler).unwrap().t - 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn pointed_cone_has_no_top_cap() {
+        let mut sampler = Sampler::new(0);
+        let material = Arc::new(Lambertian::new_from_color(Color::new(0.5, 0.5, 0.5)));
+        let c = Cone::new(Point3::new(0.0, 0.0, 0.0), 1.0, Point3::new(0.0, 1.0, 0.0), 0.0, material);
+        let down = Ray::new(Point3::new(0.25, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let rec = c.hit(&down, 0.001, INFINITY, &mut sampler).unwrap();
+        assert!((rec.t - 4.25).abs() < 1e-9);
+        let bbox = c.bounding_box(0.0, 1.0).unwrap();
+        assert!((bbox.maximum.y - 1.0).abs() < 1e-9 && (bbox.maximum.x - 1.0).abs() < 1e-9);
+    }
+}
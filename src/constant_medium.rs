@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use crate::{hittable::{HitRecord, Hittable}, material::{Isotropic, Material}, ray::Ray, rtweekend::{random_double, INFINITY}, texture::{SolidColor, Texture}, vec3::{Color, Vec3}};
+use crate::{hittable::{HitRecord, Hittable}, material::{Isotropic, Material}, ray::Ray, rtweekend::{INFINITY, Sampler}, texture::{SolidColor, Texture}, vec3::{Color, Vec3}};
 
 
 
@@ -27,9 +27,9 @@ impl ConstantMedium {
 }
 
 impl Hittable for ConstantMedium {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let mut rec1 = self.boundary.hit(r, -INFINITY, INFINITY)?;
-        let mut rec2 = self.boundary.hit(r, rec1.t + 0.0001, INFINITY)?;
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, sampler: &mut Sampler) -> Option<HitRecord> {
+        let mut rec1 = self.boundary.hit(r, -INFINITY, INFINITY, sampler)?;
+        let mut rec2 = self.boundary.hit(r, rec1.t + 0.0001, INFINITY, sampler)?;
 
         if rec1.t < t_min {
             rec1.t = t_min;
@@ -48,7 +48,7 @@ impl Hittable for ConstantMedium {
 
         let ray_length = r.direction.length();
         let distance_inside_boundary = (rec2.t - rec1.t) * ray_length;
-        let hit_distance = self.neg_inv_density * random_double().ln();
+        let hit_distance = self.neg_inv_density * sampler.random_double().ln();
 
         if hit_distance > distance_inside_boundary {
             return None;
@@ -62,6 +62,7 @@ impl Hittable for ConstantMedium {
             p,
             normal: Vec3::new(1.0, 0.0, 0.0),
             front_face: true,
+            color: None,
             material: Arc::clone(&self.phase_function),
             u: 0.0,
             v: 0.0,
@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use crate::{aabb::Aabb, aarect::{XYRect, XZRect, YZRect}, hittable::Hittable, hittable_list::HittableList, material::Material, vec3::Point3};
+use crate::{aabb::Aabb, hittable::Hittable, hittable_list::HittableList, material::Material, quad::Quad, rtweekend::Sampler, vec3::{Point3, Vec3}};
 
 
 pub struct Cuboid {
@@ -10,32 +10,88 @@ pub struct Cuboid {
 }
 
 impl Cuboid {
+    // Opposite corners in any order; every side's normal points out.
     pub fn new(p0: Point3, p1: Point3, material: Arc<dyn Material>) -> Self {
-        let mut sides = HittableList::new();
-
-        sides.add(Arc::new(XYRect::new(p0.x, p1.x, p0.y, p1.y, p1.z, Arc::clone(&material))));
-        sides.add(Arc::new(XYRect::new(p0.x, p1.x, p0.y, p1.y, p0.z, Arc::clone(&material))));
-
-        sides.add(Arc::new(XZRect::new(p0.x, p1.x, p0.z, p1.z, p1.y, Arc::clone(&material))));
-        sides.add(Arc::new(XZRect::new(p0.x, p1.x, p0.z, p1.z, p0.y, Arc::clone(&material))));
+        let min = Point3::new(p0.x.min(p1.x), p0.y.min(p1.y), p0.z.min(p1.z));
+        let max = Point3::new(p0.x.max(p1.x), p0.y.max(p1.y), p0.z.max(p1.z));
+        let dx = Vec3::new(max.x - min.x, 0.0, 0.0);
+        let dy = Vec3::new(0.0, max.y - min.y, 0.0);
+        let dz = Vec3::new(0.0, 0.0, max.z - min.z);
 
-        sides.add(Arc::new(YZRect::new(p0.y, p1.y, p0.z, p1.z, p1.x, Arc::clone(&material))));
-        sides.add(Arc::new(YZRect::new(p0.y, p1.y, p0.z, p1.z, p0.x, Arc::clone(&material))));
+        let mut sides = HittableList::new();
+        sides.add(Arc::new(Quad::new(Point3::new(min.x, min.y, max.z), dx, dy, Arc::clone(&material)))); // front
+        sides.add(Arc::new(Quad::new(Point3::new(max.x, min.y, max.z), -dz, dy, Arc::clone(&material)))); // right
+        sides.add(Arc::new(Quad::new(Point3::new(max.x, min.y, min.z), -dx, dy, Arc::clone(&material)))); // back
+        sides.add(Arc::new(Quad::new(Point3::new(min.x, min.y, min.z), dz, dy, Arc::clone(&material)))); // left
+        sides.add(Arc::new(Quad::new(Point3::new(min.x, max.y, max.z), dx, -dz, Arc::clone(&material)))); // top
+        sides.add(Arc::new(Quad::new(Point3::new(min.x, min.y, min.z), dx, dz, Arc::clone(&material)))); // bottom
 
         Self {
-            box_min: p0,
-            box_max: p1,
+            box_min: min,
+            box_max: max,
             sides
         }
     }
 }
 
 impl Hittable for Cuboid {
-    fn hit(&self, r: &crate::ray::Ray, t_min: f64, t_max: f64) -> Option<crate::hittable::HitRecord> {
-       self.sides.hit(r, t_min, t_max)
+    fn hit(&self, r: &crate::ray::Ray, t_min: f64, t_max: f64, sampler: &mut Sampler) -> Option<crate::hittable::HitRecord> {
+       self.sides.hit(r, t_min, t_max, sampler)
     }
 
-    fn bounding_box(&self, time0: f64, time1: f64) -> Option<crate::aabb::Aabb> {
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<crate::aabb::Aabb> {
        Some(Aabb::new(self.box_min, self.box_max))
     }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, sampler: &mut Sampler) -> f64 {
+        self.sides.pdf_value(origin, direction, sampler)
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut Sampler) -> Vec3 {
+        self.sides.random(origin, sampler)
+    }
+
+    fn can_sample(&self) -> bool {
+        true
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.sides.is_emissive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Lambertian, ray::Ray, rtweekend::INFINITY, vec3::Color};
+
+    #[test]
+    fn every_side_faces_out() {
+        let mut sampler = Sampler::new(0);
+        let material = Arc::new(Lambertian::new_from_color(Color::new(0.5, 0.5, 0.5)));
+        // Corners in the "wrong" order on purpose.
+        let cuboid = Cuboid::new(Point3::new(3.0, 2.0, 1.0), Point3::new(1.0, -2.0, -1.0), material);
+        let center = Point3::new(2.0, 0.0, 0.0);
+
+        let axes = [
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(0.0, 0.0, -1.0),
+        ];
+        for axis in axes {
+            // Coming in from outside, each side's front face is hit and its
+            // normal is the outward axis.
+            let rec = cuboid.hit(&Ray::new(center + 10.0 * axis, -axis), 0.001, INFINITY, &mut sampler).unwrap();
+            assert!(rec.front_face, "{:?}", axis);
+            assert_eq!(rec.normal, axis);
+
+            // Leaving from the inside, the back face of the same side.
+            let rec = cuboid.hit(&Ray::new(center, axis), 0.001, INFINITY, &mut sampler).unwrap();
+            assert!(!rec.front_face, "{:?}", axis);
+            assert_eq!(rec.normal, -axis);
+        }
+    }
 }
@@ -1,23 +1,60 @@
 use std::sync::Arc;
 
-use crate::{aabb::Aabb, hittable::{HitRecord, Hittable}, material::Material, ray::Ray, vec3::{Point3, Vec3}};
+use crate::{aabb::Aabb, hittable::{HitRecord, Hittable}, material::Material, onb::Onb, ray::Ray, rtweekend::{INFINITY, PI, Sampler}, vec3::{dot, Point3, Vec3}};
 
 
 
+// A circular cylinder around the segment from p0 to p1, capped at both ends
+// unless it is open. Hits are found in a local frame with p0 at the origin
+// and the axis along +y; for an upright cylinder that is the world frame.
 pub struct Cylinder {
-    pub y0: f64,
-    pub y1: f64,
+    pub p0: Point3,
+    pub p1: Point3,
     pub radius: f64,
+    pub open: bool,
     pub material: Arc<dyn Material>,
+    // World directions of the local x, y (the axis) and z.
+    frame: [Vec3; 3],
+    height: f64,
 }
 
 impl Cylinder {
+    // Upright on the y axis, from y0 to y1.
     pub fn new(y0: f64, y1: f64, radius: f64, material: Arc<dyn Material>) -> Self {
+        Self::between(Point3::new(0.0, y0, 0.0), Point3::new(0.0, y1, 0.0), radius, material)
+    }
+
+    pub fn between(p0: Point3, p1: Point3, radius: f64, material: Arc<dyn Material>) -> Self {
+        let axis = p1 - p0;
+        let uvw = Onb::build_from_w(axis);
         Self {
-            y0,
-            y1,
+            p0,
+            p1,
             radius,
+            open: false,
             material,
+            frame: [uvw.u(), uvw.w(), -uvw.v()],
+            height: axis.length(),
+        }
+    }
+
+    fn to_local(&self, v: Vec3) -> Vec3 {
+        Vec3::new(dot(&v, &self.frame[0]), dot(&v, &self.frame[1]), dot(&v, &self.frame[2]))
+    }
+
+    fn to_world(&self, v: Vec3) -> Vec3 {
+        self.frame[0] * v.x + self.frame[1] * v.y + self.frame[2] * v.z
+    }
+
+    fn side_area(&self) -> f64 {
+        2.0 * PI * self.radius * self.height
+    }
+
+    fn area(&self) -> f64 {
+        if self.open {
+            self.side_area()
+        } else {
+            self.side_area() + 2.0 * PI * self.radius * self.radius
         }
     }
 }
@@ -30,20 +67,24 @@ enum HitSurface {
 
 
 impl Hittable for Cylinder {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, _sampler: &mut Sampler) -> Option<HitRecord> {
+        // The frame is orthonormal, so t is the same in both.
+        let local_r = Ray::with_time(self.to_local(r.origin - self.p0), self.to_local(r.direction), r.time);
         let mut hits: Vec<(f64, HitSurface)> = Vec::new();
 
-        // check hit for top cap
-        if let Some(t) = try_cap_hit(r, self.y1, self.radius, t_min, t_max) {
-            hits.push((t, HitSurface::TopCap));
-        }
+        if !self.open {
+            // check hit for top cap
+            if let Some(t) = try_cap_hit(&local_r, self.height, self.radius, t_min, t_max) {
+                hits.push((t, HitSurface::TopCap));
+            }
 
-        // check hit for bottom cap
-        if let Some(t) = try_cap_hit(r, self.y0, self.radius, t_min, t_max) {
-            hits.push((t, HitSurface::BottomCap));
+            // check hit for bottom cap
+            if let Some(t) = try_cap_hit(&local_r, 0.0, self.radius, t_min, t_max) {
+                hits.push((t, HitSurface::BottomCap));
+            }
         }
 
-        if let Some(t) = try_side_hit(r, self.y0, self.y1, self.radius, t_min, t_max) {
+        if let Some(t) = try_side_hit(&local_r, 0.0, self.height, self.radius, t_min, t_max) {
             hits.push((t, HitSurface::Side));
         }
 
@@ -52,44 +93,94 @@ impl Hittable for Cylinder {
             .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
 
         if let Some(&(t, ref surface)) = best_hit {
-            let outward_normal;
-            let p = r.at(t);
-
-            match surface {
-                HitSurface::TopCap => {
-                    outward_normal = Vec3::new(0.0, 1.0, 0.0);
-                }
-                HitSurface::BottomCap => {
-                    outward_normal = Vec3::new(0.0, -1.0, 0.0);
-                }
-                HitSurface::Side => {
-                    outward_normal = Vec3::new(p.x, 0.0, p.z).unit_vector();
-                }
-            }
-
-            let (u, v) = (0.0, 0.0);
+            let p = local_r.at(t);
+
+            // Caps are mapped straight down the axis onto the unit square;
+            // the side wraps u once around from -x like a sphere and runs v
+            // from p0 to p1.
+            let (outward_normal, u, v) = match surface {
+                HitSurface::TopCap => (
+                    Vec3::new(0.0, 1.0, 0.0),
+                    0.5 + p.x / (2.0 * self.radius),
+                    0.5 - p.z / (2.0 * self.radius),
+                ),
+                HitSurface::BottomCap => (
+                    Vec3::new(0.0, -1.0, 0.0),
+                    0.5 + p.x / (2.0 * self.radius),
+                    0.5 + p.z / (2.0 * self.radius),
+                ),
+                HitSurface::Side => (
+                    Vec3::new(p.x, 0.0, p.z).unit_vector(),
+                    ((-p.z).atan2(p.x) + PI) / (2.0 * PI),
+                    p.y / self.height,
+                ),
+            };
 
             let mut rec = HitRecord {
                 t,
-                p,
+                p: r.at(t),
                 u,
                 v,
                 normal: Vec3::new(0.0, 0.0, 0.0),
                 front_face: false,
+                color: None,
                 material: Arc::clone(&self.material),
             };
-            rec.set_face_normal(r, outward_normal);
+            rec.set_face_normal(r, self.to_world(outward_normal));
 
             return Some(rec)
         }
         None
     }
 
-    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
-        Some(Aabb::new(
-            Point3::new(-self.radius, self.y0, -self.radius),
-            Point3::new(self.radius, self.y1, self.radius),
-        ))
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        // A disk of radius r facing along unit axis a reaches r * sqrt(1 - a_i^2)
+        // either side of its center along world axis i.
+        let axis = (self.p1 - self.p0).unit_vector();
+        let extent = Vec3::new(
+            self.radius * (1.0 - axis.x * axis.x).max(0.0).sqrt(),
+            self.radius * (1.0 - axis.y * axis.y).max(0.0).sqrt(),
+            self.radius * (1.0 - axis.z * axis.z).max(0.0).sqrt(),
+        );
+        let min = Point3::new(self.p0.x.min(self.p1.x), self.p0.y.min(self.p1.y), self.p0.z.min(self.p1.z));
+        let max = Point3::new(self.p0.x.max(self.p1.x), self.p0.y.max(self.p1.y), self.p0.z.max(self.p1.z));
+        Some(Aabb::new(min - extent, max + extent))
+    }
+
+    // Points are sampled uniformly by area. A direction can cross the
+    // surface twice, going in and coming out, and a sample may land on
+    // either, so the density sums over every crossing.
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, sampler: &mut Sampler) -> f64 {
+        let ray = Ray::new(*origin, direction.unit_vector());
+        let mut pdf = 0.0;
+        let mut t_min = 0.001;
+        for _ in 0..4 {
+            let Some(rec) = self.hit(&ray, t_min, INFINITY, sampler) else { break };
+            let cosine = dot(&ray.direction, &rec.normal).abs();
+            pdf += rec.t * rec.t / (cosine * self.area());
+            t_min = rec.t + 1e-6;
+        }
+        pdf
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut Sampler) -> Vec3 {
+        let phi = 2.0 * PI * sampler.random_double();
+        let local = if self.open || sampler.random_double() * self.area() < self.side_area() {
+            Vec3::new(self.radius * phi.cos(), self.height * sampler.random_double(), self.radius * phi.sin())
+        } else {
+            let r = self.radius * sampler.random_double().sqrt();
+            let y = if sampler.random_double() < 0.5 { 0.0 } else { self.height };
+            Vec3::new(r * phi.cos(), y, r * phi.sin())
+        };
+        self.p0 + self.to_world(local) - *origin
+    }
+
+    fn can_sample(&self) -> bool {
+        true
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
     }
 }
 
@@ -121,31 +212,27 @@ fn try_side_hit(r: &Ray, y0: f64, y1: f64, radius: f64, t_min: f64, t_max: f64)
         std::mem::swap(&mut t0, &mut t1);
     }
 
-    let t = {
-        let t_temp = t0;
-        if t_temp >= t_min && t_temp <= t_max {
+    let t_temp = t0;
+    if t_temp >= t_min && t_temp <= t_max {
 
-            let hit_y = r.origin.y + t_temp * r.direction.y;
+        let hit_y = r.origin.y + t_temp * r.direction.y;
 
-            if hit_y >= y0 && hit_y <= y1 {
-                return Some(t_temp);
-            }
+        if hit_y >= y0 && hit_y <= y1 {
+            return Some(t_temp);
         }
-        let t_temp = t1;
+    }
+    let t_temp = t1;
 
-        if t_temp >= t_min && t_temp <= t_max {
+    if t_temp >= t_min && t_temp <= t_max {
 
-            let hit_y = r.origin.y + t_temp * r.direction.y;
+        let hit_y = r.origin.y + t_temp * r.direction.y;
 
-            if hit_y >= y0 && hit_y <= y1 {
-                return Some(t_temp);
-            }
+        if hit_y >= y0 && hit_y <= y1 {
+            return Some(t_temp);
         }
+    }
 
-        None
-    };
-
-    t
+    None
 }
 
 fn try_cap_hit(r: &Ray, y: f64, radius: f64, t_min: f64, t_max: f64) -> Option<f64>{
@@ -164,3 +251,141 @@ fn try_cap_hit(r: &Ray, y: f64, radius: f64, t_min: f64, t_max: f64) -> Option<f
 
     Some(t)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Lambertian, rtweekend::Sampler, vec3::{cross, random_unit_vector, Color}};
+
+    fn cylinder(p0: Point3, p1: Point3, radius: f64) -> Cylinder {
+        Cylinder::between(p0, p1, radius, Arc::new(Lambertian::new_from_color(Color::new(0.5, 0.5, 0.5))))
+    }
+
+    // Upright from y = 0 to 2 with radius 1.
+    fn upright() -> Cylinder {
+        cylinder(Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 2.0, 0.0), 1.0)
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn side_uvs_wrap_around_the_axis() {
+        let mut sampler = Sampler::new(0);
+        // Rays in toward the axis at height 0.5, from +x, +z and -z.
+        let cases = [(Vec3::new(1.0, 0.0, 0.0), 0.5), (Vec3::new(0.0, 0.0, 1.0), 0.25), (Vec3::new(0.0, 0.0, -1.0), 0.75)];
+        for (side, u) in cases {
+            let r = Ray::new(Point3::new(0.0, 0.5, 0.0) + 10.0 * side, -side);
+            let rec = upright().hit(&r, 0.001, INFINITY, &mut sampler).unwrap();
+            assert!(close(rec.t, 9.0));
+            assert!(rec.front_face);
+            assert!((rec.normal - side).length() < 1e-9);
+            assert!(close(rec.u, u) && close(rec.v, 0.25), "{:?}: {} {}", side, rec.u, rec.v);
+        }
+    }
+
+    #[test]
+    fn caps_map_onto_the_unit_square() {
+        let mut sampler = Sampler::new(0);
+        let top = upright().hit(&Ray::new(Point3::new(0.5, 5.0, -0.5), Vec3::new(0.0, -1.0, 0.0)), 0.001, INFINITY, &mut sampler).unwrap();
+        assert!(close(top.t, 3.0) && top.front_face);
+        assert_eq!(top.normal, Vec3::new(0.0, 1.0, 0.0));
+        assert!(close(top.u, 0.75) && close(top.v, 0.75));
+
+        let bottom = upright().hit(&Ray::new(Point3::new(0.5, -5.0, -0.5), Vec3::new(0.0, 1.0, 0.0)), 0.001, INFINITY, &mut sampler).unwrap();
+        assert!(close(bottom.t, 5.0) && bottom.front_face);
+        assert_eq!(bottom.normal, Vec3::new(0.0, -1.0, 0.0));
+        assert!(close(bottom.u, 0.75) && close(bottom.v, 0.25));
+    }
+
+    #[test]
+    fn open_cylinders_have_no_caps() {
+        let mut sampler = Sampler::new(0);
+        // Down through the top and out toward the wall.
+        let r = Ray::new(Point3::new(0.0, 3.0, 0.0), Vec3::new(0.5, -1.0, 0.0));
+        let closed = upright().hit(&r, 0.001, INFINITY, &mut sampler).unwrap();
+        assert!(close(closed.t, 1.0));
+
+        let mut open = upright();
+        open.open = true;
+        let rec = open.hit(&r, 0.001, INFINITY, &mut sampler).unwrap();
+        assert!(close(rec.t, 2.0));
+        // The inside of the wall.
+        assert!(!rec.front_face);
+        assert!((rec.normal - Vec3::new(-1.0, 0.0, 0.0)).length() < 1e-9);
+
+        // Straight down the axis there is nothing to hit.
+        assert!(open.hit(&Ray::new(Point3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0)), 0.001, INFINITY, &mut sampler).is_none());
+    }
+
+    #[test]
+    fn arbitrary_axes() {
+        let mut sampler = Sampler::new(0);
+        let p0 = Point3::new(1.0, 2.0, 3.0);
+        let axis = Vec3::new(1.0, -2.0, 0.5).unit_vector();
+        let shape = cylinder(p0, p0 + 4.0 * axis, 0.5);
+
+        // Side on, toward the middle of the axis.
+        let across = cross(&axis, &Vec3::new(0.0, 0.0, 1.0)).unit_vector();
+        let middle = p0 + 2.0 * axis;
+        let rec = shape.hit(&Ray::new(middle + 5.0 * across, -across), 0.001, INFINITY, &mut sampler).unwrap();
+        assert!(close(rec.t, 4.5) && rec.front_face);
+        assert!((rec.normal - across).length() < 1e-9);
+        assert!(close(rec.v, 0.5));
+
+        // End on, into the cap at p0.
+        let rec = shape.hit(&Ray::new(p0 - 3.0 * axis + 0.2 * across, axis), 0.001, INFINITY, &mut sampler).unwrap();
+        assert!(close(rec.t, 3.0) && rec.front_face);
+        assert!((rec.normal + axis).length() < 1e-9);
+
+        // Every sampled surface point lies within the bounding box.
+        let bbox = shape.bounding_box(0.0, 1.0).unwrap();
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        for _ in 0..10_000 {
+            let p = origin + shape.random(&origin, &mut sampler);
+            for a in 0..3 {
+                assert!(p[a] >= bbox.minimum[a] - 1e-9 && p[a] <= bbox.maximum[a] + 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn pdf_matches_the_sampled_density() {
+        let mut sampler = Sampler::new(1);
+        let p0 = Point3::new(0.0, 0.0, 0.0);
+        // Above and to the side, looking into the open top.
+        let origin = Point3::new(1.5, 4.0, 0.5);
+        let n = 200_000;
+        for open in [false, true] {
+            let mut shape = cylinder(p0, Point3::new(0.2, 2.0, -0.3), 1.0);
+            shape.open = open;
+
+            // Uniform directions: the pdf integrates to one, and the share
+            // that hit gives the solid angle. Grazing the wall the pdf grows
+            // as 1 / cos, so this estimate is the noisier of the two.
+            let (mut integral, mut hits) = (0.0, 0);
+            for _ in 0..n {
+                let direction = random_unit_vector(&mut sampler);
+                integral += shape.pdf_value(&origin, &direction, &mut sampler);
+                if shape.hit(&Ray::new(origin, direction), 0.001, INFINITY, &mut sampler).is_some() {
+                    hits += 1;
+                }
+            }
+            let integral = integral / n as f64 * 4.0 * PI;
+            let solid_angle = hits as f64 / n as f64 * 4.0 * PI;
+            assert!((integral - 1.0).abs() < 0.05, "open {}: {}", open, integral);
+
+            // Sampled directions: 1 / pdf averages to that same solid angle.
+            let mut total = 0.0;
+            for _ in 0..n {
+                let direction = shape.random(&origin, &mut sampler);
+                let pdf = shape.pdf_value(&origin, &direction, &mut sampler);
+                assert!(pdf > 0.0);
+                total += 1.0 / pdf;
+            }
+            let estimate = total / n as f64;
+            assert!((estimate - solid_angle).abs() < 0.03 * solid_angle, "open {}: {} vs {}", open, estimate, solid_angle);
+        }
+    }
+}
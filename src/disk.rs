@@ -0,0 +1,184 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    material::Material,
+    onb::Onb,
+    ray::Ray,
+    rtweekend::{INFINITY, PI, Sampler},
+    vec3::{dot, Point3, Vec3},
+};
+
+// A flat disk facing along `normal`, or an annulus when the inner radius
+// is above zero. u goes once around the center and v runs outward from the
+// inner edge to the rim.
+pub struct Disk {
+    pub center: Point3,
+    pub radius: f64,
+    pub inner_radius: f64,
+    pub material: Arc<dyn Material>,
+    uvw: Onb,
+}
+
+impl Disk {
+    pub fn new(center: Point3, normal: Vec3, radius: f64, material: Arc<dyn Material>) -> Self {
+        Self::annulus(center, normal, 0.0, radius, material)
+    }
+
+    pub fn annulus(center: Point3, normal: Vec3, inner_radius: f64, radius: f64, material: Arc<dyn Material>) -> Self {
+        Self {
+            center,
+            radius,
+            inner_radius,
+            material,
+            uvw: Onb::build_from_w(normal),
+        }
+    }
+
+    fn area(&self) -> f64 {
+        PI * (self.radius * self.radius - self.inner_radius * self.inner_radius)
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, _sampler: &mut Sampler) -> Option<HitRecord> {
+        let normal = self.uvw.w();
+        let denom = dot(&normal, &r.direction);
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = dot(&normal, &(self.center - r.origin)) / denom;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        // Same rim tolerance as the cylinder caps.
+        let p = r.at(t);
+        let local = self.uvw.to_local(p - self.center);
+        let rho_squared = local.x * local.x + local.y * local.y;
+        if rho_squared > self.radius * self.radius + 1e-8 || rho_squared < self.inner_radius * self.inner_radius - 1e-8 {
+            return None;
+        }
+
+        let phi = local.y.atan2(local.x);
+        let mut rec = HitRecord {
+            t,
+            p,
+            u: (phi + PI) / (2.0 * PI),
+            v: ((rho_squared.sqrt() - self.inner_radius) / (self.radius - self.inner_radius)).clamp(0.0, 1.0),
+            normal: Vec3::ZERO,
+            front_face: false,
+            color: None,
+            material: Arc::clone(&self.material),
+        };
+        rec.set_face_normal(r, normal);
+
+        Some(rec)
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        // See Cylinder: a disk reaches r * sqrt(1 - n_i^2) along axis i,
+        // padded so a disk in an axis plane still has some thickness.
+        let n = self.uvw.w();
+        let extent = Vec3::new(
+            self.radius * (1.0 - n.x * n.x).max(0.0).sqrt() + 0.0001,
+            self.radius * (1.0 - n.y * n.y).max(0.0).sqrt() + 0.0001,
+            self.radius * (1.0 - n.z * n.z).max(0.0).sqrt() + 0.0001,
+        );
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, sampler: &mut Sampler) -> f64 {
+        let ray = Ray::new(*origin, *direction);
+        if let Some(rec) = self.hit(&ray, 0.001, INFINITY, sampler) {
+            let distance_squared = rec.t * rec.t * direction.length_squared();
+            let cosine = dot(direction, &rec.normal).abs() / direction.length();
+            return distance_squared / (cosine * self.area());
+        }
+
+        0.0
+    }
+
+    // Uniform over the area: the squared radius is uniform between the edges.
+    fn random(&self, origin: &Point3, sampler: &mut Sampler) -> Vec3 {
+        let inner_squared = self.inner_radius * self.inner_radius;
+        let rho = (inner_squared + sampler.random_double() * (self.radius * self.radius - inner_squared)).sqrt();
+        let phi = 2.0 * PI * sampler.random_double();
+        self.center + self.uvw.local(rho * phi.cos(), rho * phi.sin(), 0.0) - *origin
+    }
+
+    fn can_sample(&self) -> bool {
+        true
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Lambertian, vec3::Color};
+
+    fn disk(inner_radius: f64) -> Disk {
+        let material = Arc::new(Lambertian::new_from_color(Color::new(0.5, 0.5, 0.5)));
+        Disk::annulus(Point3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 1.0, 0.0), inner_radius, 2.0, material)
+    }
+
+    fn down_at(x: f64, z: f64) -> Ray {
+        Ray::new(Point3::new(x, 5.0, z), Vec3::new(0.0, -1.0, 0.0))
+    }
+
+    #[test]
+    fn hits_the_front_face_from_above() {
+        let mut sampler = Sampler::new(0);
+        let rec = disk(0.0).hit(&down_at(0.5, 0.0), 0.001, INFINITY, &mut sampler).unwrap();
+        assert!((rec.t - 4.0).abs() < 1e-9);
+        assert!(rec.front_face);
+        assert!((rec.normal - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-9);
+        assert!((rec.v - 0.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn back_face_from_below() {
+        let mut sampler = Sampler::new(0);
+        let r = Ray::new(Point3::new(0.5, -3.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let rec = disk(0.0).hit(&r, 0.001, INFINITY, &mut sampler).unwrap();
+        assert!(!rec.front_face);
+        assert!((rec.normal - Vec3::new(0.0, -1.0, 0.0)).length() < 1e-9);
+    }
+
+    #[test]
+    fn misses_outside_the_rim_and_inside_the_hole() {
+        let mut sampler = Sampler::new(0);
+        assert!(disk(0.0).hit(&down_at(2.01, 0.0), 0.001, INFINITY, &mut sampler).is_none());
+        assert!(disk(1.0).hit(&down_at(0.0, 0.5), 0.001, INFINITY, &mut sampler).is_none());
+        assert!(disk(1.0).hit(&down_at(0.0, 1.5), 0.001, INFINITY, &mut sampler).is_some());
+    }
+
+    #[test]
+    fn grazing_rays() {
+        let mut sampler = Sampler::new(0);
+        // Exactly on the rim counts; a ray in the disk's plane never hits.
+        assert!(disk(0.0).hit(&down_at(2.0, 0.0), 0.001, INFINITY, &mut sampler).is_some());
+        let r = Ray::new(Point3::new(-5.0, 1.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(disk(0.0).hit(&r, 0.001, INFINITY, &mut sampler).is_none());
+    }
+
+    #[test]
+    fn samples_land_on_the_annulus() {
+        let mut sampler = Sampler::new(0);
+        let d = disk(1.0);
+        let origin = Point3::new(0.0, 5.0, 0.0);
+        for _ in 0..100 {
+            let direction = d.random(&origin, &mut sampler);
+            let p = origin + direction;
+            let rho = (p.x * p.x + p.z * p.z).sqrt();
+            assert!((p.y - 1.0).abs() < 1e-9 && (1.0 - 1e-9..=2.0 + 1e-9).contains(&rho));
+            assert!(d.pdf_value(&origin, &direction, &mut sampler) > 0.0);
+        }
+    }
+}
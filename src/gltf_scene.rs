@@ -0,0 +1,440 @@
+use std::{collections::HashMap, fmt, path::{Path, PathBuf}, sync::Arc};
+
+use gltf::{camera::Projection, image::Format, mesh::Mode, texture::WrappingMode};
+use image::{DynamicImage, Rgb32FImage};
+
+use crate::{
+    hittable::Hittable,
+    material::{Material, MetallicRoughness},
+    matrix::Mat4,
+    scene::{CameraSettings, RenderSettings, Scene, SceneBuilder},
+    texture::{ImageTexture, ScaledTexture, SolidColor, Texture},
+    triangle::{MeshData, NormalMap, TriangleMesh},
+    vec3::{Color, Point3, Vec3},
+};
+
+#[derive(Debug)]
+pub enum GltfError {
+    Import { path: PathBuf, source: gltf::Error },
+    Invalid { path: PathBuf, message: String },
+}
+
+impl fmt::Display for GltfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GltfError::Import { path, source } => write!(f, "{}: {}", path.display(), source),
+            GltfError::Invalid { path, message } => write!(f, "{}: {}", path.display(), message),
+        }
+    }
+}
+
+impl std::error::Error for GltfError {}
+
+// Walks the node tree of a .gltf/.glb scene, baking each node's world
+// transform into its meshes. Textures and materials are shared between the
+// primitives that use them.
+struct Importer<'a> {
+    path: &'a Path,
+    buffers: &'a [gltf::buffer::Data],
+    images: &'a [gltf::image::Data],
+    textures: HashMap<(usize, bool), Arc<dyn Texture>>,
+    materials: HashMap<Option<usize>, Arc<dyn Material>>,
+    objects: SceneBuilder,
+    camera: Option<(CameraSettings, Option<f64>)>,
+}
+
+// Loads the default scene (or the first one) of a glTF 2.0 file. The first
+// perspective camera found in the node tree becomes the scene camera;
+// without one the camera looks down -Z at the whole scene.
+pub fn load_gltf(path: &Path) -> Result<Scene, GltfError> {
+    let (document, buffers, images) = gltf::import(path).map_err(|source| GltfError::Import {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    let mut importer = Importer {
+        path,
+        buffers: &buffers,
+        images: &images,
+        textures: HashMap::new(),
+        materials: HashMap::new(),
+        objects: SceneBuilder::new(),
+        camera: None,
+    };
+
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or_else(|| importer.invalid("file has no scenes".to_string()))?;
+    for node in scene.nodes() {
+        importer.node(&node, &Mat4::IDENTITY)?;
+    }
+
+    if importer.objects.is_empty() {
+        return Err(importer.invalid("scene has no triangle meshes".to_string()));
+    }
+
+    let camera = importer.camera.take();
+    let (world, lights) = importer.objects.build(0.0, 1.0);
+
+    let mut render = RenderSettings::default();
+    let camera = match camera {
+        Some((camera, aspect_ratio)) => {
+            if let Some(aspect_ratio) = aspect_ratio {
+                render.aspect_ratio = aspect_ratio;
+            }
+            camera
+        }
+        None => {
+            let bbox = world.bounding_box(0.0, 1.0).unwrap();
+            let center = 0.5 * (bbox.minimum + bbox.maximum);
+            let radius = 0.5 * (bbox.maximum - bbox.minimum).length();
+            let vfov: f64 = 40.0;
+            let distance = radius / (0.5 * vfov.to_radians()).sin();
+            CameraSettings {
+                lookfrom: center + Vec3::new(0.0, 0.0, distance),
+                lookat: center,
+                vfov,
+                focus_dist: distance,
+                ..CameraSettings::default()
+            }
+        }
+    };
+
+    // glTF has no notion of a background; a lit scene renders against black
+    // and an unlit one against the usual sky.
+    let background = if lights.emitters.is_some() {
+        Color::new(0.0, 0.0, 0.0)
+    } else {
+        Color::new(0.70, 0.80, 1.00)
+    };
+
+    Ok(Scene {
+        world,
+        lights,
+        background,
+        camera,
+        render,
+    })
+}
+
+impl Importer<'_> {
+    fn invalid(&self, message: String) -> GltfError {
+        GltfError::Invalid {
+            path: self.path.to_path_buf(),
+            message,
+        }
+    }
+
+    fn node(&mut self, node: &gltf::Node, parent: &Mat4) -> Result<(), GltfError> {
+        let transform = *parent * Mat4::from_columns(node.transform().matrix());
+
+        if let Some(mesh) = node.mesh() {
+            for primitive in mesh.primitives() {
+                self.primitive(&mesh, &primitive, &transform)?;
+            }
+        }
+
+        if self.camera.is_none()
+            && let Some(camera) = node.camera()
+        {
+            match camera.projection() {
+                Projection::Perspective(perspective) => {
+                    let lookfrom = transform.transform_point(Point3::new(0.0, 0.0, 0.0));
+                    let forward = transform.transform_vector(Vec3::new(0.0, 0.0, -1.0)).unit_vector();
+                    let settings = CameraSettings {
+                        lookfrom,
+                        lookat: lookfrom + forward,
+                        vup: transform.transform_vector(Vec3::new(0.0, 1.0, 0.0)),
+                        vfov: (perspective.yfov() as f64).to_degrees(),
+                        ..CameraSettings::default()
+                    };
+                    self.camera = Some((settings, perspective.aspect_ratio().map(|a| a as f64)));
+                }
+                Projection::Orthographic(_) => {
+                    eprintln!("warning: {}: skipping orthographic camera", self.path.display());
+                }
+            }
+        }
+
+        for child in node.children() {
+            self.node(&child, &transform)?;
+        }
+        Ok(())
+    }
+
+    fn primitive(&mut self, mesh: &gltf::Mesh, primitive: &gltf::Primitive, transform: &Mat4) -> Result<(), GltfError> {
+        let name = mesh.name().map(str::to_string).unwrap_or_else(|| format!("#{}", mesh.index()));
+        if primitive.mode() != Mode::Triangles {
+            eprintln!("warning: {}: skipping {:?} primitive of mesh {}", self.path.display(), primitive.mode(), name);
+            return Ok(());
+        }
+
+        let buffers = self.buffers;
+        let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+
+        let positions: Vec<Point3> = match reader.read_positions() {
+            Some(positions) => positions.map(|p| transform.transform_point(point(p))).collect(),
+            None => return Err(self.invalid(format!("mesh {} has a primitive without positions", name))),
+        };
+
+        // Normals go through the inverse transpose so they stay perpendicular
+        // under non-uniform scale.
+        let normals: Vec<Vec3> = match (reader.read_normals(), transform.inverse()) {
+            (Some(normals), Some(inverse)) => {
+                let normal_matrix = inverse.transpose();
+                normals.map(|n| normal_matrix.transform_vector(point(n)).unit_vector()).collect()
+            }
+            _ => Vec::new(),
+        };
+
+        // glTF puts the uv origin at the top left of the image.
+        let uvs: Vec<(f64, f64)> = reader
+            .read_tex_coords(0)
+            .map(|uvs| uvs.into_f32().map(|[u, v]| (u as f64, 1.0 - v as f64)).collect())
+            .unwrap_or_default();
+
+        let colors: Vec<Color> = reader
+            .read_colors(0)
+            .map(|colors| colors.into_rgb_f32().map(point).collect())
+            .unwrap_or_default();
+
+        let flat: Vec<usize> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().map(|i| i as usize).collect(),
+            None => (0..positions.len()).collect(),
+        };
+        if let Some(&bad) = flat.iter().find(|&&i| i >= positions.len()) {
+            return Err(self.invalid(format!("mesh {}: index {} out of range for {} vertices", name, bad, positions.len())));
+        }
+
+        // A mirroring transform flips the winding, and with it the side the
+        // geometric normal faces.
+        let mirrored = transform.linear_determinant() < 0.0;
+        let indices: Vec<[usize; 3]> = flat
+            .chunks_exact(3)
+            .map(|f| if mirrored { [f[0], f[2], f[1]] } else { [f[0], f[1], f[2]] })
+            .collect();
+        if indices.is_empty() {
+            return Ok(());
+        }
+
+        let material = primitive.material();
+        let normal_map = match (material.normal_texture(), uvs.is_empty()) {
+            (Some(normal), false) => Some(NormalMap {
+                texture: self.texture(&normal.texture(), false),
+                scale: normal.scale() as f64,
+            }),
+            _ => None,
+        };
+
+        let mesh = TriangleMesh::new(MeshData {
+            positions,
+            normals,
+            uvs,
+            colors,
+            indices,
+            material: self.material(&material),
+            normal_map,
+        });
+        self.objects.add(Arc::new(mesh));
+        Ok(())
+    }
+
+    fn material(&mut self, material: &gltf::Material) -> Arc<dyn Material> {
+        if let Some(material) = self.materials.get(&material.index()) {
+            return Arc::clone(material);
+        }
+
+        let pbr = material.pbr_metallic_roughness();
+        let [r, g, b, _] = pbr.base_color_factor();
+        let base_color = self.factored(
+            pbr.base_color_texture().map(|info| info.texture()),
+            true,
+            Color::new(r as f64, g as f64, b as f64),
+        );
+        let metallic_roughness = self.factored(
+            pbr.metallic_roughness_texture().map(|info| info.texture()),
+            false,
+            Color::new(1.0, pbr.roughness_factor() as f64, pbr.metallic_factor() as f64),
+        );
+
+        let [r, g, b] = material.emissive_factor();
+        let strength = material.emissive_strength().unwrap_or(1.0) as f64;
+        let emissive_factor = strength * Color::new(r as f64, g as f64, b as f64);
+        let emissive = (emissive_factor != Color::ZERO).then(|| {
+            self.factored(material.emissive_texture().map(|info| info.texture()), true, emissive_factor)
+        });
+
+        let result: Arc<dyn Material> = Arc::new(MetallicRoughness::new(base_color, metallic_roughness, emissive));
+        self.materials.insert(material.index(), Arc::clone(&result));
+        result
+    }
+
+    // A texture scaled by its factor, or the bare factor when there is none.
+    fn factored(&mut self, texture: Option<gltf::Texture>, srgb: bool, factor: Color) -> Arc<dyn Texture> {
+        match texture {
+            Some(texture) => {
+                let image = self.texture(&texture, srgb);
+                if factor == Color::new(1.0, 1.0, 1.0) {
+                    image
+                } else {
+                    Arc::new(ScaledTexture::new(image, factor))
+                }
+            }
+            None => Arc::new(SolidColor::new(factor)),
+        }
+    }
+
+    // Only TEXCOORD_0 is read, so every texture is looked up with it.
+    fn texture(&mut self, texture: &gltf::Texture, srgb: bool) -> Arc<dyn Texture> {
+        let key = (texture.index(), srgb);
+        if let Some(texture) = self.textures.get(&key) {
+            return Arc::clone(texture);
+        }
+
+        let repeat = texture.sampler().wrap_s() != WrappingMode::ClampToEdge;
+        let data = &self.images[texture.source().index()];
+        let result: Arc<dyn Texture> = Arc::new(ImageTexture::from_image(to_image(data), srgb, repeat));
+        self.textures.insert(key, Arc::clone(&result));
+        result
+    }
+}
+
+fn point(p: [f32; 3]) -> Vec3 {
+    Vec3::new(p[0] as f64, p[1] as f64, p[2] as f64)
+}
+
+// Decoded glTF images come in several layouts; one and two channel images
+// are gray (with alpha), the rest RGB(A). Alpha is dropped.
+fn to_image(data: &gltf::image::Data) -> DynamicImage {
+    let (channels, bytes) = match data.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        Format::R32G32B32FLOAT => (3, 4),
+        Format::R32G32B32A32FLOAT => (4, 4),
+    };
+
+    let sample = |bytes_at: &[u8]| -> f32 {
+        match bytes {
+            1 => bytes_at[0] as f32 / 255.0,
+            2 => u16::from_ne_bytes([bytes_at[0], bytes_at[1]]) as f32 / 65535.0,
+            _ => f32::from_ne_bytes([bytes_at[0], bytes_at[1], bytes_at[2], bytes_at[3]]),
+        }
+    };
+
+    let mut rgb = Vec::with_capacity((data.width * data.height * 3) as usize);
+    for pixel in data.pixels.chunks_exact(channels * bytes) {
+        let channel = |c: usize| sample(&pixel[c * bytes..]);
+        if channels < 3 {
+            let gray = channel(0);
+            rgb.extend([gray, gray, gray]);
+        } else {
+            rgb.extend([channel(0), channel(1), channel(2)]);
+        }
+    }
+
+    DynamicImage::ImageRgb32F(Rgb32FImage::from_raw(data.width, data.height, rgb).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{ray::Ray, rtweekend::{INFINITY, Sampler}};
+
+    // One triangle with uvs, placed twice under a parent node: once scaled
+    // and once mirrored in x. The indices accessor is 2 for the good ones and
+    // 3 for ones that reach past the last vertex.
+    fn load_fixture(name: &str, indices: usize) -> Result<Scene, GltfError> {
+        let mut bin = Vec::new();
+        for x in [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0] {
+            bin.extend(x.to_le_bytes());
+        }
+        for i in [0u16, 1, 2, 0, 0, 1, 5, 0] {
+            bin.extend(i.to_le_bytes());
+        }
+
+        let gltf = format!(
+            r#"{{
+  "asset": {{ "version": "2.0" }},
+  "scene": 0,
+  "scenes": [{{ "nodes": [0] }}],
+  "nodes": [
+    {{ "translation": [0, 0, -5], "children": [1, 2, 3] }},
+    {{ "translation": [2, 0, 0], "scale": [2, 2, 2], "mesh": 0 }},
+    {{ "translation": [-2, 0, 0], "scale": [-1, 1, 1], "mesh": 0 }},
+    {{ "translation": [0, 1, 10], "camera": 0 }}
+  ],
+  "cameras": [{{ "type": "perspective", "perspective": {{ "yfov": 0.5, "aspectRatio": 1.5, "znear": 0.1 }} }}],
+  "meshes": [{{ "name": "tri", "primitives": [{{ "attributes": {{ "POSITION": 0, "TEXCOORD_0": 1 }}, "indices": {} }}] }}],
+  "buffers": [{{ "uri": "{}.bin", "byteLength": {} }}],
+  "bufferViews": [
+    {{ "buffer": 0, "byteOffset": 0, "byteLength": 36 }},
+    {{ "buffer": 0, "byteOffset": 36, "byteLength": 24 }},
+    {{ "buffer": 0, "byteOffset": 60, "byteLength": 6 }},
+    {{ "buffer": 0, "byteOffset": 68, "byteLength": 6 }}
+  ],
+  "accessors": [
+    {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [1, 1, 0] }},
+    {{ "bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC2" }},
+    {{ "bufferView": 2, "componentType": 5123, "count": 3, "type": "SCALAR" }},
+    {{ "bufferView": 3, "componentType": 5123, "count": 3, "type": "SCALAR" }}
+  ]
+}}"#,
+            indices,
+            name,
+            bin.len()
+        );
+
+        let dir = std::env::temp_dir().join(format!("gltf_{}_{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(format!("{}.bin", name)), &bin).unwrap();
+        fs::write(dir.join(format!("{}.gltf", name)), gltf).unwrap();
+        let scene = load_gltf(&dir.join(format!("{}.gltf", name)));
+        fs::remove_dir_all(&dir).unwrap();
+        scene
+    }
+
+    #[test]
+    fn nodes_place_meshes_and_the_camera() {
+        let mut sampler = Sampler::new(0);
+        let scene = load_fixture("placed", 2).unwrap();
+        let mut down_z = |x: f64, y: f64| scene.world.hit(&Ray::new(Point3::new(x, y, 0.0), Vec3::new(0.0, 0.0, -1.0)), 0.001, INFINITY, &mut sampler);
+
+        // The point (0.25, 0.25) of the triangle, scaled by 2 and moved by
+        // both nodes' translations. glTF's v runs down the image, ours up.
+        let rec = down_z(2.5, 0.5).unwrap();
+        assert!((rec.t - 5.0).abs() < 1e-9);
+        assert!(rec.front_face);
+        assert!((rec.u - 0.25).abs() < 1e-6 && (rec.v - 0.75).abs() < 1e-6);
+
+        // Mirrored, the triangle keeps facing +z and its uvs stay put.
+        let rec = down_z(-2.25, 0.25).unwrap();
+        assert!((rec.t - 5.0).abs() < 1e-9);
+        assert!(rec.front_face);
+        assert!((rec.u - 0.25).abs() < 1e-6 && (rec.v - 0.75).abs() < 1e-6);
+        assert!(down_z(-1.75, 0.25).is_none());
+
+        let camera = &scene.camera;
+        assert!((camera.lookfrom - Point3::new(0.0, 1.0, 5.0)).length() < 1e-9);
+        assert!((camera.lookat - Point3::new(0.0, 1.0, 4.0)).length() < 1e-9);
+        assert!((camera.vfov - 0.5f64.to_degrees()).abs() < 1e-5);
+        assert!((scene.render.aspect_ratio - 1.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn out_of_range_indices_are_rejected() {
+        match load_fixture("bad_index", 3) {
+            Err(GltfError::Invalid { message, .. }) => assert_eq!(message, "mesh tri: index 5 out of range for 3 vertices"),
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("expected an error"),
+        }
+    }
+}
@@ -2,19 +2,29 @@ use std::sync::Arc;
 
 use crate::material::{EmptyMaterial, Material};
 use crate::ray::Ray;
-use crate::rtweekend::{degrees_to_radians, INFINITY};
-use crate::vec3::{dot, Point3, Vec3};
+use crate::matrix::Mat4;
+use crate::rtweekend::{INFINITY, Sampler};
+use crate::vec3::{dot, Color, Point3, Vec3};
 use crate::aabb::Aabb;
 
 pub trait Hittable: Send + Sync {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, sampler: &mut Sampler) -> Option<HitRecord>;
     fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb>;
-    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, _sampler: &mut Sampler) -> f64 {
         0.0
     }
-    fn random(&self, origin: &Point3) -> Vec3 {
+    fn random(&self, origin: &Point3, _sampler: &mut Sampler) -> Vec3 {
         Vec3::new(1.0, 0.0, 0.0)
     }
+    // True when pdf_value/random are implemented, whatever the material.
+    fn can_sample(&self) -> bool {
+        false
+    }
+    // True when the object emits light and implements pdf_value/random, so
+    // the scene builder can add it to the light list.
+    fn is_emissive(&self) -> bool {
+        false
+    }
 }
 
 pub struct FlipFace {
@@ -29,19 +39,18 @@ pub struct HitRecord {
     pub u: f64,
     pub v: f64,
     pub front_face: bool,
+    pub color: Option<Color>, // interpolated vertex color, for VertexColorTexture
 }
 
-pub struct Translate {
+// An object placed by an affine matrix. Rays are taken into object space
+// with the inverse; hit points come back through the matrix and normals
+// through the inverse transpose, which keeps them perpendicular under
+// non-uniform scale.
+pub struct Transform {
     pub ptr: Arc<dyn Hittable>,
-    pub offset: Vec3,
-}
-
-pub struct RotateY {
-    pub ptr: Arc<dyn Hittable>,
-    pub sin_theta: f64,
-    pub cos_theta: f64,
-    pub hasbox: bool,
-    pub bbox: Aabb,
+    pub matrix: Mat4,
+    pub inverse: Mat4,
+    pub normal_matrix: Mat4,
 }
 
 impl FlipFace {
@@ -50,53 +59,24 @@ impl FlipFace {
     }
 }
 
-impl RotateY {
-    pub fn new(ptr: Arc<dyn Hittable>, angle: f64) -> Self {
-        let radians = degrees_to_radians(angle);
-        let sin_theta = radians.sin();
-        let cos_theta = radians.cos();
-
-        let mut bbox = Aabb::default();
-        let hasbox = ptr.bounding_box(0.0, 1.0).map(|b| {
-            bbox = b;
-
-            let mut min = Point3::new(INFINITY, INFINITY, INFINITY);
-            let mut max = Point3::new(-INFINITY, -INFINITY, -INFINITY);
-
-            for i in 0..2 {
-                let x = if i == 0 { bbox.minimum.x } else { bbox.maximum.x };
-                for j in 0..2 {
-                    let y = if j == 0 { bbox.minimum.y } else { bbox.maximum.y };
-                    for k in 0..2 {
-                        let z = if k == 0 { bbox.minimum.z } else { bbox.maximum.z };
-
-                        let newx = cos_theta * x + sin_theta * z;
-                        let newz = -sin_theta * x + cos_theta * z;
-
-                        let tester = Vec3::new(newx, y, newz);
-
-                        min.x = min.x.min(tester.x);
-                        min.y = min.y.min(tester.y);
-                        min.z = min.z.min(tester.z);
-
-                        max.x = max.x.max(tester.x);
-                        max.y = max.y.max(tester.y);
-                        max.z = max.z.max(tester.z);
-                    }
-                }
-            }
-            bbox = Aabb::new(min, max);
-            true
-        }).unwrap_or(false);
-
+impl Transform {
+    pub fn new(ptr: Arc<dyn Hittable>, matrix: Mat4) -> Self {
+        let inverse = matrix.inverse().expect("Transform matrix is singular");
         Self {
             ptr,
-            sin_theta,
-            cos_theta,
-            hasbox,
-            bbox,
+            matrix,
+            inverse,
+            normal_matrix: inverse.transpose(),
         }
     }
+
+    pub fn translate(ptr: Arc<dyn Hittable>, offset: Vec3) -> Self {
+        Self::new(ptr, Mat4::translation(offset))
+    }
+
+    pub fn rotate(ptr: Arc<dyn Hittable>, axis: Vec3, angle: f64) -> Self {
+        Self::new(ptr, Mat4::rotation(axis, angle))
+    }
 }
 
 impl Default for HitRecord {
@@ -109,6 +89,7 @@ impl Default for HitRecord {
             u: 0.0,
             v: 0.0,
             front_face: true,
+            color: None,
         }
     }
 }
@@ -120,15 +101,9 @@ impl HitRecord {
     }
 }
 
-impl Translate {
-    pub fn new(ptr: Arc<dyn Hittable>, offset: Vec3) -> Self {
-        Self { ptr, offset }
-    }
-}
-
 impl Hittable for FlipFace {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        if let Some(mut rec) = self.ptr.hit(r, t_min, t_max) {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, sampler: &mut Sampler) -> Option<HitRecord> {
+        if let Some(mut rec) = self.ptr.hit(r, t_min, t_max, sampler) {
             rec.front_face = !rec.front_face;
             Some(rec)
         } else {
@@ -139,75 +114,160 @@ impl Hittable for FlipFace {
     fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
         self.ptr.bounding_box(time0, time1)
     }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, sampler: &mut Sampler) -> f64 {
+        self.ptr.pdf_value(origin, direction, sampler)
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut Sampler) -> Vec3 {
+        self.ptr.random(origin, sampler)
+    }
+
+    fn can_sample(&self) -> bool {
+        self.ptr.can_sample()
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.ptr.is_emissive()
+    }
 }
 
-impl Hittable for Translate {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let moved_r = Ray::with_time(r.origin - self.offset, r.direction, r.time);
+impl Hittable for Transform {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, sampler: &mut Sampler) -> Option<HitRecord> {
+        // The direction is not renormalized, so t means the same in both spaces.
+        let local_r = Ray::with_time(
+            self.inverse.transform_point(r.origin),
+            self.inverse.transform_vector(r.direction),
+            r.time,
+        );
+
+        let mut rec = self.ptr.hit(&local_r, t_min, t_max, sampler)?;
+        rec.p = self.matrix.transform_point(rec.p);
+        // The inverse transpose keeps the side the normal faces relative to
+        // the ray, so front_face carries over.
+        rec.normal = self.normal_matrix.transform_vector(rec.normal).unit_vector();
+        Some(rec)
+    }
 
-        if let Some(mut rec) = self.ptr.hit(&moved_r, t_min, t_max) {
-            rec.p += self.offset;
-            rec.set_face_normal(&moved_r, rec.normal);
-            Some(rec)
-        } else {
-            None
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+        let bbox = self.ptr.bounding_box(time0, time1)?;
+
+        let mut min = Point3::new(INFINITY, INFINITY, INFINITY);
+        let mut max = Point3::new(-INFINITY, -INFINITY, -INFINITY);
+        for i in 0..8 {
+            let corner = Point3::new(
+                if i & 1 == 0 { bbox.minimum.x } else { bbox.maximum.x },
+                if i & 2 == 0 { bbox.minimum.y } else { bbox.maximum.y },
+                if i & 4 == 0 { bbox.minimum.z } else { bbox.maximum.z },
+            );
+            let p = self.matrix.transform_point(corner);
+            for a in 0..3 {
+                min[a] = min[a].min(p[a]);
+                max[a] = max[a].max(p[a]);
+            }
         }
+        Some(Aabb::new(min, max))
     }
 
-    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
-        self.ptr.bounding_box(time0, time1).map(|bbox| {
-            Aabb::new(bbox.minimum + self.offset, bbox.maximum + self.offset)
-        })
+    // The child's density is per object-space solid angle. Mapping a unit
+    // direction w through the linear part A of the inverse scales solid
+    // angle by |det A| / |A w|^3.
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, sampler: &mut Sampler) -> f64 {
+        let local_direction = self.inverse.transform_vector(direction.unit_vector());
+        let jacobian = self.inverse.linear_determinant().abs() / local_direction.length().powi(3);
+        self.ptr.pdf_value(&self.inverse.transform_point(*origin), &local_direction, sampler) * jacobian
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut Sampler) -> Vec3 {
+        self.matrix.transform_vector(self.ptr.random(&self.inverse.transform_point(*origin), sampler))
+    }
+
+    fn can_sample(&self) -> bool {
+        self.ptr.can_sample()
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.ptr.is_emissive()
     }
 }
 
-impl Hittable for RotateY {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let origin = {
-            let x = self.cos_theta * r.origin.x - self.sin_theta * r.origin.z;
-            let z = self.sin_theta * r.origin.x + self.cos_theta * r.origin.z;
-            Point3::new(x, r.origin.y, z)
-        };
-
-        let direction = {
-            let x = self.cos_theta * r.direction.x - self.sin_theta * r.direction.z;
-            let z = self.sin_theta * r.direction.x + self.cos_theta * r.direction.z;
-            Vec3::new(x, r.direction.y, z)
-        };
-
-        let rotated_r = Ray::with_time(origin, direction, r.time);
-
-        // Hit test in rotated space
-        if let Some(mut rec) = self.ptr.hit(&rotated_r, t_min, t_max) {
-            // Rotate hit point and normal back to world space
-            let p = {
-                let x = self.cos_theta * rec.p.x + self.sin_theta * rec.p.z;
-                let z = -self.sin_theta * rec.p.x + self.cos_theta * rec.p.z;
-                Point3::new(x, rec.p.y, z)
-            };
-
-            let normal = {
-                let x = self.cos_theta * rec.normal.x + self.sin_theta * rec.normal.z;
-                let z = -self.sin_theta * rec.normal.x + self.cos_theta * rec.normal.z;
-                Vec3::new(x, rec.normal.y, z)
-            };
-
-            rec.p = p;
-
-            rec.set_face_normal(&rotated_r, normal);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::rtweekend::Sampler;
+    use crate::sphere::Sphere;
+    use crate::vec3::random_unit_vector;
+    use std::f64::consts::PI;
+
+    fn sphere(center: Point3, radius: f64) -> Arc<dyn Hittable> {
+        let material = Arc::new(Lambertian::new_from_color(Color::new(0.5, 0.5, 0.5)));
+        Arc::new(Sphere::new(center, radius, material))
+    }
 
-            Some(rec)
+    // The unit sphere stretched into an ellipsoid twice as long in x.
+    fn ellipsoid() -> Transform {
+        Transform::new(sphere(Point3::new(0.0, 0.0, 0.0), 1.0), Mat4::scaling(Vec3::new(2.0, 1.0, 1.0)))
+    }
 
-        } else {
-            None
+    #[test]
+    fn hits_keep_world_distances_under_non_uniform_scale() {
+        let mut sampler = Sampler::new(0);
+        let r = Ray::new(Point3::new(-10.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let rec = ellipsoid().hit(&r, 0.001, INFINITY, &mut sampler).unwrap();
+        assert!((rec.t - 8.0).abs() < 1e-9);
+        assert!((rec.p - Point3::new(-2.0, 0.0, 0.0)).length() < 1e-9);
+        assert!((rec.normal - Vec3::new(-1.0, 0.0, 0.0)).length() < 1e-9);
+        assert!(rec.front_face);
+    }
+
+    #[test]
+    fn normals_stay_perpendicular_under_non_uniform_scale() {
+        let mut sampler = Sampler::new(0);
+        // x²/4 + y² + z² = 1 at x = 1, y = 0.5 has z = -√0.5, and the
+        // gradient (x/4, y, z) is the normal; the scaled object normal
+        // (x, y, z) would not be.
+        let r = Ray::new(Point3::new(1.0, 0.5, -10.0), Vec3::new(0.0, 0.0, 1.0));
+        let rec = ellipsoid().hit(&r, 0.001, INFINITY, &mut sampler).unwrap();
+        let z = -(0.5_f64).sqrt();
+        assert!((rec.t - (10.0 + z)).abs() < 1e-9);
+        assert!((rec.p - Point3::new(1.0, 0.5, z)).length() < 1e-9);
+        let expected = Vec3::new(0.25, 0.5, z).unit_vector();
+        assert!((rec.normal - expected).length() < 1e-9);
+    }
+
+    #[test]
+    fn pdf_under_uniform_scale_matches_a_bigger_sphere() {
+        let mut sampler = Sampler::new(0);
+        let placed = Transform::new(
+            sphere(Point3::new(0.0, 0.0, 0.0), 1.0),
+            Mat4::translation(Vec3::new(1.0, 2.0, 3.0)) * Mat4::scaling(Vec3::new(2.0, 2.0, 2.0)),
+        );
+        let direct = sphere(Point3::new(1.0, 2.0, 3.0), 2.0);
+        let origin = Point3::new(-4.0, 1.0, 8.0);
+        for direction in [Vec3::new(5.0, 1.0, -5.0), Vec3::new(5.5, 1.2, -5.0), Vec3::new(0.0, 1.0, 0.0)] {
+            let expected = direct.pdf_value(&origin, &direction, &mut sampler);
+            assert!((placed.pdf_value(&origin, &direction, &mut sampler) - expected).abs() < 1e-9 * expected.max(1.0));
         }
     }
 
-    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
-        if self.hasbox {
-            Some(self.bbox)
-        } else {
-            None
+    #[test]
+    fn pdf_under_non_uniform_scale_integrates_to_one() {
+        let mut sampler = Sampler::new(0);
+        let object = ellipsoid();
+        let origin = Point3::new(0.5, 0.3, -3.0);
+
+        // Uniform directions over the sphere: the mean pdf times 4π.
+        let n = 400_000;
+        let total: f64 = (0..n).map(|_| object.pdf_value(&origin, &random_unit_vector(&mut sampler), &mut sampler)).sum();
+        let integral = total / n as f64 * 4.0 * PI;
+        assert!((integral - 1.0).abs() < 0.03, "{}", integral);
+
+        // Sampled directions land on the object and have a density there.
+        for _ in 0..1000 {
+            let direction = object.random(&origin, &mut sampler);
+            assert!(object.hit(&Ray::new(origin, direction), 0.001, INFINITY, &mut sampler).is_some());
+            assert!(object.pdf_value(&origin, &direction, &mut sampler) > 0.0);
         }
     }
 }
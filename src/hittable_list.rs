@@ -1,8 +1,6 @@
 use std::sync::Arc;
 
-use rand::{distr::weighted::Weight, random, seq::IndexedRandom};
-
-use crate::{aabb::Aabb, hittable::{HitRecord, Hittable}, ray::Ray, rtweekend::random_int, vec3::{Point3, Vec3}};
+use crate::{aabb::Aabb, hittable::{HitRecord, Hittable}, ray::Ray, rtweekend::Sampler, vec3::{Point3, Vec3}};
 
 pub struct HittableList {
     pub objects: Vec<Arc<dyn Hittable>>,
@@ -16,19 +14,15 @@ impl HittableList {
     pub fn add(&mut self, object: Arc<dyn Hittable>) {
         self.objects.push(object);
     }
-
-    pub fn clear(&mut self) {
-        self.objects.clear();
-    }
 }
 
 impl Hittable for HittableList {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, sampler: &mut Sampler) -> Option<HitRecord> {
         let mut closest_so_far = t_max;
         let mut hit_record = None;
 
         for object in &self.objects {
-            if let Some(temp_rec) = object.hit(ray, t_min, closest_so_far) {
+            if let Some(temp_rec) = object.hit(ray, t_min, closest_so_far, sampler) {
                 closest_so_far = temp_rec.t;
                 hit_record = Some(temp_rec);
             }
@@ -59,20 +53,28 @@ impl Hittable for HittableList {
         output_box
     }
 
-    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, sampler: &mut Sampler) -> f64 {
         let weight = 1.0 / self.objects.len() as f64;
         let mut sum = 0.0;
 
         for object in &self.objects {
-            sum += weight * object.pdf_value(origin, direction);
+            sum += weight * object.pdf_value(origin, direction, sampler);
         }
 
         sum
     }
 
-    fn random(&self, origin: &Point3) -> Vec3 {
+    fn random(&self, origin: &Point3, sampler: &mut Sampler) -> Vec3 {
         let int_size = self.objects.len() as i32;
-        let idx = random_int(0, int_size - 1) as usize;
-        self.objects[idx].random(origin)
+        let idx = sampler.random_int(0, int_size - 1) as usize;
+        self.objects[idx].random(origin, sampler)
+    }
+
+    fn can_sample(&self) -> bool {
+        !self.objects.is_empty() && self.objects.iter().all(|object| object.can_sample())
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.objects.iter().any(|object| object.is_emissive())
     }
 }
@@ -0,0 +1,192 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    bvh::LinearBvh,
+    hittable::{HitRecord, Hittable, Transform},
+    material::Material,
+    matrix::Mat4,
+    ray::Ray,
+    rtweekend::Sampler,
+    vec3::{Point3, Vec3},
+};
+
+// One placement of a shared prototype. The prototype's geometry and its
+// BVH are never copied, so thousands of instances cost a matrix each. The
+// scene's BVH over the instances is the top level of a two-level
+// structure; rays that reach an instance continue into the prototype's own
+// BVH (the bottom level) in object space.
+pub struct Instance {
+    transform: Transform,
+    // Replaces whatever materials the prototype was built with.
+    material: Option<Arc<dyn Material>>,
+}
+
+impl Instance {
+    pub fn new(prototype: Arc<dyn Hittable>, matrix: Mat4, material: Option<Arc<dyn Material>>) -> Self {
+        Self {
+            transform: Transform::new(prototype, matrix),
+            material,
+        }
+    }
+}
+
+// The bottom level for a prototype made of several objects. A single
+// object is used as is, since a mesh already carries its own BVH.
+pub fn prototype(mut objects: Vec<Arc<dyn Hittable>>, time0: f64, time1: f64) -> Arc<dyn Hittable> {
+    if objects.len() == 1 {
+        objects.pop().unwrap()
+    } else {
+        Arc::new(LinearBvh::new(&mut objects, time0, time1))
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, sampler: &mut Sampler) -> Option<HitRecord> {
+        let mut rec = self.transform.hit(r, t_min, t_max, sampler)?;
+        if let Some(material) = &self.material {
+            rec.material = Arc::clone(material);
+        }
+        Some(rec)
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+        self.transform.bounding_box(time0, time1)
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, sampler: &mut Sampler) -> f64 {
+        self.transform.pdf_value(origin, direction, sampler)
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut Sampler) -> Vec3 {
+        self.transform.random(origin, sampler)
+    }
+
+    fn can_sample(&self) -> bool {
+        self.transform.can_sample()
+    }
+
+    // An emissive override only makes a light of a prototype whose parts
+    // can all be sampled.
+    fn is_emissive(&self) -> bool {
+        match &self.material {
+            Some(material) => material.is_emissive() && self.transform.can_sample(),
+            None => self.transform.is_emissive(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        material::{DiffuseLight, Lambertian},
+        rtweekend::{INFINITY, Sampler},
+        sphere::Sphere,
+        texture::SolidColor,
+        torus::Torus,
+        vec3::{random_unit_vector, Color},
+    };
+
+    fn gray() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new_from_color(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    fn light() -> Arc<dyn Material> {
+        Arc::new(DiffuseLight::new(Arc::new(SolidColor::new(Color::new(4.0, 4.0, 4.0)))))
+    }
+
+    // Two spheres side by side in object space.
+    fn pair(material: Arc<dyn Material>) -> Arc<dyn Hittable> {
+        prototype(
+            vec![
+                Arc::new(Sphere::new(Point3::new(-1.0, 0.0, 0.0), 0.8, Arc::clone(&material))),
+                Arc::new(Sphere::new(Point3::new(1.0, 0.0, 0.0), 0.5, material)),
+            ],
+            0.0,
+            1.0,
+        )
+    }
+
+    fn placement() -> Mat4 {
+        Mat4::translation(Vec3::new(3.0, -1.0, 2.0))
+            * Mat4::rotation(Vec3::new(1.0, 1.0, 0.0), 40.0)
+            * Mat4::scaling(Vec3::new(1.5, 0.5, 2.0))
+    }
+
+    #[test]
+    fn hits_are_the_prototype_hits_moved_into_place() {
+        let mut sampler = Sampler::new(0);
+        let material = gray();
+        let prototype = pair(Arc::clone(&material));
+        let matrix = placement();
+        let inverse = matrix.inverse().unwrap();
+        let instance = Instance::new(Arc::clone(&prototype), matrix, None);
+
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let origin = Point3::new(sampler.random_double_range(-5.0, 10.0), sampler.random_double_range(-5.0, 5.0), sampler.random_double_range(-5.0, 10.0));
+            let target = matrix.transform_point(Point3::new(sampler.random_double_range(-2.0, 2.0), 0.0, 0.0)) + 0.5 * random_unit_vector(&mut sampler);
+            let r = Ray::new(origin, target - origin);
+            let local = Ray::new(inverse.transform_point(r.origin), inverse.transform_vector(r.direction));
+
+            match (instance.hit(&r, 0.001, INFINITY, &mut sampler), prototype.hit(&local, 0.001, INFINITY, &mut sampler)) {
+                (Some(placed), Some(original)) => {
+                    assert!((placed.t - original.t).abs() < 1e-9);
+                    assert!((placed.p - matrix.transform_point(original.p)).length() < 1e-9);
+                    assert!((placed.p - r.at(placed.t)).length() < 1e-9);
+                    assert_eq!(placed.front_face, original.front_face);
+                    assert!(Arc::ptr_eq(&placed.material, &material));
+                    hits += 1;
+                }
+                (None, None) => {}
+                _ => panic!("the instance and its prototype disagree"),
+            }
+        }
+        assert!(hits > 500, "{} hits", hits);
+    }
+
+    #[test]
+    fn override_material_replaces_the_prototype_one() {
+        let mut sampler = Sampler::new(0);
+        let red: Arc<dyn Material> = Arc::new(Lambertian::new_from_color(Color::new(0.8, 0.1, 0.1)));
+        let instance = Instance::new(pair(gray()), Mat4::translation(Vec3::new(0.0, 0.0, -5.0)), Some(Arc::clone(&red)));
+        let r = Ray::new(Point3::new(-1.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = instance.hit(&r, 0.001, INFINITY, &mut sampler).unwrap();
+        assert!((rec.t - 9.2).abs() < 1e-9);
+        assert!(Arc::ptr_eq(&rec.material, &red));
+    }
+
+    #[test]
+    fn emission_follows_the_override() {
+        let matrix = placement();
+        assert!(!Instance::new(pair(gray()), matrix, None).is_emissive());
+        assert!(!Instance::new(pair(light()), matrix, Some(gray())).is_emissive());
+
+        // A multi-part prototype lit by the override is sampled through its
+        // parts, and every sampled direction leads back to the instance.
+        let mut sampler = Sampler::new(1);
+        let lit = Instance::new(pair(gray()), matrix, Some(light()));
+        assert!(lit.is_emissive());
+        let origin = Point3::new(-6.0, 4.0, 1.0);
+        for _ in 0..100 {
+            let direction = lit.random(&origin, &mut sampler);
+            assert!(lit.hit(&Ray::new(origin, direction), 0.001, INFINITY, &mut sampler).is_some());
+            assert!(lit.pdf_value(&origin, &direction, &mut sampler) > 0.0);
+        }
+
+        // A part with no light sampling keeps the override out of the light list.
+        let torus: Arc<dyn Hittable> = Arc::new(Torus::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 1.0, 0.25, gray()));
+        assert!(!Instance::new(prototype(vec![torus], 0.0, 1.0), matrix, Some(light())).is_emissive());
+
+        // A single part is its own bottom level and is sampled like the
+        // placed object.
+        let part: Arc<dyn Hittable> = Arc::new(Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, light()));
+        let instance = Instance::new(prototype(vec![Arc::clone(&part)], 0.0, 1.0), matrix, None);
+        assert!(instance.is_emissive());
+        let direction = matrix.transform_point(Point3::new(0.0, 0.0, 0.0)) - origin;
+        let placed = Transform::new(part, matrix);
+        assert_eq!(instance.pdf_value(&origin, &direction, &mut sampler), placed.pdf_value(&origin, &direction, &mut sampler));
+        assert!(instance.pdf_value(&origin, &direction, &mut sampler) > 0.0);
+    }
+}
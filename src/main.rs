@@ -1,19 +1,16 @@
-use aarect::{XYRect, XZRect, YZRect};
-use bvh::BvhNode;
-use camera::Camera;
-use color::write_color;
-use constant_medium::ConstantMedium;
-use cuboid::Cuboid;
-use cylinder::Cylinder;
-use hittable::{FlipFace, Hittable, RotateY, Translate};
-use hittable_list::HittableList;
-use material::{Dielectric, DiffuseLight, EmptyMaterial, Lambertian, Material, Metal};
-use moving_sphere::MovingSphere;
-use pdf::{CosinePdf, HittablePdf, MixturePdf, Pdf};
-use rtweekend::{random_double, random_double_range, INFINITY};
-use sphere::Sphere;
-use texture::{CheckerTexture, ImageTexture, NoiseTexture, SolidColor, Texture};
-use std::{io::{self, Write}, sync::{atomic::{AtomicI32, Ordering}, Arc}};
+use clap::Parser;
+use bench::bench_bvh;
+use bvh::SplitMethod;
+use cli::Cli;
+use output::{write_image, write_ppm, OutputFormat};
+use hittable::{HitRecord, Hittable};
+use pdf::{power_heuristic, HittablePdf, MixturePdf, Pdf};
+use rtweekend::{INFINITY, Sampler};
+use scene::{Lights, Scene};
+use spectrum::SampledWavelengths;
+use gltf_scene::load_gltf;
+use scene_file::load_scene;
+use std::{error::Error, io::{self, BufWriter, Write}, path::Path, sync::{atomic::{AtomicI32, Ordering}, Arc}};
 use rayon::prelude::*;
 
 mod vec3;
@@ -30,711 +27,338 @@ mod aabb;
 mod bvh;
 mod texture;
 mod perlin;
-mod aarect;
+mod quad;
 mod cuboid;
 mod constant_medium;
 mod onb;
 mod pdf;
+mod microfacet;
+mod spectrum;
 mod cylinder;
+mod disk;
+mod cone;
+mod torus;
+mod capsule;
+mod paraboloid;
+mod triangle;
+mod obj;
+mod ply;
+mod gltf_scene;
+mod matrix;
+mod instance;
+mod animation;
+mod scene;
+mod bench;
+mod scene_file;
+mod scenes;
+mod cli;
+mod output;
 
 use ray::Ray;
-use vec3::{dot, Color, Point3, Vec3};
+use vec3::{dot, Color, Vec3};
 
+// Radiance arriving along r, traced as an iterative path. Every vertex
+// multiplies its weight into `throughput`; after `min_bounces` the path is
+// ended by Russian roulette with a chance that follows the throughput, and
+// `max_depth` only remains as a safety cap.
 fn ray_color(
     r: &Ray,
     background: &Color,
     world: &Arc<dyn Hittable>,
-    lights: &Arc<dyn Hittable>,
-    depth: u32
+    lights: &Lights,
+    max_depth: u32,
+    min_bounces: u32,
+    sampler: &mut Sampler,
 ) -> Color {
+    let mut color = Color::ZERO;
+    let mut throughput = Color::new(1.0, 1.0, 1.0);
+    let mut ray = *r;
+
+    // Solid angle density the previous vertex sampled the ray with; None for
+    // camera rays and after specular bounces, where light sampling could not
+    // have found the emitter.
+    let mut scatter_pdf: Option<f64> = None;
+
+    // Absorption coefficient of the medium the ray travels through, if any.
+    let mut medium: Option<Color> = None;
+
+    // Wavelengths the path carries once a dispersive surface has split it,
+    // each with its own weight; `spectral` is their estimate in RGB. Until
+    // then the radiance doesn't depend on wavelength and it stays white.
+    let mut wavelengths: Option<SampledWavelengths> = None;
+    let mut beta = [1.0; 4];
+    let mut spectral = Color::new(1.0, 1.0, 1.0);
+
+    for bounce in 0..max_depth {
+        let rec = match world.hit(&ray, 0.001, INFINITY, sampler) {
+            Some(rec) => rec,
+            None => {
+                color += throughput * spectral * *background;
+                break;
+            }
+        };
 
-    // If we have exceeded the ray bounce limit, no more light is gathered.
-    if depth == 0 {
-        return Color::new(0.0, 0.0, 0.0);
-    }
+        if let Some(sigma) = medium {
+            throughput = throughput * transmittance(sigma, rec.t * ray.direction.length());
+        }
 
-    let rec = match world.hit(&r, 0.001, INFINITY) {
-        Some(rec) => rec,
-        None => return *background,
-    };
+        let mut color_from_emission = rec.material.emitted(rec.u, rec.v, &rec.p, &rec);
 
-    let color_from_emission = rec.material.emitted(rec.u, rec.v, &rec.p, &rec);
+        // Emission found by scattering was also reachable by the shadow ray of
+        // the previous vertex, so it only gets its share of the two strategies.
+        if let (Some(scatter_pdf), Some(emitters)) = (scatter_pdf, &lights.emitters) {
+            let light_pdf = emitters.pdf_value(&ray.origin, &ray.direction, sampler);
+            color_from_emission = power_heuristic(scatter_pdf, light_pdf) * color_from_emission;
+        }
+        color += throughput * spectral * color_from_emission;
 
-    let srec = match rec.material.scatter(r, &rec) {
-        Some(srec) => srec,
-        None => return color_from_emission,
-    };
+        if rec.material.is_dispersive() && wavelengths.is_none() {
+            wavelengths = Some(SampledWavelengths::sample(sampler.random_double()));
+        }
+        ray.wavelength = wavelengths.map(|w| w.hero());
+
+        let srec = match rec.material.scatter(&ray, &rec, sampler) {
+            Some(srec) => srec,
+            None => break,
+        };
+
+        if srec.skip_pdf {
+            // Only the hero can follow a sharp refraction.
+            if let Some(w) = wavelengths.as_mut().filter(|_| rec.material.is_dispersive()) {
+                w.terminate_secondary();
+                spectral = w.to_rgb(beta);
+            }
+            throughput = throughput * srec.attenuation;
+            ray = srec.skip_pdf_ray;
+            scatter_pdf = None;
+        } else if let Some(w) = wavelengths.filter(|_| rec.material.is_dispersive()) {
+            ray = match scatter_dispersive(&ray, &rec, &w, &mut beta, sampler) {
+                Some(scattered) => scattered,
+                None => break,
+            };
+            spectral = w.to_rgb(beta);
+            throughput = throughput * srec.attenuation;
+            scatter_pdf = None;
+        } else {
+            let material_pdf = srec.pdf_ptr.unwrap();
+            let pdf: Arc<dyn Pdf> = match &lights.sampled {
+                Some(sampled) => {
+                    let sampled_ptr = Arc::new(HittablePdf::new(Arc::clone(sampled), rec.p));
+                    Arc::new(MixturePdf::new(sampled_ptr, material_pdf))
+                }
+                None => material_pdf,
+            };
 
-    if srec.skip_pdf {
-        return srec.attenuation * ray_color(&srec.skip_pdf_ray, background, world, lights, depth - 1);
-    }
+            if let Some(emitters) = &lights.emitters {
+                color += throughput * spectral * sample_light(&ray, &rec, srec.attenuation, pdf.as_ref(), world, emitters, medium, sampler);
+            }
 
-    let light_ptr = Arc::new(HittablePdf::new(Arc::clone(lights), rec.p));
-    let mixed_pdf = MixturePdf::new(light_ptr, srec.pdf_ptr.unwrap());
+            let direction = pdf.generate(sampler);
+            if direction.length_squared() == 0.0 {
+                break;
+            }
+            let scattered = Ray::with_time(rec.p, direction, ray.time);
+            let pdf_val = pdf.value(&scattered.direction, sampler);
+            if pdf_val < 1e-15 {
+                break;
+            }
 
-    let direction = mixed_pdf.generate();
-    let scattered = Ray::with_time(rec.p, direction, r.time);
-    let pdf_val = mixed_pdf.value(&scattered.direction);
-    let scattering_pdf = rec.material.scattering_pdf(&r, &rec, &scattered);
+            let scattering = rec.material.scattering(&ray, &rec, &scattered, srec.attenuation);
+            throughput = throughput * scattering / pdf_val;
+            ray = scattered;
+            scatter_pdf = Some(pdf_val);
+        }
+        medium = medium_after(medium, &rec, &ray.direction);
 
-    if pdf_val < 1e-15 {
-        return color_from_emission;
+        if bounce + 1 >= min_bounces {
+            let survive = throughput.x.max(throughput.y).max(throughput.z).min(0.95);
+            if sampler.random_double() >= survive {
+                break;
+            }
+            throughput = throughput / survive;
+        }
     }
 
-    let sample_color = ray_color(&scattered, background, world, lights, depth - 1);
-    let color_from_scatter =
-        (srec.attenuation * scattering_pdf * sample_color ) / pdf_val;
-
-    return color_from_emission + color_from_scatter;
+    color
 }
 
-pub fn final_scene() -> Arc<dyn Hittable> {
-    let mut objects = vec![];
-
-    // Ground: grid of boxes
-    let mut boxes1: Vec<Arc<dyn Hittable>> = vec![];
-    let ground: Arc<dyn Material> = Arc::new(Lambertian {
-        albedo: Arc::new(SolidColor::new(Color::new(0.48, 0.83, 0.53))),
-    });
-    let boxes_per_side = 20;
-
-    for i in 0..boxes_per_side {
-        for j in 0..boxes_per_side {
-            let w = 100.0;
-            let x0 = -1000.0 + i as f64 * w;
-            let z0 = -1000.0 + j as f64 * w;
-            let y0 = 0.0;
-            let x1 = x0 + w;
-            let y1 = random_double_range(1.0, 101.0);
-            let z1 = z0 + w;
-
-            let cube = Arc::new(Cuboid::new(
-                Point3::new(x0, y0, z0),
-                Point3::new(x1, y1, z1),
-                Arc::clone(&ground),
-            ));
-            boxes1.push(cube);
-        }
+// Hero wavelength MIS at a rough dispersive surface: the direction comes
+// from the hero's lobe, and since any of the wavelengths could have been
+// the hero, each is weighted by the balance heuristic over all their lobes.
+// Light sampling is left to the other vertices of the path.
+fn scatter_dispersive(
+    r: &Ray,
+    rec: &HitRecord,
+    wavelengths: &SampledWavelengths,
+    beta: &mut [f64; 4],
+    sampler: &mut Sampler,
+) -> Option<Ray> {
+    let active: Vec<usize> = (0..4).filter(|&i| wavelengths.pdf[i] > 0.0).collect();
+    let lobes: Vec<(Ray, Option<Arc<dyn Pdf>>)> = active
+        .iter()
+        .map(|&i| {
+            let r_i = Ray { wavelength: Some(wavelengths.lambda[i]), ..*r };
+            let pdf = rec.material.scatter(&r_i, rec, sampler).and_then(|srec| srec.pdf_ptr);
+            (r_i, pdf)
+        })
+        .collect();
+
+    let direction = lobes[0].1.as_ref()?.generate(sampler);
+    if direction.length_squared() == 0.0 {
+        return None;
     }
-
-    objects.push(Arc::new(BvhNode::new(&mut boxes1, 0.0, 1.0)) as Arc<dyn Hittable>);
-
-    // Light
-    let light: Arc<dyn Material> = Arc::new(DiffuseLight {
-        emit: Arc::new(SolidColor::new(Color::new(7.0, 7.0, 7.0))),
-    });
-    objects.push(Arc::new(XZRect::new(123.0, 423.0, 147.0, 412.0, 554.0, Arc::clone(&light))));
-
-    // Moving sphere
-    let center1 = Point3::new(400.0, 400.0, 200.0);
-    let center2 = center1 + Vec3::new(30.0, 0.0, 0.0);
-    let moving_mat: Arc<dyn Material> = Arc::new(Lambertian {
-        albedo: Arc::new(SolidColor::new(Color::new(0.7, 0.3, 0.1))),
-    });
-    objects.push(Arc::new(MovingSphere::new(center1, center2, 0.0, 1.0, 50.0, moving_mat)));
-
-    // Glass ball
-    objects.push(Arc::new(Sphere::new(
-        Point3::new(260.0, 150.0, 45.0),
-        50.0,
-        Arc::new(Dielectric::new(1.5)),
-    )));
-
-    // Metal ball
-    objects.push(Arc::new(Sphere::new(
-        Point3::new(0.0, 150.0, 145.0),
-        50.0,
-        Arc::new(Metal::new(Color::new(0.8, 0.8, 0.9), 1.0)),
-    )));
-
-    // Subsurface blue fog inside glass
-    let boundary1: Arc<dyn Hittable> = Arc::new(Sphere::new(
-        Point3::new(360.0, 150.0, 145.0),
-        70.0,
-        Arc::new(Dielectric::new(1.5)),
-    ));
-    objects.push(Arc::clone(&boundary1));
-    objects.push(Arc::new(ConstantMedium::from_color(
-        boundary1,
-        0.2,
-        Color::new(0.2, 0.4, 0.9),
-    )));
-
-    // Global white fog
-    let boundary2 = Arc::new(Sphere::new(
-        Point3::new(0.0, 0.0, 0.0),
-        5000.0,
-        Arc::new(Dielectric::new(1.5)),
-    ));
-    objects.push(Arc::new(ConstantMedium::from_color(
-        boundary2,
-        0.0001,
-        Color::new(1.0, 1.0, 1.0),
-    )));
-
-    // Textured Earth sphere
-    let earth_texture = Arc::new(ImageTexture::new("earthmap.jpg"));
-    let earth_surface: Arc<dyn Material> = Arc::new(Lambertian {
-        albedo: earth_texture,
-    });
-    objects.push(Arc::new(Sphere::new(
-        Point3::new(400.0, 200.0, 400.0),
-        100.0,
-        Arc::clone(&earth_surface),
-    )));
-
-    // Perlin noise sphere
-    let noise_texture = Arc::new(NoiseTexture::new(0.1));
-    let noise_material: Arc<dyn Material> = Arc::new(Lambertian {
-        albedo: noise_texture,
-    });
-    objects.push(Arc::new(Sphere::new(
-        Point3::new(220.0, 280.0, 300.0),
-        80.0,
-        noise_material,
-    )));
-
-    // Small spheres cluster
-    let mut boxes2: Vec<Arc<dyn Hittable>> = vec![];
-    let white: Arc<dyn Material> = Arc::new(Lambertian {
-        albedo: Arc::new(SolidColor::new(Color::new(0.73, 0.73, 0.73))),
-    });
-
-    for _ in 0..1000 {
-        boxes2.push(Arc::new(Sphere::new(
-            Point3::random_range(0.0, 165.0),
-            10.0,
-            Arc::clone(&white),
-        )));
+    let scattered = Ray::with_time(rec.p, direction, r.time);
+    let pdf_sum: f64 = lobes.iter().filter_map(|(_, pdf)| pdf.as_ref()).map(|pdf| pdf.value(&direction, sampler)).sum();
+    let mean_pdf = pdf_sum / active.len() as f64;
+    if mean_pdf < 1e-15 {
+        return None;
     }
 
-    let cluster = Arc::new(Translate::new(
-        Arc::new(RotateY::new(
-            Arc::new(BvhNode::new(&mut boxes2, 0.0, 1.0)),
-            15.0,
-        )),
-        Vec3::new(-100.0, 270.0, 395.0),
-    ));
-    objects.push(cluster);
-
-    Arc::new(BvhNode::new(&mut objects, 0.0, 1.0))
-}
-
-pub fn cornell_smoke() -> Arc<dyn Hittable> {
-    let mut objects: Vec<Arc<dyn Hittable>> = vec![];
-
-    let red: Arc<dyn Material> = Arc::new(Lambertian {
-        albedo: Arc::new(SolidColor::new(Color::new(0.65, 0.05, 0.05))),
-    });
-
-    let white: Arc<dyn Material> = Arc::new(Lambertian {
-        albedo: Arc::new(SolidColor::new(Color::new(0.73, 0.73, 0.73))),
-    });
-
-    let green: Arc<dyn Material> = Arc::new(Lambertian {
-        albedo: Arc::new(SolidColor::new(Color::new(0.12, 0.45, 0.15))),
-    });
-
-    let light: Arc<dyn Material> = Arc::new(DiffuseLight {
-        emit: Arc::new(SolidColor::new(Color::new(15.0, 15.0, 15.0))),
-    });
-
-    objects.push(Arc::new(YZRect::new(0.0, 555.0, 0.0, 555.0, 555.0, Arc::clone(&green))));
-    objects.push(Arc::new(YZRect::new(0.0, 555.0, 0.0, 555.0, 0.0, Arc::clone(&red))));
-
-    objects.push(Arc::new(XZRect::new(213.0, 343.0, 227.0, 332.0, 554.0, Arc::clone(&light))));
-
-    objects.push(Arc::new(XZRect::new(0.0, 555.0, 0.0, 555.0, 0.0, Arc::clone(&white))));
-    objects.push(Arc::new(XZRect::new(0.0, 555.0, 0.0, 555.0, 555.0, Arc::clone(&white))));
-    objects.push(Arc::new(XYRect::new(0.0, 555.0, 0.0, 555.0, 555.0, Arc::clone(&white))));
-
-    // two boxes
-
-    let box1: Arc<dyn Hittable> = Arc::new(Cuboid::new(
-        Point3::new(0.0, 0.0, 0.0),
-        Point3::new(165.0, 330.0, 165.0),
-        Arc::clone(&white),
-    ));
-    let box1 = Arc::new(RotateY::new(box1, 15.0));
-    let box1 = Arc::new(Translate::new(box1, Vec3::new(265.0, 0.0, 295.0)));
-    let smoke1 = Arc::new(ConstantMedium::from_color(box1, 0.01, Color::new(0.0, 0.0, 0.0)));
-    objects.push(smoke1);
-
-
-    let box2: Arc<dyn Hittable> = Arc::new(Cuboid::new(
-        Point3::new(0.0, 0.0, 0.0),
-        Point3::new(165.0, 165.0, 165.0),
-        Arc::clone(&white),
-    ));
-    let box2 = Arc::new(RotateY::new(box2, -18.0));
-    let box2 = Arc::new(Translate::new(box2, Vec3::new(130.0, 0.0, 65.0)));
-    let smoke2 = Arc::new(ConstantMedium::from_color(box2, 0.01, Color::new(1.0, 1.0, 1.0)));
-    objects.push(smoke2);
-
-    Arc::new(BvhNode::new(&mut objects, 0.0, 1.0))
-}
-
-pub fn cornell_box() -> Arc<dyn Hittable> {
-    let mut objects: Vec<Arc<dyn Hittable>> = vec![];
-
-    let red: Arc<dyn Material> = Arc::new(Lambertian {
-        albedo: Arc::new(SolidColor::new(Color::new(0.65, 0.05, 0.05))),
-    });
-
-    let white: Arc<dyn Material> = Arc::new(Lambertian {
-        albedo: Arc::new(SolidColor::new(Color::new(0.73, 0.73, 0.73))),
-    });
-
-    let green: Arc<dyn Material> = Arc::new(Lambertian {
-        albedo: Arc::new(SolidColor::new(Color::new(0.12, 0.45, 0.15))),
-    });
-
-    let light: Arc<dyn Material> = Arc::new(DiffuseLight {
-        emit: Arc::new(SolidColor::new(Color::new(15.0, 15.0, 15.0))),
-    });
-
-    objects.push(Arc::new(YZRect::new(0.0, 555.0, 0.0, 555.0, 555.0, Arc::clone(&green))));
-    objects.push(Arc::new(YZRect::new(0.0, 555.0, 0.0, 555.0, 0.0, Arc::clone(&red))));
-
-    objects.push(Arc::new(
-        FlipFace::new(Arc::new(
-            XZRect::new(213.0, 343.0, 227.0, 332.0, 554.0, Arc::clone(&light))
-        ))
-    ));
-
-    objects.push(Arc::new(XZRect::new(0.0, 555.0, 0.0, 555.0, 0.0, Arc::clone(&white))));
-    objects.push(Arc::new(XZRect::new(0.0, 555.0, 0.0, 555.0, 555.0, Arc::clone(&white))));
-    objects.push(Arc::new(XYRect::new(0.0, 555.0, 0.0, 555.0, 555.0, Arc::clone(&white))));
-
-    // room objects
-
-    let aluminum = Arc::new(Metal::new(Color::new(0.8, 0.85, 0.88), 0.0));
-
-    let box1: Arc<dyn Hittable> = Arc::new(Cuboid::new(
-        Point3::new(0.0, 0.0, 0.0),
-        Point3::new(165.0, 330.0, 165.0),
-        //Arc::clone(&white),
-        aluminum.clone(),
-    ));
-    let box1 = Arc::new(RotateY::new(box1, 15.0));
-    let box1 = Arc::new(Translate::new(box1, Vec3::new(265.0, 0.0, 295.0)));
-    //objects.push(box1);
-
-    // cylinder
-
-    let cyl: Arc<dyn Hittable> = Arc::new(Cylinder::new(
-        0.0,
-        330.0,
-        83.0,
-        aluminum.clone(),
-        //Arc::clone(&white),
-    ));
-    let cyl = Arc::new(Translate::new(cyl, Vec3::new(348.0, 0.0, 378.0)));
-    objects.push(cyl);
-
-    // front small box
-
-    let box2: Arc<dyn Hittable> = Arc::new(Cuboid::new(
-        Point3::new(0.0, 0.0, 0.0),
-        Point3::new(165.0, 165.0, 165.0),
-        Arc::clone(&white),
-    ));
-    let box2 = Arc::new(RotateY::new(box2, -18.0));
-    let box2 = Arc::new(Translate::new(box2, Vec3::new(130.0, 0.0, 65.0)));
-    //objects.push(box2);
-
-    // glass ball
-    let glass = Arc::new(Dielectric::new(1.5));
-    let ball = Arc::new(Sphere::new(
-        Point3::new(190.0, 90.0, 190.0),
-        90.0,
-        glass,
-    ));
-    objects.push(ball);
-
-    Arc::new(BvhNode::new(&mut objects, 0.0, 1.0))
-}
-
-pub fn earth() -> Arc<dyn Hittable> {
-    let earth_texture: Arc<dyn Texture> = Arc::new(ImageTexture::new("earthmap.jpg"));
-    let earth_surface: Arc<dyn Material> = Arc::new(Lambertian { albedo: earth_texture });
-    let globe: Arc<dyn Hittable> = Arc::new(Sphere {
-        center: Point3::new(0.0, 0.0, 0.0),
-        radius: 2.0,
-        material: earth_surface,
-    });
-
-    Arc::new(BvhNode::new(&mut vec![globe], 0.0, 1.0))
+    // A dispersive surface leaves its color out of scattering_pdf, which is
+    // then the whole BSDF times the cosine at each wavelength.
+    for (&i, (r_i, _)) in active.iter().zip(&lobes) {
+        beta[i] *= rec.material.scattering_pdf(r_i, rec, &scattered) / mean_pdf;
+    }
+    Some(scattered)
 }
 
-/*
-pub fn simple_light() -> Arc<dyn Hittable> {
-    let mut objects: Vec<Arc<dyn Hittable>> = vec![];
-
-    let pertext = Arc::new(NoiseTexture::new(4.0));
-    let lambert: Arc<dyn Material> = Arc::new(Lambertian { albedo: pertext });
-
-    objects.push(Arc::new(Sphere {
-        center: Point3::new(0.0, -1000.0, 0.0),
-        radius: 1000.0,
-        material: Arc::clone(&lambert),
-    }));
-
-    objects.push(Arc::new(Sphere {
-        center: Point3::new(0.0, 2.0, 0.0),
-        radius: 2.0,
-        material: Arc::clone(&lambert),
-    }));
+// Next-event estimation: one shadow ray toward a point on the emitters,
+// weighted against the chance of the scattering pdf picking the same direction.
+#[allow(clippy::too_many_arguments)]
+fn sample_light(
+    r: &Ray,
+    rec: &HitRecord,
+    attenuation: Color,
+    scatter_pdf: &dyn Pdf,
+    world: &Arc<dyn Hittable>,
+    emitters: &Arc<dyn Hittable>,
+    medium: Option<Color>,
+    sampler: &mut Sampler,
+) -> Color {
+    let shadow_ray = Ray::with_time(rec.p, emitters.random(&rec.p, sampler), r.time);
+    let light_pdf = emitters.pdf_value(&shadow_ray.origin, &shadow_ray.direction, sampler);
+    // Zero when the surface cannot scatter toward the light at all.
+    let scattering_pdf = rec.material.scattering_pdf(r, rec, &shadow_ray);
 
-    let light: Arc<dyn Material> = Arc::new(DiffuseLight {
-        emit: Arc::new(SolidColor::new(Color::new(4.0, 4.0, 4.0))),
-    });
+    if light_pdf < 1e-15 || scattering_pdf <= 0.0 {
+        return Color::ZERO;
+    }
 
-    objects.push(Arc::new(XYRect::new(3.0, 5.0, 1.0, 3.0, -2.0, light)));
+    // Whatever the shadow ray hits first is what it sees; an occluder emits nothing.
+    let light_rec = match world.hit(&shadow_ray, 0.001, INFINITY, sampler) {
+        Some(light_rec) => light_rec,
+        None => return Color::ZERO,
+    };
+    let emitted = light_rec.material.emitted(light_rec.u, light_rec.v, &light_rec.p, &light_rec);
 
-    Arc::new(BvhNode::new(&mut objects, 0.0, 1.0))
-}
-*/
-
-pub fn simple_light() -> Arc<dyn Hittable> {
-    let mut objects: Vec<Arc<dyn Hittable>> = vec![];
-
-    let pertext = Arc::new(NoiseTexture::new(4.0));
-    let lambert: Arc<dyn Material> = Arc::new(Lambertian { albedo: pertext });
-
-    objects.push(Arc::new(Sphere {
-        center: Point3::new(0.0, -1000.0, 0.0),
-        radius: 1000.0,
-        material: Arc::clone(&lambert),
-    }));
-
-    objects.push(Arc::new(Sphere {
-        center: Point3::new(0.0, 2.0, 0.0),
-        radius: 2.0,
-        material: Arc::clone(&lambert),
-    }));
-
-    let light_color = Arc::new(SolidColor::new(Color::new(4.0, 4.0, 4.0)));
-    let light: Arc<dyn Material> = Arc::new(DiffuseLight { emit: light_color });
-
-    objects.push(Arc::new(XYRect::new(3.0, 5.0, 3.0, 5.0, -2.0, Arc::clone(&light))));
-
-    // Glowing sphere nearly under main sphere
-    objects.push(Arc::new(Sphere {
-        center: Point3::new(2.0, 0.5, -0.2),
-        radius: 0.5,
-        material: Arc::clone(&light),
-    }));
-
-    // Glowing ball on the left
-    objects.push(Arc::new(Sphere {
-        center: Point3::new(-3.5, 0.5, 2.0),
-        radius: 0.5,
-        material: Arc::clone(&light),
-    }));
-
-    Arc::new(BvhNode::new(&mut objects, 0.0, 1.0))
+    let weight = power_heuristic(light_pdf, scatter_pdf.value(&shadow_ray.direction, sampler));
+    let scattering = rec.material.scattering(r, rec, &shadow_ray, attenuation);
+    let absorbed = match medium_after(medium, rec, &shadow_ray.direction) {
+        Some(sigma) => transmittance(sigma, light_rec.t * shadow_ray.direction.length()),
+        None => Color::new(1.0, 1.0, 1.0),
+    };
+    weight * (scattering * absorbed * emitted) / light_pdf
 }
 
-pub fn two_perlin_spheres() -> Arc<dyn Hittable> {
-    let mut objects: Vec<Arc<dyn Hittable>> = vec![];
-
-    let pertext = Arc::new(NoiseTexture::new(4.0));
-
-    let pertext_material: Arc<dyn Material> = Arc::new(Lambertian {
-        albedo: pertext,
-    });
-
-    objects.push(Arc::new(Sphere {
-        center: Point3::new(0.0, -1000.0, 0.0),
-        radius: 1000.0,
-        material: Arc::clone(&pertext_material),
-    }));
-
-    objects.push(Arc::new(Sphere {
-        center: Point3::new(0.0, 2.0, 0.0),
-        radius: 2.0,
-        material: Arc::clone(&pertext_material),
-    }));
-
-    Arc::new(BvhNode::new(&mut objects, 0.0, 1.0))
+// The medium a ray leaving rec in `direction` travels through. Only medium
+// boundaries change it: crossing one from the front enters its medium and
+// crossing it from the back returns to the outside.
+fn medium_after(medium: Option<Color>, rec: &HitRecord, direction: &Vec3) -> Option<Color> {
+    match rec.material.absorption() {
+        Some(sigma) if dot(direction, &rec.normal) < 0.0 => rec.front_face.then_some(sigma),
+        _ => medium,
+    }
 }
 
-pub fn two_spheres() -> Arc<dyn Hittable> {
-    let mut objects: Vec<Arc<dyn Hittable>> = vec![];
-
-    let checker = Arc::new(CheckerTexture::from_colors(
-        Color::new(0.2, 0.3, 0.1),
-        Color::new(0.9, 0.9, 0.9),
-    ));
-
-    let checker_material: Arc<dyn Material> = Arc::new(Lambertian {
-        albedo: checker,
-    });
-
-    objects.push(Arc::new(Sphere {
-        center: Point3::new(0.0, -10.0, 0.0),
-        radius: 10.0,
-        material: Arc::clone(&checker_material),
-    }));
-
-    objects.push(Arc::new(Sphere {
-        center: Point3::new(0.0, 10.0, 0.0),
-        radius: 10.0,
-        material: Arc::clone(&checker_material),
-    }));
-
-    Arc::new(BvhNode::new(&mut objects, 0.0, 1.0))
+// Beer-Lambert: the fraction of light left after `distance` in a medium.
+fn transmittance(sigma: Color, distance: f64) -> Color {
+    Color::new((-sigma.x * distance).exp(), (-sigma.y * distance).exp(), (-sigma.z * distance).exp())
 }
 
-pub fn random_scene() -> Arc<dyn Hittable> {
-    let mut objects: Vec<Arc<dyn Hittable>> = vec![];
-
-    let checker = Arc::new(CheckerTexture::from_colors(
-        Color::new(0.2, 0.3, 0.1),
-        Color::new(0.9, 0.9, 0.9),
-    ));
-
-    let checker_material = Arc::new(Lambertian {
-        albedo: checker,
-    });
-
-    objects.push(Arc::new(Sphere {
-        center: Point3::new(0.0, -1000.0, 0.0),
-        radius: 1000.0,
-        material: checker_material,
-    }));
-
-    for a in -11..11 {
-        for b in -11..11 {
-            let choose_mat = random_double();
-            let center = Point3::new(
-                a as f64 + 0.9 * random_double(),
-                0.2,
-                b as f64 + 0.9 * random_double(),
-            );
-
-            if (center - Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
-                let sphere_material: Arc<dyn Material>;
-
-                if choose_mat < 0.8 {
-                    // Diffuse
-                    let albedo = Color::random() * Color::random();
-                    sphere_material = Arc::new(Lambertian {
-                        albedo: Arc::new(SolidColor::new(albedo)),
-                    });
-                    let center2 = center + Vec3::new(0.0, random_double_range(0.0, 0.5), 0.0);
-                    objects.push(Arc::new(MovingSphere {
-                        center0: center,
-                        center1: center2,
-                        time0: 0.0,
-                        time1: 1.0,
-                        radius: 0.2,
-                        material: Arc::clone(&sphere_material),
-                    }));
-                } else if choose_mat < 0.95 {
-                    let albedo = Color::random_range(0.5, 1.0);
-                    let fuzz = random_double_range(0.0, 0.5);
-                    sphere_material = Arc::new(Metal { albedo, fuzz});
-
-                    objects.push(Arc::new(Sphere {
-                        center,
-                        radius: 0.2,
-                        material: sphere_material,
-                    }));
-                } else {
-                    // Glass
-                    sphere_material = Arc::new(Dielectric { ir: 1.5 });
-
-                    objects.push(Arc::new(Sphere {
-                        center,
-                        radius: 0.2,
-                        material: sphere_material,
-                    }));
-                }
-            }
-        }
+// glTF files are whole scenes; anything else is a TOML scene description.
+fn load_scene_file(path: &Path, sampler: &mut Sampler) -> Result<Scene, Box<dyn Error>> {
+    match path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase).as_deref() {
+        Some("gltf" | "glb") => Ok(load_gltf(path)?),
+        _ => Ok(load_scene(path, sampler)?),
     }
-
-    // Three big spheres
-    let material1: Arc<dyn Material> = Arc::new(Dielectric { ir: 1.5 });
-    objects.push(Arc::new(Sphere {
-        center: Point3::new(0.0, 1.0, 0.0),
-        radius: 1.0,
-        material: material1,
-    }));
-
-    let material2: Arc<dyn Material> = Arc::new(Lambertian {
-        albedo: Arc::new(SolidColor::new(Color::new(0.4, 0.2, 0.1))),
-    });
-    objects.push(Arc::new(Sphere {
-        center: Point3::new(-4.0, 1.0, 0.0),
-        radius: 1.0,
-        material: material2,
-    }));
-
-    let material3: Arc<dyn Material> = Arc::new(Metal {
-        albedo: Color::new(0.7, 0.6, 0.5),
-        fuzz: 0.0,
-    });
-    objects.push(Arc::new(Sphere {
-        center: Point3::new(4.0, 1.0, 0.0),
-        radius: 1.0,
-        material: material3,
-    }));
-
-    Arc::new(BvhNode::new(&mut objects, 0.0, 1.0))
 }
 
 fn main() -> io::Result<()> {
+    let cli = Cli::parse();
 
-    // Image
-    let mut aspect_ratio: f64 = 16.0/9.0;
-    let mut image_width: i32 = 400;
-    const MAX_DEPTH: u32 = 50;
-
-    let mut samples_per_pixel: u32 = 100;
-
-    let lookfrom: Point3;
-    let lookat: Point3;
-    let mut vfov = 40.0;
-    let mut aperture = 0.0;
-    let mut background = Color::new(0.0, 0.0, 0.0);
-
-    let world: Arc<dyn Hittable>;
-
-    let scene_id = 6;
-
-    match scene_id {
-        1 => {
-
-            world = random_scene();
-
-            background = Color::new(0.70, 0.80, 1.00);
-            lookfrom = Point3::new(13.0, 2.0, 3.0);
-            lookat = Point3::new(0.0, 0.0, 0.0);
-            vfov = 20.0;
-            aperture = 0.1;
-        },
-        2 => {
+    if cli.list_scenes {
+        for scene in scenes::SCENES {
+            println!("{:<20} {}", scene.name, scene.description);
+        }
+        return Ok(());
+    }
 
-            world = two_spheres();
+    if let Some(path) = &cli.output
+        && let Err(e) = OutputFormat::with_bit_depth(path, cli.bit_depth)
+    {
+        eprintln!("error: {}", e);
+        std::process::exit(2);
+    }
 
-            background = Color::new(0.70, 0.80, 1.00);
-            lookfrom = Point3::new(13.0, 2.0, 3.0);
-            lookat = Point3::new(0.0, 0.0, 0.0);
-            vfov = 20.0;
-        },
-        3 => {
-            world = two_perlin_spheres();
+    // Scene construction and BVH builds draw from one sampler; every pixel
+    // sample gets its own, so renders do not depend on thread scheduling.
+    let mut sampler = Sampler::new(cli.seed);
 
-            background = Color::new(0.70, 0.80, 1.00);
-            lookfrom = Point3::new(13.0, 2.0, 3.0);
-            lookat = Point3::new(0.0, 0.0, 0.0);
-            vfov = 20.0;
-        },
-        4 => {
-            world = earth();
-            background = Color::new(0.70, 0.80, 1.00);
-            lookfrom = Point3::new(13.0, 2.0, 3.0);
-            lookat = Point3::new(0.0, 0.0, 0.0);
-            vfov = 20.0;
-        },
-        5 => {
-            world = simple_light();
-
-            samples_per_pixel = 400;
-            background = Color::new(0.0, 0.0, 0.0);
-            lookfrom = Point3::new(26.0, 3.0, 6.0);
-            lookat = Point3::new(0.0, 2.0, 0.0);
-            vfov = 20.0;
-            aperture = 0.1;
-        },
-        6 => {
-            world = cornell_box();
-
-            aspect_ratio = 1.0;
-            image_width = 600;
-            samples_per_pixel = 1000;
+    if let Some(threads) = cli.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .expect("Could not configure the render thread pool");
+    }
 
-            background = Color::new(0.0, 0.0, 0.0);
-            lookfrom = Point3::new(278.0, 278.0, -800.0);
-            lookat = Point3::new(278.0, 278.0, 0.0);
-            vfov = 40.0;
+    let mut scene = match &cli.scene_file {
+        Some(path) => match load_scene_file(path, &mut sampler) {
+            Ok(scene) => scene,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                std::process::exit(1);
+            }
         },
-        7 => {
-            world = cornell_smoke();
-
-            aspect_ratio = 1.0;
-            image_width = 600;
-            samples_per_pixel = 200;
-
-            background = Color::new(0.0, 0.0, 0.0);
-            lookfrom = Point3::new(278.0, 278.0, -800.0);
-            lookat = Point3::new(278.0, 278.0, 0.0);
-            vfov = 40.0;
+        None => match scenes::find(&cli.scene) {
+            Some(builtin) => (builtin.build)(&mut sampler),
+            None => {
+                eprintln!("error: unknown scene '{}' (see --list-scenes)", cli.scene);
+                std::process::exit(2);
+            }
         },
-        8 => {
-            world = final_scene();
-
-            aspect_ratio = 1.0;
-            image_width = 800;
-            samples_per_pixel = 100;
+    };
+    if let Err(e) = cli.apply(&mut scene) {
+        eprintln!("error: {}", e);
+        std::process::exit(2);
+    }
+    if cli.bvh != SplitMethod::Sah {
+        scene.split_world(cli.bvh, &mut sampler);
+    }
 
-            background = Color::new(0.0, 0.0, 0.0);
-            lookfrom = Point3::new(478.0, 278.0, -600.0);
-            lookat = Point3::new(278.0, 278.0, 0.0);
-            vfov = 40.0;
-        },
-        _ => {
-            world = random_scene();
-
-            background = Color::new(0.70, 0.80, 1.00);
-            lookfrom = Point3::new(13.0, 2.0, 3.0);
-            lookat = Point3::new(0.0, 0.0, 0.0);
-            vfov = 20.0;
-            aperture = 0.1;
-        }
+    if cli.bvh_stats {
+        eprintln!("BVH: {}", scene.world.stats());
+    }
+    if cli.bench_bvh {
+        bench_bvh(&scene, cli.bvh, &mut sampler);
+        return Ok(());
     }
 
-    let vup = Vec3::new(0.0, 1.0, 0.0);
-    let dist_to_focus = 10.0;
-    let image_height: i32 = (image_width as f64 / aspect_ratio) as i32;
-
-    let cam: Camera = Camera::new(
-        lookfrom,
-        lookat,
-        vup,
-        vfov,
-        aspect_ratio,
-        aperture,
-        dist_to_focus,
-        0.0,
-        1.0
-    );
+    let image_width = scene.render.image_width;
+    let image_height = scene.render.image_height();
+    let samples_per_pixel = scene.render.samples_per_pixel;
+    let max_depth = scene.render.max_depth;
+    let min_bounces = scene.render.min_bounces;
+    let display = scene.render.display;
+    let background = scene.background;
+    let world: Arc<dyn Hittable> = scene.world.clone();
+    let cam = scene.camera();
 
     // Render
 
     let mut pixels: Vec<Color> = vec![Color::ZERO; (image_width * image_height) as usize];
     let remaining = Arc::new(AtomicI32::new(image_height));
 
-    let empty_material: Arc<dyn Material> = Arc::new(EmptyMaterial);
-
-    let mut lights: Vec<Arc<dyn Hittable>> = Vec::new();
-    lights.push(Arc::new(XZRect::new(
-        213.0, 343.0,
-        227.0, 332.0,
-        554.0,
-        Arc::clone(&empty_material),
-    )));
-
-    lights.push(Arc::new(Sphere::new(
-        Point3::new(190.0, 90.0, 190.0),
-        90.0,
-        Arc::clone(&empty_material),
-    )));
-
-    let lights: Arc<dyn Hittable> = Arc::new(HittableList { objects: lights });
+    let lights = &scene.lights;
 
     pixels
         .par_chunks_mut(image_width as usize)
@@ -743,23 +367,40 @@ fn main() -> io::Result<()> {
             let j = image_height - 1 - j_rev as i32;
             for i in 0..image_width {
                 let mut pixel_color = Color::new(0.0, 0.0, 0.0);
-                for _ in 0..samples_per_pixel {
-                    let u = (i as f64 + random_double()) / (image_width - 1) as f64;
-                    let v = (j as f64 + random_double()) / (image_height - 1) as f64;
-                    let r = cam.get_ray(u, v);
-                    pixel_color += ray_color(&r, &background, &world, &lights, MAX_DEPTH);
+                let pixel = (j_rev as i32 * image_width + i) as u64;
+                for s in 0..samples_per_pixel {
+                    let sampler = &mut Sampler::for_sample(cli.seed, pixel, s as u64);
+                    // A one-pixel row or column spans the whole view.
+                    let u = (i as f64 + sampler.random_double()) / (image_width - 1).max(1) as f64;
+                    let v = (j as f64 + sampler.random_double()) / (image_height - 1).max(1) as f64;
+                    let r = cam.get_ray(u, v, sampler);
+                    pixel_color += ray_color(&r, &background, &world, lights, max_depth, min_bounces, sampler);
                 }
                 row[i as usize] = pixel_color;
             }
             let left = remaining.fetch_sub(1, Ordering::SeqCst);
-            write!(io::stderr(), "\rScanlines remaining: {} ", left - 1).unwrap();
+            eprint!("\rScanlines remaining: {} ", left - 1);
             io::stderr().flush().unwrap();
         });
 
-    print!("P3\n{} {}\n255\n", image_width, image_height);
-
-    for color in pixels {
-        write_color(io::stdout(), color, samples_per_pixel)?;
+    match &cli.output {
+        Some(path) => write_image(
+            path,
+            &pixels,
+            image_width as u32,
+            image_height as u32,
+            samples_per_pixel,
+            &display,
+            cli.bit_depth,
+        )?,
+        None => write_ppm(
+            BufWriter::new(io::stdout()),
+            &pixels,
+            image_width as u32,
+            image_height as u32,
+            samples_per_pixel,
+            &display,
+        )?,
     }
 
     Ok(())
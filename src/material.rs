@@ -1,9 +1,9 @@
 use std::sync::Arc;
 
-use crate::{hittable::HitRecord, onb::Onb, pdf::{CosinePdf, Pdf}, ray::Ray, rtweekend::{random_double, PI}, texture::{SolidColor, Texture}, vec3::{dot, random_cosine_direction, random_in_hemisphere, random_in_unit_sphere, random_unit_vector, reflect, refract, unit_vector, Color, Point3, Vec3}};
+use crate::{color::luminance, hittable::HitRecord, microfacet::{dielectric_bsdf, fresnel_conductor, fresnel_dielectric, fresnel_schlick, Ggx, Gtr1}, onb::Onb, pdf::{CosinePdf, GgxDielectricPdf, GgxPdf, Gtr1Pdf, LobePdf, Pdf, SpherePdf}, ray::Ray, rtweekend::{PI, Sampler}, texture::{SolidColor, Texture}, vec3::{dot, random_cosine_direction, random_unit_vector, reflect, refract, unit_vector, Color, Point3, Vec3}};
 
 pub trait Material: Send + Sync {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, _sampler: &mut Sampler) -> Option<ScatterRecord> {
         None
     }
 
@@ -11,9 +11,38 @@ pub trait Material: Send + Sync {
         Color::new(0.0, 0.0, 0.0)
     }
 
+    // The BSDF times the cosine toward `scattered`, leaving out scatter()'s
+    // attenuation, and zero where the material cannot scatter that way. It
+    // is not the density scatter() samples with (that is pdf_ptr's value),
+    // though the two agree for Lambertian.
     fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
         0.0
     }
+
+    // The BSDF times the cosine toward `scattered`, which the integrator
+    // divides by its sampling pdf. Most materials split it into scatter()'s
+    // attenuation and scattering_pdf; ones whose color changes with the
+    // direction override this instead, and return its luminance from
+    // scattering_pdf.
+    fn scattering(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray, attenuation: Color) -> Color {
+        attenuation * self.scattering_pdf(r_in, rec, scattered)
+    }
+
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    // Some absorption coefficient when the surface bounds a medium (glass,
+    // water) that absorbs light over the distance travelled inside it.
+    fn absorption(&self) -> Option<Color> {
+        None
+    }
+
+    // True when scattering depends on r_in.wavelength, which the integrator
+    // then fills in.
+    fn is_dispersive(&self) -> bool {
+        false
+    }
 }
 
 pub struct ScatterRecord {
@@ -29,13 +58,69 @@ pub struct Lambertian {
     pub albedo: Arc<dyn Texture>,
 }
 
+// How much light a metal reflects at each angle: Schlick's curve from its
+// color at normal incidence, or the exact result for a complex index of
+// refraction eta + ik (per RGB channel).
+#[derive(Clone, Copy)]
+pub enum Fresnel {
+    Schlick(Color),
+    Conductor { eta: Color, k: Color },
+}
+
+// A GGX microfacet conductor. roughness_u applies along the surface's share
+// of `tangent` and roughness_v across it, so unequal values brush the metal
+// in that direction. Zero roughness is a perfect mirror.
 pub struct Metal {
-    pub albedo: Color,
-    pub fuzz: f64,
+    pub fresnel: Fresnel,
+    pub roughness_u: f64,
+    pub roughness_v: f64,
+    pub tangent: Vec3,
 }
 
+// An index of refraction that changes with the wavelength (in nm), which
+// splits white light into its colors. Coefficients take micrometres.
+#[derive(Clone, Copy)]
+pub enum Dispersion {
+    // n = a + b / λ²
+    Cauchy { a: f64, b: f64 },
+    // n² = 1 + Σ b λ² / (λ² - c)
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Dispersion {
+    // Schott's coefficients for common glasses, and diamond.
+    pub fn preset(name: &str) -> Option<Self> {
+        let (b, c) = match name {
+            "bk7" => ([1.03961212, 0.231792344, 1.01046945], [0.00600069867, 0.0200179144, 103.560653]),
+            "sf11" => ([1.73759695, 0.313747346, 1.89878101], [0.013188707, 0.0623068142, 155.23629]),
+            "fused_silica" => ([0.6961663, 0.4079426, 0.8974794], [0.004679148, 0.01351206, 97.934]),
+            "diamond" => ([4.3356, 0.3306, 0.0], [0.011236, 0.030625, 0.0]),
+            _ => return None,
+        };
+        Some(Dispersion::Sellmeier { b, c })
+    }
+
+    pub fn ior(&self, wavelength: f64) -> f64 {
+        let l2 = (wavelength / 1000.0).powi(2);
+        match *self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => {
+                (1.0 + (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum::<f64>()).sqrt()
+            }
+        }
+    }
+}
+
+// Glass and the like. Above zero roughness the surface is frosted: a GGX
+// microfacet interface that is sampled like any other rough material. The
+// inside absorbs `absorption` per unit length (Beer-Lambert), which tints
+// thick parts more deeply than thin ones. With dispersion the index follows
+// the path's wavelength, and `ir` is only used for rays without one.
 pub struct Dielectric {
     pub ir: f64,
+    pub roughness: f64,
+    pub absorption: Color,
+    pub dispersion: Option<Dispersion>,
 }
 
 pub struct DiffuseLight {
@@ -46,6 +131,50 @@ pub struct Isotropic {
     pub albedo: Arc<dyn Texture>,
 }
 
+// glTF's metallic-roughness model, shaded as the principled BSDF with its
+// defaults (a 4% dielectric reflection) for whatever glTF leaves out: GGX
+// reflection tinted by the base color for metals, over a diffuse base for
+// dielectrics. Vertex colors tint the base color.
+pub struct MetallicRoughness {
+    pub base_color: Arc<dyn Texture>,
+    pub metallic_roughness: Arc<dyn Texture>, // roughness in green, metalness in blue
+    pub emissive: Option<Arc<dyn Texture>>,
+}
+
+// Disney's principled BSDF (Burley 2012, with the 2015 refraction): one
+// material whose parameters blend a diffuse base with retro-reflection and
+// sheen, a GGX specular reflection tinted toward the base color as it turns
+// metallic, a GTR1 clearcoat, and rough refraction through an interface of
+// index `ior`. Every parameter is a texture; scalar ones read its red
+// channel. Scattering picks one lobe by its share of the reflected light,
+// and the density over all of them is what MIS sees.
+pub struct Principled {
+    pub base_color: Arc<dyn Texture>,
+    pub metallic: Arc<dyn Texture>,
+    pub roughness: Arc<dyn Texture>,
+    pub specular: Arc<dyn Texture>,
+    pub specular_tint: Arc<dyn Texture>,
+    pub sheen: Arc<dyn Texture>,
+    pub clearcoat: Arc<dyn Texture>,
+    pub clearcoat_gloss: Arc<dyn Texture>,
+    pub transmission: Arc<dyn Texture>,
+    pub ior: Arc<dyn Texture>,
+}
+
+// Principled's parameters looked up at one hit.
+struct PrincipledParams {
+    base_color: Color,
+    metallic: f64,
+    roughness: f64,
+    specular: f64,
+    specular_tint: f64,
+    sheen: f64,
+    clearcoat: f64,
+    clearcoat_gloss: f64,
+    transmission: f64,
+    ior: f64,
+}
+
 impl Isotropic {
     pub fn new( albedo: Arc<dyn Texture>) -> Self {
         Self {
@@ -54,6 +183,208 @@ impl Isotropic {
     }
 }
 
+impl MetallicRoughness {
+    pub fn new(
+        base_color: Arc<dyn Texture>,
+        metallic_roughness: Arc<dyn Texture>,
+        emissive: Option<Arc<dyn Texture>>,
+    ) -> Self {
+        Self {
+            base_color,
+            metallic_roughness,
+            emissive,
+        }
+    }
+
+    fn params(&self, rec: &HitRecord) -> PrincipledParams {
+        let mr = self.metallic_roughness.value_at(rec);
+        PrincipledParams {
+            base_color: self.base_color.value_at(rec) * rec.color.unwrap_or(Color::new(1.0, 1.0, 1.0)),
+            metallic: mr.z.clamp(0.0, 1.0),
+            roughness: mr.y.clamp(0.0, 1.0),
+            specular: 0.5,
+            specular_tint: 0.0,
+            sheen: 0.0,
+            clearcoat: 0.0,
+            clearcoat_gloss: 1.0,
+            transmission: 0.0,
+            ior: 1.5,
+        }
+    }
+}
+
+impl Principled {
+    // A rough dielectric of the given color; set the other parameters after.
+    pub fn new(base_color: Arc<dyn Texture>) -> Self {
+        let value = |v: f64| -> Arc<dyn Texture> { Arc::new(SolidColor::new(Color::new(v, v, v))) };
+        Self {
+            base_color,
+            metallic: value(0.0),
+            roughness: value(0.5),
+            specular: value(0.5),
+            specular_tint: value(0.0),
+            sheen: value(0.0),
+            clearcoat: value(0.0),
+            clearcoat_gloss: value(1.0),
+            transmission: value(0.0),
+            ior: value(1.5),
+        }
+    }
+
+    fn params(&self, rec: &HitRecord) -> PrincipledParams {
+        let scalar = |texture: &Arc<dyn Texture>| texture.value_at(rec).x.clamp(0.0, 1.0);
+        PrincipledParams {
+            base_color: self.base_color.value_at(rec),
+            metallic: scalar(&self.metallic),
+            roughness: scalar(&self.roughness),
+            specular: scalar(&self.specular),
+            specular_tint: scalar(&self.specular_tint),
+            sheen: scalar(&self.sheen),
+            clearcoat: scalar(&self.clearcoat),
+            clearcoat_gloss: scalar(&self.clearcoat_gloss),
+            transmission: scalar(&self.transmission),
+            // An index of exactly one would make refraction look like reflection.
+            ior: self.ior.value_at(rec).x.max(1.001),
+        }
+    }
+}
+
+impl PrincipledParams {
+    // The direction back along r_in in the shading frame, if it is above
+    // the surface.
+    fn wo(r_in: &Ray, rec: &HitRecord) -> Option<Vec3> {
+        let wo = Onb::build_from_w(rec.normal).to_local(-r_in.direction.unit_vector());
+        (wo.z > 0.0).then_some(wo)
+    }
+
+    fn lobe_pdf(&self, rec: &HitRecord, wo: Vec3) -> Option<LobePdf> {
+        let frame = || Onb::build_from_w(rec.normal);
+        let weights = [
+            self.diffuse_weight(),
+            luminance(self.specular_reflectance(wo.z)),
+            self.transmission_weight(),
+            0.25 * self.clearcoat * fresnel_schlick(wo.z, Color::new(0.04, 0.04, 0.04)).x,
+        ];
+        let total: f64 = weights.iter().sum();
+        if total <= 0.0 {
+            return None;
+        }
+
+        let mut lobes: Vec<(f64, Arc<dyn Pdf>)> = Vec::new();
+        if weights[0] > 0.0 {
+            lobes.push((weights[0] / total, Arc::new(CosinePdf::new(rec.normal))));
+        }
+        if weights[1] > 0.0 {
+            lobes.push((weights[1] / total, Arc::new(GgxPdf::new(frame(), wo, self.ggx()))));
+        }
+        if weights[2] > 0.0 {
+            lobes.push((weights[2] / total, Arc::new(GgxDielectricPdf::new(frame(), wo, self.ggx(), self.eta(rec)))));
+        }
+        if weights[3] > 0.0 {
+            lobes.push((weights[3] / total, Arc::new(Gtr1Pdf::new(frame(), wo, self.clearcoat()))));
+        }
+        Some(LobePdf::new(lobes))
+    }
+
+    // What Material::scatter, scattering_pdf and scattering return for a
+    // surface with these parameters. The color comes from scattering(),
+    // once the direction is known.
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, _sampler: &mut Sampler) -> Option<ScatterRecord> {
+        let wo = Self::wo(r_in, rec)?;
+        let pdf = self.lobe_pdf(rec, wo)?;
+        Some(ScatterRecord {
+            attenuation: Color::new(1.0, 1.0, 1.0),
+            pdf_ptr: Some(Arc::new(pdf)),
+            skip_pdf: false,
+            skip_pdf_ray: Ray::default(),
+        })
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        luminance(self.scattering(r_in, rec, scattered))
+    }
+
+    fn scattering(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        let Some(wo) = Self::wo(r_in, rec) else {
+            return Color::ZERO;
+        };
+        let wi = Onb::build_from_w(rec.normal).to_local(scattered.direction.unit_vector());
+        self.bsdf(&wo, &wi, self.eta(rec))
+    }
+
+    fn diffuse_weight(&self) -> f64 {
+        (1.0 - self.metallic) * (1.0 - self.transmission)
+    }
+
+    fn transmission_weight(&self) -> f64 {
+        (1.0 - self.metallic) * self.transmission
+    }
+
+    // The base color's hue and saturation at unit luminance.
+    fn tint(&self) -> Color {
+        let lum = luminance(self.base_color);
+        if lum > 0.0 { self.base_color / lum } else { Color::new(1.0, 1.0, 1.0) }
+    }
+
+    // Fresnel reflectance of the opaque parts: Schlick's from up to 8% at
+    // normal incidence for the dielectric one (4%, glass, at the default
+    // specular of one half) and from the base color for the metal. The
+    // transmissive part reflects by its ior instead.
+    fn specular_reflectance(&self, cos_theta: f64) -> Color {
+        let white = Color::new(1.0, 1.0, 1.0);
+        let dielectric = 0.08 * self.specular * (white + self.specular_tint * (self.tint() - white));
+        self.diffuse_weight() * fresnel_schlick(cos_theta, dielectric)
+            + self.metallic * fresnel_schlick(cos_theta, self.base_color)
+    }
+
+    fn ggx(&self) -> Ggx {
+        Ggx::from_roughness(self.roughness, self.roughness)
+    }
+
+    fn clearcoat(&self) -> Gtr1 {
+        Gtr1 { alpha: 0.1 + self.clearcoat_gloss * (0.001 - 0.1) }
+    }
+
+    fn eta(&self, rec: &HitRecord) -> f64 {
+        if rec.front_face { self.ior } else { 1.0 / self.ior }
+    }
+
+    // The BSDF times |cos| of wi, both directions in the shading frame.
+    fn bsdf(&self, wo: &Vec3, wi: &Vec3, eta: f64) -> Color {
+        // Exact Fresnel, so that light inside can be totally reflected.
+        let glass = self.transmission_weight() * dielectric_bsdf(&self.ggx(), wo, wi, eta);
+        if wi.z < 0.0 {
+            // The square root of the base color at each crossing tints light
+            // through a solid object by the whole color.
+            let tint = Color::new(self.base_color.x.sqrt(), self.base_color.y.sqrt(), self.base_color.z.sqrt());
+            return glass * tint;
+        }
+
+        let h = (*wo + *wi).unit_vector();
+        let cos_d = dot(wi, &h);
+        let schlick = |cos: f64| (1.0 - cos).clamp(0.0, 1.0).powi(5);
+        let (fl, fv, fh) = (schlick(wi.z), schlick(wo.z), schlick(cos_d));
+        let white = Color::new(1.0, 1.0, 1.0);
+
+        // Burley's diffuse darkens smooth surfaces at grazing angles and
+        // brightens rough ones, and sheen (half tinted, Disney's default)
+        // adds a soft rim for cloth.
+        let fd90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+        let diffuse = self.base_color / PI * (1.0 + (fd90 - 1.0) * fl) * (1.0 + (fd90 - 1.0) * fv);
+        let sheen = self.sheen * fh * (white + 0.5 * (self.tint() - white));
+
+        let ggx = self.ggx();
+        let specular = ggx.d(&h) * ggx.g2(wo, wi) / (4.0 * wo.z) * self.specular_reflectance(cos_d);
+
+        // A fixed 1.5 index coat over everything, with its own masking.
+        let coat = Ggx { alpha_x: 0.25, alpha_y: 0.25 };
+        let clearcoat = 0.25 * self.clearcoat * self.clearcoat().d(&h) * coat.g2(wo, wi) / (4.0 * wo.z)
+            * (0.04 + 0.96 * fh);
+
+        self.diffuse_weight() * (diffuse + sheen) * wi.z + specular + (clearcoat + glass) * white
+    }
+}
+
 impl DiffuseLight {
     pub fn new( emit: Arc<dyn Texture>) -> Self {
         Self {
@@ -80,21 +411,110 @@ impl Dielectric {
     pub fn new( ir: f64) -> Self {
         Self {
             ir,
+            roughness: 0.0,
+            absorption: Color::ZERO,
+            dispersion: None,
+        }
+    }
+
+    // `ir` is the index at the sodium D line, the usual quoted value.
+    pub fn dispersive(dispersion: Dispersion) -> Self {
+        Self {
+            dispersion: Some(dispersion),
+            ..Self::new(dispersion.ior(589.3))
         }
     }
+
+    // The absorption that leaves `color` of the light after `distance`.
+    pub fn absorption_for(color: Color, distance: f64) -> Color {
+        let sigma = |c: f64| -c.clamp(1e-9, 1.0).ln() / distance;
+        Color::new(sigma(color.x), sigma(color.y), sigma(color.z))
+    }
+
+    // The index across the surface over the index on the side rec faces.
+    fn eta(&self, r_in: &Ray, rec: &HitRecord) -> f64 {
+        let ir = match (self.dispersion, r_in.wavelength) {
+            (Some(dispersion), Some(wavelength)) => dispersion.ior(wavelength),
+            _ => self.ir,
+        };
+        if rec.front_face { ir } else { 1.0 / ir }
+    }
 }
 
 impl Metal {
-    pub fn new( albedo: Color, fuzz: f64) -> Self {
+    pub fn new(albedo: Color, roughness: f64) -> Self {
         Self {
-            albedo,
-            fuzz,
+            fresnel: Fresnel::Schlick(albedo),
+            roughness_u: roughness,
+            roughness_v: roughness,
+            tangent: Vec3::new(0.0, 1.0, 0.0),
+        }
+    }
+
+    pub fn conductor(eta: Color, k: Color, roughness: f64) -> Self {
+        Self {
+            fresnel: Fresnel::Conductor { eta, k },
+            ..Self::new(Color::ZERO, roughness)
         }
     }
+
+    // Measured indices of refraction sampled at red, green and blue.
+    pub fn gold(roughness: f64) -> Self {
+        Self::conductor(Color::new(0.143119, 0.374957, 1.44248), Color::new(3.98316, 2.38572, 1.60322), roughness)
+    }
+
+    pub fn copper(roughness: f64) -> Self {
+        Self::conductor(Color::new(0.200438, 0.924033, 1.10221), Color::new(3.91295, 2.45285, 2.14219), roughness)
+    }
+
+    pub fn aluminium(roughness: f64) -> Self {
+        Self::conductor(Color::new(1.65746, 0.880369, 0.521229), Color::new(9.22387, 6.26952, 4.837), roughness)
+    }
+
+    pub fn silver(roughness: f64) -> Self {
+        Self::conductor(Color::new(0.155265, 0.116723, 0.138342), Color::new(4.82835, 3.12225, 2.14696), roughness)
+    }
+
+    pub fn preset(name: &str, roughness: f64) -> Option<Self> {
+        match name {
+            "gold" => Some(Self::gold(roughness)),
+            "copper" => Some(Self::copper(roughness)),
+            "aluminium" | "aluminum" => Some(Self::aluminium(roughness)),
+            "silver" => Some(Self::silver(roughness)),
+            _ => None,
+        }
+    }
+
+    fn ggx(&self) -> Ggx {
+        Ggx::from_roughness(self.roughness_u, self.roughness_v)
+    }
+
+    fn reflectance(&self, cos_theta: f64) -> Color {
+        match self.fresnel {
+            Fresnel::Schlick(f0) => fresnel_schlick(cos_theta, f0),
+            Fresnel::Conductor { eta, k } => fresnel_conductor(cos_theta, eta, k),
+        }
+    }
+
+    // D G / (4 cos_o) for a reflection from r_in into `scattered`, with the
+    // cosine between them and their half vector for the Fresnel term. The
+    // BSDF's 1 / cos_i cancels against the integrator's cosine.
+    fn microfacet(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Option<(f64, f64)> {
+        let uvw = Onb::build_from_w_and_tangent(rec.normal, self.tangent);
+        let wo = uvw.to_local(-r_in.direction.unit_vector());
+        let wi = uvw.to_local(scattered.direction.unit_vector());
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return None;
+        }
+
+        let h = (wo + wi).unit_vector();
+        let ggx = self.ggx();
+        Some((ggx.d(&h) * ggx.g2(&wo, &wi) / (4.0 * wo.z), dot(&wo, &h)))
+    }
 }
 
 impl Material for EmptyMaterial {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, _sampler: &mut Sampler) -> Option<ScatterRecord> {
         None
     }
 
@@ -108,10 +528,10 @@ impl Material for EmptyMaterial {
 }
 
 impl Material for Isotropic {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, _sampler: &mut Sampler) -> Option<ScatterRecord> {
         Some(ScatterRecord {
-                attenuation: self.albedo.value(rec.u, rec.v, &rec.p),
-                pdf_ptr: Some(Arc::new(CosinePdf::new(rec.normal))),
+                attenuation: self.albedo.value_at(rec),
+                pdf_ptr: Some(Arc::new(SpherePdf)),
                 skip_pdf: false,
                 skip_pdf_ray: Ray::default(),
             })
@@ -123,10 +543,10 @@ impl Material for Isotropic {
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, _sampler: &mut Sampler) -> Option<ScatterRecord> {
 
         Some(ScatterRecord {
-                attenuation: self.albedo.value(rec.u, rec.v, &rec.p),
+                attenuation: self.albedo.value_at(rec),
                 pdf_ptr: Some(Arc::new(CosinePdf::new(rec.normal))),
                 skip_pdf: false,
                 skip_pdf_ray: Ray::default(),
@@ -145,37 +565,74 @@ impl Material for Lambertian {
 }
 
 impl Material for Metal {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
-        let reflected = reflect(&r_in.direction.unit_vector(), &rec.normal)
-                        + self.fuzz * random_in_unit_sphere();
-
-        Some(ScatterRecord {
-                attenuation: self.albedo,
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, _sampler: &mut Sampler) -> Option<ScatterRecord> {
+        let unit_direction = r_in.direction.unit_vector();
+        let ggx = self.ggx();
+
+        if ggx.is_smooth() {
+            let cos_theta = dot(&-unit_direction, &rec.normal).clamp(0.0, 1.0);
+            return Some(ScatterRecord {
+                attenuation: self.reflectance(cos_theta),
                 pdf_ptr: None,
                 skip_pdf: true,
-                skip_pdf_ray: Ray::with_time(rec.p, reflected, r_in.time()),
-            })
+                skip_pdf_ray: Ray::with_time(rec.p, reflect(&unit_direction, &rec.normal), r_in.time()),
+            });
+        }
+
+        let uvw = Onb::build_from_w_and_tangent(rec.normal, self.tangent);
+        let wo = uvw.to_local(-unit_direction);
+        if wo.z <= 0.0 {
+            return None;
+        }
+
+        // The color comes from scattering(), once the direction is known.
+        Some(ScatterRecord {
+            attenuation: Color::new(1.0, 1.0, 1.0),
+            pdf_ptr: Some(Arc::new(GgxPdf::new(uvw, wo, ggx))),
+            skip_pdf: false,
+            skip_pdf_ray: Ray::default(),
+        })
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        self.microfacet(r_in, rec, scattered).map_or(0.0, |(value, _)| value)
+    }
+
+    fn scattering(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray, _attenuation: Color) -> Color {
+        match self.microfacet(r_in, rec, scattered) {
+            Some((value, cos_theta)) => value * self.reflectance(cos_theta),
+            None => Color::ZERO,
+        }
     }
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, sampler: &mut Sampler) -> Option<ScatterRecord> {
         let attenuation: Color = Color::new(1.0, 1.0, 1.0);
-        let refraction_ratio: f64 = if rec.front_face {
-            1.0 / self.ir
-        } else {
-            self.ir
-        };
-
+        let eta = self.eta(r_in, rec);
         let unit_direction: Vec3 = r_in.direction.unit_vector();
-        let cos_theta = (dot(&-unit_direction, &rec.normal)).min(1.0);
-        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        let ggx = Ggx::from_roughness(self.roughness, self.roughness);
+
+        if !ggx.is_smooth() {
+            let uvw = Onb::build_from_w(rec.normal);
+            let wo = uvw.to_local(-unit_direction);
+            if wo.z <= 0.0 {
+                return None;
+            }
+            return Some(ScatterRecord {
+                attenuation,
+                pdf_ptr: Some(Arc::new(GgxDielectricPdf::new(uvw, wo, ggx, eta))),
+                skip_pdf: false,
+                skip_pdf_ray: Ray::default(),
+            });
+        }
 
-        let cannot_refract = refraction_ratio * sin_theta > 1.0;
-        let direction: Vec3 = if cannot_refract || reflectance(cos_theta, refraction_ratio) > random_double() {
+        // Total internal reflection shows up as a reflectance of one.
+        let cos_theta = (dot(&-unit_direction, &rec.normal)).min(1.0);
+        let direction: Vec3 = if fresnel_dielectric(cos_theta, eta) > sampler.random_double() {
             reflect(&unit_direction, &rec.normal)
         } else {
-            refract(&unit_direction, &rec.normal, refraction_ratio)
+            refract(&unit_direction, &rec.normal, 1.0 / eta)
         };
 
         Some(ScatterRecord {
@@ -186,28 +643,80 @@ impl Material for Dielectric {
             })
     }
 
+    // Zero for smooth glass, which is never sampled through a pdf.
     fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
-        1.0
+        let ggx = Ggx::from_roughness(self.roughness, self.roughness);
+        if ggx.is_smooth() {
+            return 0.0;
+        }
+        let uvw = Onb::build_from_w(rec.normal);
+        let wo = uvw.to_local(-r_in.direction.unit_vector());
+        let wi = uvw.to_local(scattered.direction.unit_vector());
+        dielectric_bsdf(&ggx, &wo, &wi, self.eta(r_in, rec))
+    }
+
+    fn absorption(&self) -> Option<Color> {
+        Some(self.absorption)
+    }
+
+    fn is_dispersive(&self) -> bool {
+        self.dispersion.is_some()
+    }
+}
+
+impl Material for MetallicRoughness {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, sampler: &mut Sampler) -> Option<ScatterRecord> {
+        self.params(rec).scatter(r_in, rec, sampler)
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        self.params(rec).scattering_pdf(r_in, rec, scattered)
+    }
+
+    fn scattering(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray, _attenuation: Color) -> Color {
+        self.params(rec).scattering(r_in, rec, scattered)
+    }
+
+    fn emitted(&self, _u: f64, _v: f64, _p: &Point3, rec: &HitRecord) -> Color {
+        match &self.emissive {
+            Some(emissive) if rec.front_face => emissive.value_at(rec),
+            _ => Color::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.emissive.is_some()
+    }
+}
+
+impl Material for Principled {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, sampler: &mut Sampler) -> Option<ScatterRecord> {
+        self.params(rec).scatter(r_in, rec, sampler)
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        self.params(rec).scattering_pdf(r_in, rec, scattered)
+    }
+
+    fn scattering(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray, _attenuation: Color) -> Color {
+        self.params(rec).scattering(r_in, rec, scattered)
     }
 }
 
 impl Material for DiffuseLight {
-    fn scatter(&self, r_in: &Ray, _rec: &HitRecord) -> Option<ScatterRecord> {
+    fn scatter(&self, r_in: &Ray, _rec: &HitRecord, _sampler: &mut Sampler) -> Option<ScatterRecord> {
         None
     }
 
-    fn emitted(&self, u: f64, v: f64, p: &Point3, rec: &HitRecord) -> Color {
+    fn emitted(&self, _u: f64, _v: f64, _p: &Point3, rec: &HitRecord) -> Color {
         if rec.front_face {
-            self.emit.value(u, v, p)
+            self.emit.value_at(rec)
         } else {
             Color::new(0.0, 0.0, 0.0)
         }
     }
-}
 
-fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
-    // Use Schlick's approximation for reflectance.
-    let mut r0 = (1.0-ref_idx) / (1.0+ref_idx);
-    r0 = r0*r0;
-    r0 + (1.0-r0)*(1.0-cosine).powf(5.0)
+    fn is_emissive(&self) -> bool {
+        true
+    }
 }
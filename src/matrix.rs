@@ -0,0 +1,231 @@
+use std::ops::Mul;
+
+use crate::{rtweekend::degrees_to_radians, vec3::{cross, dot, Point3, Vec3}};
+
+// Row-major 4x4 matrix for affine transforms; m[row][col], with points
+// treated as column vectors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat4 {
+    pub m: [[f64; 4]; 4],
+}
+
+impl Mat4 {
+    pub const IDENTITY: Mat4 = Mat4 {
+        m: [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    };
+
+    // From column-major data, the layout glTF and most file formats use.
+    pub fn from_columns(cols: [[f32; 4]; 4]) -> Self {
+        let mut m = [[0.0; 4]; 4];
+        for (c, col) in cols.iter().enumerate() {
+            for (r, value) in col.iter().enumerate() {
+                m[r][c] = *value as f64;
+            }
+        }
+        Self { m }
+    }
+
+    pub fn translation(offset: Vec3) -> Self {
+        let mut m = Mat4::IDENTITY;
+        m.m[0][3] = offset.x;
+        m.m[1][3] = offset.y;
+        m.m[2][3] = offset.z;
+        m
+    }
+
+    pub fn scaling(factors: Vec3) -> Self {
+        let mut m = Mat4::IDENTITY;
+        m.m[0][0] = factors.x;
+        m.m[1][1] = factors.y;
+        m.m[2][2] = factors.z;
+        m
+    }
+
+    // Counter-clockwise by `angle` degrees looking down the axis toward the
+    // origin (Rodrigues' formula); the axis need not be normalized.
+    pub fn rotation(axis: Vec3, angle: f64) -> Self {
+        let a = axis.unit_vector();
+        let radians = degrees_to_radians(angle);
+        let (sin, cos) = radians.sin_cos();
+        let t = 1.0 - cos;
+
+        let mut m = Mat4::IDENTITY;
+        m.m[0][0] = t * a.x * a.x + cos;
+        m.m[0][1] = t * a.x * a.y - sin * a.z;
+        m.m[0][2] = t * a.x * a.z + sin * a.y;
+        m.m[1][0] = t * a.x * a.y + sin * a.z;
+        m.m[1][1] = t * a.y * a.y + cos;
+        m.m[1][2] = t * a.y * a.z - sin * a.x;
+        m.m[2][0] = t * a.x * a.z - sin * a.y;
+        m.m[2][1] = t * a.y * a.z + sin * a.x;
+        m.m[2][2] = t * a.z * a.z + cos;
+        m
+    }
+
+    // Places an object at `from` with its local +Z axis pointing at `to` and
+    // its +Y axis as close to `up` as possible.
+    pub fn look_at(from: Point3, to: Point3, up: Vec3) -> Self {
+        let z = (to - from).unit_vector();
+        let x = cross(&up, &z).unit_vector();
+        let y = cross(&z, &x);
+
+        let mut m = Mat4::IDENTITY;
+        for (c, axis) in [x, y, z, from].iter().enumerate() {
+            m.m[0][c] = axis.x;
+            m.m[1][c] = axis.y;
+            m.m[2][c] = axis.z;
+        }
+        m
+    }
+
+    // Determinant of the upper 3x3 block: the volume scale of the transform,
+    // negative when it mirrors.
+    pub fn linear_determinant(&self) -> f64 {
+        let m = &self.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    pub fn transform_point(&self, p: Point3) -> Point3 {
+        let m = &self.m;
+        let x = m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3];
+        let y = m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3];
+        let z = m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3];
+        let w = m[3][0] * p.x + m[3][1] * p.y + m[3][2] * p.z + m[3][3];
+        if w == 1.0 { Point3::new(x, y, z) } else { Point3::new(x / w, y / w, z / w) }
+    }
+
+    // Ignores the translation column.
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
+
+    pub fn transpose(&self) -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (r, row) in m.iter_mut().enumerate() {
+            for (c, value) in row.iter_mut().enumerate() {
+                *value = self.m[c][r];
+            }
+        }
+        Mat4 { m }
+    }
+
+    // Gauss-Jordan elimination with partial pivoting; None if singular.
+    pub fn inverse(&self) -> Option<Mat4> {
+        let mut a = self.m;
+        let mut inv = Mat4::IDENTITY.m;
+
+        for col in 0..4 {
+            let pivot = (col..4)
+                .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+                .unwrap();
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let scale = 1.0 / a[col][col];
+            for k in 0..4 {
+                a[col][k] *= scale;
+                inv[col][k] *= scale;
+            }
+
+            for row in 0..4 {
+                if row != col {
+                    let factor = a[row][col];
+                    for k in 0..4 {
+                        a[row][k] -= factor * a[col][k];
+                        inv[row][k] -= factor * inv[col][k];
+                    }
+                }
+            }
+        }
+
+        Some(Mat4 { m: inv })
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, other: Mat4) -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (r, row) in m.iter_mut().enumerate() {
+            for (c, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[r][k] * other.m[k][c]).sum();
+            }
+        }
+        Mat4 { m }
+    }
+}
+
+// Unit quaternion, for rotations that have to be interpolated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quat {
+    pub w: f64,
+    pub v: Vec3,
+}
+
+impl Quat {
+    pub const IDENTITY: Quat = Quat { w: 1.0, v: Vec3::ZERO };
+
+    // Same convention as Mat4::rotation.
+    pub fn from_axis_angle(axis: Vec3, angle: f64) -> Self {
+        let (sin, cos) = (degrees_to_radians(angle) / 2.0).sin_cos();
+        Self { w: cos, v: sin * axis.unit_vector() }
+    }
+
+    pub fn conjugate(&self) -> Quat {
+        Quat { w: self.w, v: -self.v }
+    }
+
+    pub fn rotate(&self, p: Vec3) -> Vec3 {
+        let t = 2.0 * cross(&self.v, &p);
+        p + self.w * t + cross(&self.v, &t)
+    }
+
+    fn dot(&self, other: &Quat) -> f64 {
+        self.w * other.w + dot(&self.v, &other.v)
+    }
+
+    // Radians turned going from self to other the short way round.
+    pub fn angle_to(&self, other: &Quat) -> f64 {
+        2.0 * self.dot(other).abs().min(1.0).acos()
+    }
+
+    // Constant angular velocity along the shorter arc.
+    pub fn slerp(&self, other: &Quat, t: f64) -> Quat {
+        let mut cos = self.dot(other);
+        let mut other = *other;
+        if cos < 0.0 {
+            cos = -cos;
+            other = Quat { w: -other.w, v: -other.v };
+        }
+
+        // Nearly parallel: lerp is accurate and avoids dividing by sin ~ 0.
+        let (a, b) = if cos > 0.9995 {
+            (1.0 - t, t)
+        } else {
+            let theta = cos.acos();
+            let sin = theta.sin();
+            (((1.0 - t) * theta).sin() / sin, (t * theta).sin() / sin)
+        };
+
+        let w = a * self.w + b * other.w;
+        let v = a * self.v + b * other.v;
+        let length = (w * w + v.length_squared()).sqrt();
+        Quat { w: w / length, v: v / length }
+    }
+}
@@ -0,0 +1,372 @@
+use crate::{
+    rtweekend::{PI, Sampler},
+    vec3::{dot, Color, Vec3},
+};
+
+// The GGX (Trowbridge-Reitz) distribution of microfacet normals, with
+// separate widths along the tangent (x) and bitangent (y). Every direction
+// here is in the shading frame, where the macro surface normal is +z.
+#[derive(Clone, Copy)]
+pub struct Ggx {
+    pub alpha_x: f64,
+    pub alpha_y: f64,
+}
+
+impl Ggx {
+    // Perceptual roughness in [0, 1] maps to alpha = roughness².
+    pub fn from_roughness(roughness_u: f64, roughness_v: f64) -> Self {
+        Self {
+            alpha_x: (roughness_u * roughness_u).max(1e-4),
+            alpha_y: (roughness_v * roughness_v).max(1e-4),
+        }
+    }
+
+    // Below this the lobe is narrower than anything sampling can resolve,
+    // and the surface is treated as a perfect mirror instead.
+    pub fn is_smooth(&self) -> bool {
+        self.alpha_x.max(self.alpha_y) < 1e-3
+    }
+
+    // Density of microfacet normals around h.
+    pub fn d(&self, h: &Vec3) -> f64 {
+        if h.z <= 0.0 {
+            return 0.0;
+        }
+        let e = (h.x / self.alpha_x).powi(2) + (h.y / self.alpha_y).powi(2) + h.z * h.z;
+        1.0 / (PI * self.alpha_x * self.alpha_y * e * e)
+    }
+
+    // Smith's auxiliary function; masking is G1 = 1 / (1 + lambda).
+    fn lambda(&self, w: &Vec3) -> f64 {
+        if w.z == 0.0 {
+            return f64::INFINITY;
+        }
+        let a2 = ((self.alpha_x * w.x).powi(2) + (self.alpha_y * w.y).powi(2)) / (w.z * w.z);
+        ((1.0 + a2).sqrt() - 1.0) / 2.0
+    }
+
+    pub fn g1(&self, w: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // Height-correlated masking and shadowing for a pair of directions.
+    pub fn g2(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // A normal from the distribution of those visible from wo (Heitz 2018),
+    // which wastes no samples on facets facing away from the viewer.
+    pub fn sample_visible(&self, wo: &Vec3, sampler: &mut Sampler) -> Vec3 {
+        // Stretch to the hemisphere configuration.
+        let vh = Vec3::new(self.alpha_x * wo.x, self.alpha_y * wo.y, wo.z).unit_vector();
+        let len_squared = vh.x * vh.x + vh.y * vh.y;
+        let t1 = if len_squared > 0.0 {
+            Vec3::new(-vh.y, vh.x, 0.0) / len_squared.sqrt()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = vh.cross(&t1);
+
+        // A point on the projected disk, squeezed toward the visible half.
+        let r = sampler.random_double().sqrt();
+        let phi = 2.0 * PI * sampler.random_double();
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z);
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+        let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+
+        // Back to the ellipsoid configuration.
+        Vec3::new(self.alpha_x * nh.x, self.alpha_y * nh.y, nh.z.max(0.0)).unit_vector()
+    }
+
+    // Density of sample_visible's normal h, reflected about to give the
+    // direction wi, per unit solid angle of wi.
+    pub fn reflection_pdf(&self, wo: &Vec3, h: &Vec3) -> f64 {
+        if wo.z <= 0.0 {
+            return 0.0;
+        }
+        self.g1(wo) * self.d(h) / (4.0 * wo.z)
+    }
+}
+
+// The GTR1 (Berry) distribution of Disney's clearcoat: a sharp peak with
+// long tails, isotropic with width alpha. Same frame as Ggx.
+#[derive(Clone, Copy)]
+pub struct Gtr1 {
+    pub alpha: f64,
+}
+
+impl Gtr1 {
+    pub fn d(&self, h: &Vec3) -> f64 {
+        if h.z <= 0.0 {
+            return 0.0;
+        }
+        let a2 = self.alpha * self.alpha;
+        (a2 - 1.0) / (PI * a2.ln() * (1.0 + (a2 - 1.0) * h.z * h.z))
+    }
+
+    // A normal with density d(h) h.z.
+    pub fn sample(&self, sampler: &mut Sampler) -> Vec3 {
+        let a2 = self.alpha * self.alpha;
+        let cos_theta = ((1.0 - a2.powf(1.0 - sampler.random_double())) / (1.0 - a2)).max(0.0).sqrt();
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * sampler.random_double();
+        Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+    }
+
+    // Density of wi when sample()'s normal h reflects wo into it.
+    pub fn reflection_pdf(&self, wo: &Vec3, h: &Vec3) -> f64 {
+        let cos_o = dot(wo, h);
+        if cos_o <= 0.0 {
+            return 0.0;
+        }
+        self.d(h) * h.z / (4.0 * cos_o)
+    }
+}
+
+// Reflection and transmission through a rough interface between dielectrics
+// (Walter et al. 2007). wo is on the +z side and eta is the index of the -z
+// side over that of the +z side. Values are the BSDF times |cos| of wi.
+pub fn dielectric_bsdf(ggx: &Ggx, wo: &Vec3, wi: &Vec3, eta: f64) -> f64 {
+    let Some((h, etap)) = dielectric_half_vector(wo, wi, eta) else {
+        return 0.0;
+    };
+    let f = fresnel_dielectric(dot(wo, &h), eta);
+    if etap == 1.0 {
+        return ggx.d(&h) * ggx.g2(wo, wi) * f / (4.0 * wo.z);
+    }
+
+    // Radiance is compressed by etap² on the way into the denser side.
+    let denom = (dot(wi, &h) + dot(wo, &h) / etap).powi(2) * wo.z;
+    let value = ggx.d(&h) * ggx.g2(wo, wi) * (1.0 - f) * (dot(wi, &h) * dot(wo, &h) / denom).abs();
+    value / (etap * etap)
+}
+
+// Density of dielectric_sample producing wi, per unit solid angle.
+pub fn dielectric_pdf(ggx: &Ggx, wo: &Vec3, wi: &Vec3, eta: f64) -> f64 {
+    let Some((h, etap)) = dielectric_half_vector(wo, wi, eta) else {
+        return 0.0;
+    };
+    let r = fresnel_dielectric(dot(wo, &h), eta);
+    let visible = ggx.g1(wo) * ggx.d(&h) * dot(wo, &h) / wo.z;
+    if etap == 1.0 {
+        visible / (4.0 * dot(wo, &h)) * r
+    } else {
+        let denom = (dot(wi, &h) + dot(wo, &h) / etap).powi(2);
+        visible * dot(wi, &h).abs() / denom * (1.0 - r)
+    }
+}
+
+// A visible normal, then reflection or refraction through it chosen by its
+// Fresnel reflectance. None when the result heads the wrong way.
+pub fn dielectric_sample(ggx: &Ggx, wo: &Vec3, eta: f64, sampler: &mut Sampler) -> Option<Vec3> {
+    let h = ggx.sample_visible(wo, sampler);
+    let cos_o = dot(wo, &h);
+    if sampler.random_double() < fresnel_dielectric(cos_o, eta) {
+        let wi = 2.0 * cos_o * h - *wo;
+        (wi.z > 0.0).then_some(wi)
+    } else {
+        let sin2_t = (1.0 - cos_o * cos_o).max(0.0) / (eta * eta);
+        if sin2_t >= 1.0 {
+            return None;
+        }
+        let wi = -*wo / eta + (cos_o / eta - (1.0 - sin2_t).sqrt()) * h;
+        (wi.z < 0.0).then_some(wi)
+    }
+}
+
+// The microfacet normal that takes wo to wi, on the +z side, with the
+// relative index across it (1 for reflection). None for degenerate pairs
+// and for facets seen from behind by either direction.
+fn dielectric_half_vector(wo: &Vec3, wi: &Vec3, eta: f64) -> Option<(Vec3, f64)> {
+    if wo.z <= 0.0 || wi.z == 0.0 {
+        return None;
+    }
+    let etap = if wi.z > 0.0 { 1.0 } else { eta };
+    let h = *wi * etap + *wo;
+    if h.near_zero() {
+        return None;
+    }
+    let h = if h.z < 0.0 { -h.unit_vector() } else { h.unit_vector() };
+    if dot(&h, wi) * wi.z < 0.0 || dot(&h, wo) <= 0.0 {
+        return None;
+    }
+    Some((h, etap))
+}
+
+// Unpolarized Fresnel reflectance for light arriving at cos_theta_i to the
+// normal, where eta is the index across the interface over the index on
+// the incident side. Total internal reflection gives 1.
+pub fn fresnel_dielectric(cos_theta_i: f64, eta: f64) -> f64 {
+    let (cos_i, eta) = if cos_theta_i < 0.0 { (-cos_theta_i, 1.0 / eta) } else { (cos_theta_i, eta) };
+    let sin2_t = (1.0 - cos_i * cos_i).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let r_parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    0.5 * (r_parallel * r_parallel + r_perpendicular * r_perpendicular)
+}
+
+// Unpolarized Fresnel reflectance of a conductor with complex index of
+// refraction eta + ik, per color channel.
+pub fn fresnel_conductor(cos_theta: f64, eta: Color, k: Color) -> Color {
+    let channel = |eta: f64, k: f64| {
+        let cos2 = cos_theta * cos_theta;
+        let sin2 = 1.0 - cos2;
+        let t0 = eta * eta - k * k - sin2;
+        let a2_plus_b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+        let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+        let t1 = a2_plus_b2 + cos2;
+        let t2 = 2.0 * cos_theta * a;
+        let rs = (t1 - t2) / (t1 + t2);
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+        0.5 * (rs + rp)
+    };
+    Color::new(channel(eta.x, k.x), channel(eta.y, k.y), channel(eta.z, k.z))
+}
+
+// Schlick's approximation with a colored reflectance at normal incidence.
+pub fn fresnel_schlick(cos_theta: f64, f0: Color) -> Color {
+    let weight = (1.0 - cos_theta).clamp(0.0, 1.0).powi(5);
+    f0 + weight * (Color::new(1.0, 1.0, 1.0) - f0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Integrates the distribution's projected area over the hemisphere by
+    // sampling directions uniformly.
+    fn projected_area(d: impl Fn(&Vec3) -> f64) -> f64 {
+        let mut sampler = Sampler::new(0);
+        let n = 200_000;
+        let mut sum = 0.0;
+        for _ in 0..n {
+            let z = sampler.random_double();
+            let phi = 2.0 * PI * sampler.random_double();
+            let r = (1.0 - z * z).sqrt();
+            let h = Vec3::new(r * phi.cos(), r * phi.sin(), z);
+            sum += d(&h) * h.z * 2.0 * PI;
+        }
+        sum / n as f64
+    }
+
+    #[test]
+    fn distribution_is_normalized() {
+        for ggx in [Ggx::from_roughness(0.5, 0.5), Ggx::from_roughness(0.8, 0.3)] {
+            assert!((projected_area(|h| ggx.d(h)) - 1.0).abs() < 0.02);
+        }
+    }
+
+    #[test]
+    fn visible_normals_face_the_viewer() {
+        let mut sampler = Sampler::new(0);
+        let ggx = Ggx::from_roughness(0.6, 0.2);
+        let wo = Vec3::new(0.6, 0.3, 0.5).unit_vector();
+        for _ in 0..1000 {
+            let h = ggx.sample_visible(&wo, &mut sampler);
+            assert!(h.z >= 0.0 && dot(&wo, &h) >= -1e-9);
+            assert!((h.length() - 1.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn reflection_pdf_integrates_to_one() {
+        let mut sampler = Sampler::new(0);
+        // Every visible normal reflects wo somewhere, so the density of
+        // reflected directions covers the whole sphere exactly once.
+        let ggx = Ggx::from_roughness(0.7, 0.4);
+        let wo = Vec3::new(0.3, -0.2, 0.8).unit_vector();
+        let n = 400_000;
+        let mut sum = 0.0;
+        for _ in 0..n {
+            let z = 1.0 - 2.0 * sampler.random_double();
+            let phi = 2.0 * PI * sampler.random_double();
+            let r = (1.0 - z * z).sqrt();
+            let wi = Vec3::new(r * phi.cos(), r * phi.sin(), z);
+            let h = (wo + wi).unit_vector();
+            sum += ggx.reflection_pdf(&wo, &h) * 4.0 * PI;
+        }
+        assert!((sum / n as f64 - 1.0).abs() < 0.02);
+    }
+
+    #[test]
+    fn clearcoat_distribution_is_normalized() {
+        let mut sampler = Sampler::new(0);
+        let gtr1 = Gtr1 { alpha: 0.3 };
+        assert!((projected_area(|h| gtr1.d(h)) - 1.0).abs() < 0.02);
+
+        // Samples follow d(h) h.z: compare their mean h.z with the integral.
+        let n = 200_000;
+        let sampled = (0..n).map(|_| gtr1.sample(&mut sampler).z).sum::<f64>() / n as f64;
+        let integrated = projected_area(|h| gtr1.d(h) * h.z);
+        assert!((sampled - integrated).abs() < 0.01, "{} vs {}", sampled, integrated);
+    }
+
+    #[test]
+    fn dielectric_conserves_energy() {
+        let mut sampler = Sampler::new(0);
+        // Estimating the energy leaving each side by its own samples'
+        // weights bsdf / pdf, with transmission's 1 / eta² radiance scale
+        // undone: none is created and only masking loses any.
+        for (eta, wo) in [(1.5, Vec3::new(0.4, 0.1, 0.9)), (1.0 / 1.5, Vec3::new(0.0, 0.3, 0.5))] {
+            let wo = wo.unit_vector();
+            let ggx = Ggx::from_roughness(0.5, 0.5);
+            let n = 100_000;
+            let mut energy = 0.0;
+            for _ in 0..n {
+                if let Some(wi) = dielectric_sample(&ggx, &wo, eta, &mut sampler) {
+                    let wi = wi.unit_vector();
+                    let pdf = dielectric_pdf(&ggx, &wo, &wi, eta);
+                    assert!(pdf > 0.0);
+                    let scale = if wi.z < 0.0 { eta * eta } else { 1.0 };
+                    energy += scale * dielectric_bsdf(&ggx, &wo, &wi, eta) / pdf;
+                }
+            }
+            let energy = energy / n as f64;
+            assert!(energy > 0.9 && energy < 1.01, "energy {}", energy);
+        }
+    }
+
+    #[test]
+    fn dielectric_pdf_integrates_to_one() {
+        let mut sampler = Sampler::new(0);
+        let ggx = Ggx::from_roughness(0.6, 0.6);
+        let wo = Vec3::new(0.3, 0.0, 0.7).unit_vector();
+        let n = 400_000;
+        let mut sum = 0.0;
+        for _ in 0..n {
+            let z = 1.0 - 2.0 * sampler.random_double();
+            let phi = 2.0 * PI * sampler.random_double();
+            let r = (1.0 - z * z).sqrt();
+            let wi = Vec3::new(r * phi.cos(), r * phi.sin(), z);
+            sum += dielectric_pdf(&ggx, &wo, &wi, 1.5) * 4.0 * PI;
+        }
+        assert!((sum / n as f64 - 1.0).abs() < 0.03);
+    }
+
+    #[test]
+    fn dielectric_fresnel() {
+        // 4% at normal incidence for glass, total internal reflection past
+        // the critical angle on the way out.
+        assert!((fresnel_dielectric(1.0, 1.5) - 0.04).abs() < 1e-9);
+        assert_eq!(fresnel_dielectric(0.5, 1.0 / 1.5), 1.0);
+        assert!((fresnel_dielectric(-0.5, 1.5) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn conductor_fresnel_limits() {
+        let eta = Color::new(0.2, 0.9, 1.1);
+        let k = Color::new(3.9, 2.4, 2.1);
+        let grazing = fresnel_conductor(0.0, eta, k);
+        assert!((grazing - Color::new(1.0, 1.0, 1.0)).length() < 1e-9);
+        // At normal incidence ((eta - 1)² + k²) / ((eta + 1)² + k²).
+        let normal = fresnel_conductor(1.0, eta, k);
+        let expected = ((0.2_f64 - 1.0).powi(2) + 3.9 * 3.9) / ((0.2_f64 + 1.0).powi(2) + 3.9 * 3.9);
+        assert!((normal.x - expected).abs() < 1e-9);
+    }
+}
@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use crate::{aabb::Aabb, hittable::{HitRecord, Hittable}, material::Material, ray::Ray, rtweekend::get_sphere_uv, vec3::{dot, Point3, Vec3}};
+use crate::{aabb::Aabb, hittable::{HitRecord, Hittable}, material::Material, ray::Ray, rtweekend::{get_sphere_uv, Sampler}, vec3::{dot, Point3, Vec3}};
 
 pub struct MovingSphere {
     pub center0: Point3,
@@ -30,7 +30,7 @@ impl MovingSphere {
 }
 
 impl Hittable for MovingSphere {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, _sampler: &mut Sampler) -> Option<HitRecord> {
         let oc: Vec3 = r.origin - self.center(r.time());
         let a = r.direction().length_squared();
         let half_b = dot(&oc, &r.direction());
@@ -64,6 +64,7 @@ impl Hittable for MovingSphere {
             v,
             normal: Vec3::new(0.0, 0.0, 0.0),
             front_face: false,
+            color: None,
             material: Arc::clone(&self.material),
         };
         rec.set_face_normal(r, outward_normal);
@@ -0,0 +1,549 @@
+use std::{collections::HashMap, fmt, fs, io, path::{Path, PathBuf}, sync::Arc};
+
+use crate::{
+    material::{Dielectric, DiffuseLight, Lambertian, Material, Metal},
+    texture::{ImageTexture, SolidColor, Texture},
+    triangle::{MeshData, TriangleMesh},
+    vec3::{Color, Point3, Vec3},
+};
+
+// Wavefront OBJ with MTL materials. Every group (g or o) and material
+// (usemtl) pair becomes its own TriangleMesh, so emissive parts of a model
+// end up as separate lights. Polygons are triangulated as fans.
+//
+// MTL statements map onto the existing materials:
+//   Ke / map_Ke            DiffuseLight
+//   illum 4, 6, 7 or d < 1 Dielectric with index Ni
+//   illum 3 or 5           Metal with albedo Ks, roughness from Ns
+//   otherwise              Lambertian with Kd or map_Kd
+// Statements that don't affect rendering (s, l, Ka, bump maps...) are ignored.
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io { path: PathBuf, source: io::Error },
+    Parse { path: PathBuf, line: usize, message: String },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            ObjError::Parse { path, line, message } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+// One corner of a face: indices into the position, texcoord and normal lists.
+type Corner = (usize, Option<usize>, Option<usize>);
+
+#[derive(Default)]
+struct MeshBuilder {
+    corners: Vec<Corner>,
+    lookup: HashMap<Corner, usize>,
+    indices: Vec<[usize; 3]>,
+}
+
+impl MeshBuilder {
+    fn vertex(&mut self, corner: Corner) -> usize {
+        *self.lookup.entry(corner).or_insert_with(|| {
+            self.corners.push(corner);
+            self.corners.len() - 1
+        })
+    }
+}
+
+struct Parser<'a> {
+    path: &'a Path,
+    line: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: String) -> ObjError {
+        ObjError::Parse { path: self.path.to_path_buf(), line: self.line, message }
+    }
+
+    fn warn(&self, message: String) {
+        eprintln!("warning: {}:{}: {}", self.path.display(), self.line, message);
+    }
+
+    fn numbers<const N: usize>(&self, args: &[&str], required: usize, keyword: &str) -> Result<[f64; N], ObjError> {
+        if args.len() < required || args.len() > N {
+            return Err(self.error(format!("'{}' expects {} numbers but got {}", keyword, required, args.len())));
+        }
+        let mut out = [0.0; N];
+        for (i, arg) in args.iter().enumerate() {
+            out[i] = arg.parse().map_err(|_| self.error(format!("'{}' is not a number", arg)))?;
+        }
+        Ok(out)
+    }
+
+    // Resolves a 1-based (or negative, relative) OBJ index.
+    fn index(&self, s: &str, count: usize, kind: &str) -> Result<usize, ObjError> {
+        let i: i64 = s.parse().map_err(|_| self.error(format!("'{}' is not a {} index", s, kind)))?;
+        let resolved = if i > 0 { i - 1 } else { count as i64 + i };
+        if i == 0 || resolved < 0 || resolved >= count as i64 {
+            return Err(self.error(format!("{} index {} is out of range ({} defined)", kind, i, count)));
+        }
+        Ok(resolved as usize)
+    }
+
+    // The file a statement names, relative to the file being parsed.
+    fn file_arg(&self, args: &[&str], keyword: &str) -> Result<PathBuf, ObjError> {
+        // Options like -s or -bm come before the file name, so it is the last argument.
+        match args.last() {
+            Some(name) => Ok(self.path.parent().unwrap_or(Path::new("")).join(name)),
+            None => Err(self.error(format!("'{}' needs a file name", keyword))),
+        }
+    }
+
+    fn path_arg(&self, args: &[&str], keyword: &str) -> Result<PathBuf, ObjError> {
+        let full = self.file_arg(args, keyword)?;
+        if !full.is_file() {
+            return Err(self.error(format!("'{}' not found", full.display())));
+        }
+        Ok(full)
+    }
+}
+
+pub fn load_obj(path: &Path, default_material: Arc<dyn Material>) -> Result<Vec<TriangleMesh>, ObjError> {
+    let source = fs::read_to_string(path).map_err(|source| ObjError::Io { path: path.to_path_buf(), source })?;
+    Ok(parse_obj(path, &source, default_material)?.into_iter().map(TriangleMesh::new).collect())
+}
+
+// `path` names the file in errors and anchors mtllib and texture paths.
+fn parse_obj(path: &Path, source: &str, default_material: Arc<dyn Material>) -> Result<Vec<MeshData>, ObjError> {
+    let mut parser = Parser { path, line: 0 };
+
+    let mut positions: Vec<Point3> = Vec::new();
+    let mut texcoords: Vec<(f64, f64)> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+
+    let mut materials: HashMap<String, Arc<dyn Material>> = HashMap::new();
+    let mut group = String::new();
+    let mut material: Option<String> = None;
+
+    // Keyed by group and material name, in the order they first appear.
+    let mut builders: Vec<((String, Option<String>), MeshBuilder)> = Vec::new();
+    let mut current: Option<usize> = None;
+
+    for (n, line) in source.lines().enumerate() {
+        parser.line = n + 1;
+        let line = line.split('#').next().unwrap_or("");
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => {
+                // A fourth weight or trailing vertex colors are accepted and ignored.
+                let [x, y, z, ..] = parser.numbers::<7>(&args, 3, keyword)?;
+                positions.push(Point3::new(x, y, z));
+            }
+            "vt" => {
+                let [u, v, _w] = parser.numbers::<3>(&args, 1, keyword)?;
+                texcoords.push((u, v));
+            }
+            "vn" => {
+                let [x, y, z] = parser.numbers::<3>(&args, 3, keyword)?;
+                normals.push(Vec3::new(x, y, z));
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(parser.error(format!("face needs at least 3 vertices but has {}", args.len())));
+                }
+                let mut corners = Vec::with_capacity(args.len());
+                for arg in &args {
+                    let mut parts = arg.split('/');
+                    let v = parser.index(parts.next().unwrap_or(""), positions.len(), "vertex")?;
+                    let vt = match parts.next() {
+                        Some("") | None => None,
+                        Some(s) => Some(parser.index(s, texcoords.len(), "texcoord")?),
+                    };
+                    let vn = match parts.next() {
+                        Some("") | None => None,
+                        Some(s) => Some(parser.index(s, normals.len(), "normal")?),
+                    };
+                    if parts.next().is_some() {
+                        return Err(parser.error(format!("malformed face vertex '{}'", arg)));
+                    }
+                    corners.push((v, vt, vn));
+                }
+
+                let i = *current.get_or_insert_with(|| {
+                    let key = (group.clone(), material.clone());
+                    builders.iter().position(|(k, _)| *k == key).unwrap_or_else(|| {
+                        builders.push((key, MeshBuilder::default()));
+                        builders.len() - 1
+                    })
+                });
+                let builder = &mut builders[i].1;
+                let first = builder.vertex(corners[0]);
+                for pair in corners[1..].windows(2) {
+                    let b = builder.vertex(pair[0]);
+                    let c = builder.vertex(pair[1]);
+                    builder.indices.push([first, b, c]);
+                }
+            }
+            "g" | "o" => {
+                group = args.join(" ");
+                current = None;
+            }
+            "usemtl" => {
+                let name = args.join(" ");
+                if materials.contains_key(&name) {
+                    material = Some(name);
+                } else {
+                    parser.warn(format!("unknown material '{}', using the default", name));
+                    material = None;
+                }
+                current = None;
+            }
+            // A missing library only costs its materials, which usemtl then
+            // replaces with the default.
+            "mtllib" => {
+                let mtl_path = parser.file_arg(&args, keyword)?;
+                if mtl_path.is_file() {
+                    materials.extend(load_mtl(&mtl_path)?);
+                } else {
+                    parser.warn(format!("'{}' not found", mtl_path.display()));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut meshes = Vec::new();
+    for ((_, material), builder) in builders {
+        if builder.indices.is_empty() {
+            continue;
+        }
+        // A mesh only keeps normals or uvs when every corner has them.
+        let has_normals = builder.corners.iter().all(|c| c.2.is_some());
+        let has_uvs = builder.corners.iter().all(|c| c.1.is_some());
+
+        meshes.push(MeshData {
+            positions: builder.corners.iter().map(|c| positions[c.0]).collect(),
+            normals: if has_normals { builder.corners.iter().map(|c| normals[c.2.unwrap()]).collect() } else { Vec::new() },
+            uvs: if has_uvs { builder.corners.iter().map(|c| texcoords[c.1.unwrap()]).collect() } else { Vec::new() },
+            colors: Vec::new(),
+            indices: builder.indices,
+            material: match material {
+                Some(name) => Arc::clone(&materials[&name]),
+                None => Arc::clone(&default_material),
+            },
+            normal_map: None,
+        });
+    }
+
+    if meshes.is_empty() {
+        return Err(ObjError::Parse { path: path.to_path_buf(), line: parser.line, message: "no faces".to_string() });
+    }
+    Ok(meshes)
+}
+
+#[derive(Default)]
+struct MtlDesc {
+    kd: Option<Color>,
+    ks: Option<Color>,
+    ke: Option<Color>,
+    map_kd: Option<PathBuf>,
+    map_ke: Option<PathBuf>,
+    ns: Option<f64>,
+    ni: Option<f64>,
+    dissolve: Option<f64>,
+    illum: Option<u32>,
+}
+
+// The material an MTL entry becomes, checked in this order.
+#[derive(Debug, PartialEq)]
+enum MtlKind {
+    Light,
+    Glass,
+    Metal,
+    Diffuse,
+}
+
+impl MtlDesc {
+    fn kind(&self) -> MtlKind {
+        let emissive = self.map_ke.is_some() || self.ke.is_some_and(|ke| ke.x > 0.0 || ke.y > 0.0 || ke.z > 0.0);
+        let transparent = matches!(self.illum, Some(4 | 6 | 7)) || self.dissolve.is_some_and(|d| d < 1.0);
+        if emissive {
+            MtlKind::Light
+        } else if transparent {
+            MtlKind::Glass
+        } else if matches!(self.illum, Some(3 | 5)) {
+            MtlKind::Metal
+        } else {
+            MtlKind::Diffuse
+        }
+    }
+
+    fn material(self) -> Arc<dyn Material> {
+        match self.kind() {
+            MtlKind::Light => Arc::new(DiffuseLight::new(texture(self.map_ke, self.ke.unwrap_or(Color::new(1.0, 1.0, 1.0))))),
+            MtlKind::Glass => Arc::new(Dielectric::new(self.ni.unwrap_or(1.5))),
+            MtlKind::Metal => {
+                // Phong exponent to roughness through the usual alpha = sqrt(2 / (Ns + 2)):
+                // sharp highlights give a clean mirror.
+                let alpha = (2.0 / (self.ns.unwrap_or(1000.0) + 2.0)).sqrt().min(1.0);
+                Arc::new(Metal::new(self.ks.unwrap_or(Color::new(0.8, 0.8, 0.8)), alpha.sqrt()))
+            }
+            MtlKind::Diffuse => {
+                Arc::new(Lambertian::new_from_texture(texture(self.map_kd, self.kd.unwrap_or(Color::new(0.8, 0.8, 0.8)))))
+            }
+        }
+    }
+}
+
+fn texture(map: Option<PathBuf>, color: Color) -> Arc<dyn Texture> {
+    match map {
+        Some(path) => Arc::new(ImageTexture::new(&path.to_string_lossy())),
+        None => Arc::new(SolidColor::new(color)),
+    }
+}
+
+fn load_mtl(path: &Path) -> Result<HashMap<String, Arc<dyn Material>>, ObjError> {
+    let source = fs::read_to_string(path).map_err(|source| ObjError::Io { path: path.to_path_buf(), source })?;
+    Ok(parse_mtl(path, &source)?.into_iter().map(|(name, desc)| (name, desc.material())).collect())
+}
+
+fn parse_mtl(path: &Path, source: &str) -> Result<Vec<(String, MtlDesc)>, ObjError> {
+    let mut parser = Parser { path, line: 0 };
+
+    let mut materials = Vec::new();
+    let mut current: Option<(String, MtlDesc)> = None;
+
+    for (n, line) in source.lines().enumerate() {
+        parser.line = n + 1;
+        let line = line.split('#').next().unwrap_or("");
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            if args.is_empty() {
+                return Err(parser.error("'newmtl' needs a name".to_string()));
+            }
+            materials.extend(current.take());
+            current = Some((args.join(" "), MtlDesc::default()));
+            continue;
+        }
+
+        let desc = match &mut current {
+            Some((_, desc)) => desc,
+            None => return Err(parser.error(format!("'{}' before any 'newmtl'", keyword))),
+        };
+
+        let color = |parser: &Parser| -> Result<Color, ObjError> {
+            let [r, g, b] = parser.numbers::<3>(&args, 3, keyword)?;
+            Ok(Color::new(r, g, b))
+        };
+        let scalar = |parser: &Parser| -> Result<f64, ObjError> { Ok(parser.numbers::<1>(&args, 1, keyword)?[0]) };
+
+        match keyword {
+            "Kd" => desc.kd = Some(color(&parser)?),
+            "Ks" => desc.ks = Some(color(&parser)?),
+            "Ke" => desc.ke = Some(color(&parser)?),
+            "Ns" => desc.ns = Some(scalar(&parser)?),
+            "Ni" => desc.ni = Some(scalar(&parser)?),
+            "d" => desc.dissolve = Some(scalar(&parser)?),
+            "Tr" => desc.dissolve = Some(1.0 - scalar(&parser)?),
+            "illum" => {
+                let illum = scalar(&parser)?;
+                if illum < 0.0 || illum.fract() != 0.0 {
+                    return Err(parser.error(format!("'{}' is not an illumination model", args[0])));
+                }
+                desc.illum = Some(illum as u32);
+            }
+            "map_Kd" => desc.map_kd = Some(parser.path_arg(&args, keyword)?),
+            "map_Ke" => desc.map_ke = Some(parser.path_arg(&args, keyword)?),
+            _ => {}
+        }
+    }
+
+    materials.extend(current);
+    Ok(materials)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new_from_color(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    fn parse(source: &str) -> Result<Vec<MeshData>, ObjError> {
+        parse_obj(Path::new("test.obj"), source, gray())
+    }
+
+    fn error_line(result: Result<Vec<MeshData>, ObjError>) -> (usize, String) {
+        match result {
+            Err(ObjError::Parse { line, message, .. }) => (line, message),
+            Err(e) => panic!("expected a parse error, got {}", e),
+            Ok(_) => panic!("expected a parse error"),
+        }
+    }
+
+    fn kinds(source: &str) -> Vec<(String, MtlKind)> {
+        let descs = parse_mtl(Path::new("test.mtl"), source).unwrap();
+        descs.into_iter().map(|(name, desc)| (name, desc.kind())).collect()
+    }
+
+    const SQUARE: &str = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n";
+
+    #[test]
+    fn polygons_are_triangulated_as_fans() {
+        let meshes = parse(&format!("{}v 0.5 1.5 0\nf 1 2 3 4 5\n", SQUARE)).unwrap();
+        assert_eq!(meshes.len(), 1);
+        assert_eq!(meshes[0].indices, vec![[0, 1, 2], [0, 2, 3], [0, 3, 4]]);
+        assert_eq!(meshes[0].positions.len(), 5);
+    }
+
+    #[test]
+    fn negative_indices_count_back_from_the_last_vertex() {
+        let absolute = parse(&format!("{}f 2 3 4\n", SQUARE)).unwrap();
+        let relative = parse(&format!("{}f -3 -2 -1\n", SQUARE)).unwrap();
+        assert_eq!(absolute[0].positions, relative[0].positions);
+        assert_eq!(relative[0].positions[0], Point3::new(1.0, 0.0, 0.0));
+
+        // Relative to the vertices defined so far, not the whole file.
+        let meshes = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\nv 5 5 5\n").unwrap();
+        assert_eq!(meshes[0].positions[0], Point3::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn face_vertex_forms() {
+        let header = format!("{}vt 0 0\nvt 1 0\nvt 1 1\nvn 0 0 1\n", SQUARE);
+
+        let both = parse(&format!("{}f 1/1/1 2/2/1 3/3/1\n", header)).unwrap();
+        assert_eq!(both[0].uvs, vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)]);
+        assert_eq!(both[0].normals, vec![Vec3::new(0.0, 0.0, 1.0); 3]);
+
+        let uvs = parse(&format!("{}f 1/1 2/2 3/3\n", header)).unwrap();
+        assert_eq!(uvs[0].uvs.len(), 3);
+        assert!(uvs[0].normals.is_empty());
+
+        let normals = parse(&format!("{}f 1//1 2//1 3//1\n", header)).unwrap();
+        assert!(normals[0].uvs.is_empty());
+        assert_eq!(normals[0].normals.len(), 3);
+
+        // Corners that share a position but not a texcoord stay separate, and
+        // a mesh where some corners lack normals keeps none.
+        let mixed = parse(&format!("{}f 1/1/1 2/2 3/3/1\nf 1/2/1 3/3/1 4/1/1\n", header)).unwrap();
+        assert_eq!(mixed[0].positions.len(), 5);
+        assert!(mixed[0].normals.is_empty());
+        assert_eq!(mixed[0].uvs.len(), 5);
+    }
+
+    #[test]
+    fn groups_and_materials_split_meshes() {
+        let meshes = parse(&format!("{}f 1 2 3\ng lid\nf 1 3 4\ng\nf 2 3 4\n", SQUARE)).unwrap();
+        assert_eq!(meshes.len(), 2);
+        assert_eq!(meshes[0].indices.len(), 2);
+        assert_eq!(meshes[1].indices.len(), 1);
+    }
+
+    #[test]
+    fn mtl_statements_choose_the_material() {
+        let source = "\
+newmtl lamp
+Kd 0.8 0.8 0.8
+Ke 4 4 4
+illum 4
+
+newmtl unlit
+Ke 0 0 0
+Kd 0.5 0.5 0.5
+
+newmtl glass
+illum 6
+Ni 1.45
+
+newmtl film
+illum 2
+d 0.5
+
+newmtl tinted
+Tr 0.25
+
+newmtl chrome
+illum 3
+Ks 0.9 0.9 0.9
+
+newmtl brass
+illum 5
+
+newmtl plaster
+illum 2
+Kd 0.7 0.7 0.7
+";
+        let expected = [
+            ("lamp", MtlKind::Light),
+            ("unlit", MtlKind::Diffuse),
+            ("glass", MtlKind::Glass),
+            ("film", MtlKind::Glass),
+            ("tinted", MtlKind::Glass),
+            ("chrome", MtlKind::Metal),
+            ("brass", MtlKind::Metal),
+            ("plaster", MtlKind::Diffuse),
+        ];
+        let kinds = kinds(source);
+        assert_eq!(kinds.len(), expected.len());
+        for ((name, kind), (expected_name, expected_kind)) in kinds.iter().zip(expected) {
+            assert_eq!((name.as_str(), kind), (expected_name, &expected_kind));
+        }
+    }
+
+    #[test]
+    fn usemtl_applies_the_library_materials() {
+        let dir = std::env::temp_dir().join(format!("obj_mtllib_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("lamp.mtl"), "newmtl bulb\nKe 5 5 5\nnewmtl shade\nKd 0.2 0.2 0.2\n").unwrap();
+        let source = format!("mtllib lamp.mtl\n{}f 1 2 3\nusemtl bulb\nf 1 3 4\nusemtl shade\nf 2 3 4\n", SQUARE);
+        let meshes = parse_obj(&dir.join("lamp.obj"), &source, gray()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let emissive: Vec<bool> = meshes.iter().map(|mesh| mesh.material.is_emissive()).collect();
+        assert_eq!(emissive, vec![false, true, false]);
+    }
+
+    #[test]
+    fn missing_materials_fall_back_to_the_default() {
+        let default = gray();
+        let source = format!("mtllib missing.mtl\n{}usemtl missing\nf 1 2 3\n", SQUARE);
+        let meshes = parse_obj(Path::new("test.obj"), &source, Arc::clone(&default)).unwrap();
+        assert_eq!(meshes.len(), 1);
+        assert!(Arc::ptr_eq(&meshes[0].material, &default));
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        assert_eq!(
+            error_line(parse(&format!("{}\n# comment\nf 1 2 7\n", SQUARE))),
+            (7, "vertex index 7 is out of range (4 defined)".to_string())
+        );
+        assert_eq!(
+            error_line(parse(&format!("{}f 1 2 -5\n", SQUARE))),
+            (5, "vertex index -5 is out of range (4 defined)".to_string())
+        );
+        assert_eq!(error_line(parse("v 0 0 zero\n")), (1, "'zero' is not a number".to_string()));
+        assert_eq!(error_line(parse(&format!("{}f 1 2\n", SQUARE))), (5, "face needs at least 3 vertices but has 2".to_string()));
+        assert_eq!(error_line(parse(&format!("{}f 1/1/1/1 2 3\n", SQUARE))).0, 5);
+        assert_eq!(error_line(parse(SQUARE)).1, "no faces");
+
+        let (line, message) = match parse_mtl(Path::new("test.mtl"), "# header\nKd 1 1 1\n") {
+            Err(ObjError::Parse { line, message, .. }) => (line, message),
+            _ => panic!("expected a parse error"),
+        };
+        assert_eq!((line, message.as_str()), (2, "'Kd' before any 'newmtl'"));
+    }
+}
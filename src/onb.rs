@@ -1,4 +1,4 @@
-use crate::vec3::Vec3;
+use crate::vec3::{dot, Vec3};
 
 
 pub struct Onb {
@@ -32,6 +32,11 @@ impl Onb {
         self.u() * a.x + self.v() * a.y + self.w() * a.z
     }
 
+    // The inverse of local_vec: a world vector's components along u, v and w.
+    pub fn to_local(&self, a: Vec3) -> Vec3 {
+        Vec3::new(dot(&a, &self.u()), dot(&a, &self.v()), dot(&a, &self.w()))
+    }
+
     pub fn build_from_w(n: Vec3) -> Self {
         let w = n.unit_vector();
         let a = if w.x.abs() > 0.9 {
@@ -46,4 +51,19 @@ impl Onb {
             axis: [u, v, w,],
         }
     }
+
+    // Like build_from_w, with u along the part of `tangent` perpendicular to
+    // w; falls back to build_from_w where the two are parallel.
+    pub fn build_from_w_and_tangent(n: Vec3, tangent: Vec3) -> Self {
+        let w = n.unit_vector();
+        let u = tangent - dot(&tangent, &w) * w;
+        if u.length_squared() < 1e-12 {
+            return Self::build_from_w(n);
+        }
+        let u = u.unit_vector();
+
+        Self {
+            axis: [u, w.cross(&u), w],
+        }
+    }
 }
@@ -0,0 +1,173 @@
+use std::{fs::File, io::{self, BufWriter, Write}, path::Path};
+
+use clap::ValueEnum;
+use image::{ImageBuffer, Rgb, Rgb32FImage, RgbImage};
+
+use crate::{color::{display_color, encoded_color, write_color, DisplaySettings}, vec3::Color};
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum BitDepth {
+    #[value(name = "8")]
+    Eight,
+    #[value(name = "16")]
+    Sixteen,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum OutputFormat {
+    Ppm,
+    Png,
+    Jpeg,
+    Tiff,
+    Exr,
+    Hdr,
+}
+
+impl OutputFormat {
+    pub fn from_path(path: &Path) -> io::Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase())
+            .unwrap_or_default();
+
+        match extension.as_str() {
+            "ppm" => Ok(OutputFormat::Ppm),
+            "png" => Ok(OutputFormat::Png),
+            "jpg" | "jpeg" => Ok(OutputFormat::Jpeg),
+            "tif" | "tiff" => Ok(OutputFormat::Tiff),
+            "exr" => Ok(OutputFormat::Exr),
+            "hdr" => Ok(OutputFormat::Hdr),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "unsupported output format '{}' (use png, jpg, tif, exr, hdr or ppm)",
+                    path.display()
+                ),
+            )),
+        }
+    }
+
+    // The format for `path`, as long as it can store `bit_depth`.
+    pub fn with_bit_depth(path: &Path, bit_depth: BitDepth) -> io::Result<Self> {
+        let format = Self::from_path(path)?;
+        if matches!((format, bit_depth), (OutputFormat::Jpeg, BitDepth::Sixteen)) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("JPEG is 8-bit only; write '{}' with --bit-depth 8 or use png or tif", path.display()),
+            ));
+        }
+        Ok(format)
+    }
+}
+
+// Writes the accumulated pixel sums, top row first, in the format picked by
+// the file extension. PNG, JPEG and TIFF go through the display settings;
+// EXR and HDR keep the unclamped linear radiance, without exposure or tone
+// mapping, so renders can be compared numerically.
+pub fn write_image(
+    path: &Path,
+    pixels: &[Color],
+    width: u32,
+    height: u32,
+    samples_per_pixel: u32,
+    display: &DisplaySettings,
+    bit_depth: BitDepth,
+) -> io::Result<()> {
+    match (OutputFormat::with_bit_depth(path, bit_depth)?, bit_depth) {
+        (OutputFormat::Ppm, _) => {
+            write_ppm(BufWriter::new(File::create(path)?), pixels, width, height, samples_per_pixel, display)
+        }
+        (OutputFormat::Png | OutputFormat::Tiff, BitDepth::Sixteen) => {
+            save(to_rgb16(pixels, width, height, samples_per_pixel, display), path)
+        }
+        (OutputFormat::Png | OutputFormat::Tiff | OutputFormat::Jpeg, _) => {
+            save(to_rgb8(pixels, width, height, samples_per_pixel, display), path)
+        }
+        (OutputFormat::Exr | OutputFormat::Hdr, _) => {
+            save(to_linear(pixels, width, height, samples_per_pixel), path)
+        }
+    }
+}
+
+pub fn write_ppm(
+    mut out: impl Write,
+    pixels: &[Color],
+    width: u32,
+    height: u32,
+    samples_per_pixel: u32,
+    display: &DisplaySettings,
+) -> io::Result<()> {
+    write!(out, "P3\n{} {}\n255\n", width, height)?;
+
+    for color in pixels {
+        write_color(&mut out, *color, samples_per_pixel, display)?;
+    }
+
+    out.flush()
+}
+
+fn save<P>(image: ImageBuffer<P, Vec<P::Subpixel>>, path: &Path) -> io::Result<()>
+where
+    P: image::PixelWithColorType,
+    [P::Subpixel]: image::EncodableLayout,
+{
+    image.save(path).map_err(io::Error::other)
+}
+
+fn to_rgb8(pixels: &[Color], width: u32, height: u32, samples_per_pixel: u32, display: &DisplaySettings) -> RgbImage {
+    ImageBuffer::from_fn(width, height, |i, j| {
+        let c = display_color(pixels[(j * width + i) as usize], samples_per_pixel, display);
+        Rgb([(256.0 * c.x) as u8, (256.0 * c.y) as u8, (256.0 * c.z) as u8])
+    })
+}
+
+fn to_rgb16(pixels: &[Color], width: u32, height: u32, samples_per_pixel: u32, display: &DisplaySettings) -> ImageBuffer<Rgb<u16>, Vec<u16>> {
+    ImageBuffer::from_fn(width, height, |i, j| {
+        // Rounded to the nearest code, so full white is 65535.
+        let c = encoded_color(pixels[(j * width + i) as usize], samples_per_pixel, display);
+        Rgb([(65535.0 * c.x).round() as u16, (65535.0 * c.y).round() as u16, (65535.0 * c.z).round() as u16])
+    })
+}
+
+fn to_linear(pixels: &[Color], width: u32, height: u32, samples_per_pixel: u32) -> Rgb32FImage {
+    let scale = 1.0 / samples_per_pixel as f64;
+    ImageBuffer::from_fn(width, height, |i, j| {
+        let c = scale * pixels[(j * width + i) as usize];
+        Rgb([c.x as f32, c.y as f32, c.z as f32])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::linear_to_srgb;
+
+    #[test]
+    fn sixteen_bit_output_spans_the_full_range() {
+        let pixels = [
+            Color::new(0.0, 1.0, 4.0),
+            Color::new(0.5, 0.01, 0.999),
+        ];
+        let image = to_rgb16(&pixels, 2, 1, 1, &DisplaySettings::default());
+        // Black, white and overexposed white.
+        assert_eq!(image.get_pixel(0, 0).0, [0, 65535, 65535]);
+        let expected = [0.5, 0.01, 0.999].map(|x: f64| (65535.0 * linear_to_srgb(x)).round() as u16);
+        assert_eq!(image.get_pixel(1, 0).0, expected);
+
+        // Eight bits still tops out at 255.
+        assert_eq!(to_rgb8(&pixels, 2, 1, 1, &DisplaySettings::default()).get_pixel(0, 0).0, [0, 255, 255]);
+    }
+
+    #[test]
+    fn jpeg_refuses_sixteen_bits() {
+        for name in ["out.jpg", "out.JPEG"] {
+            let error = OutputFormat::with_bit_depth(Path::new(name), BitDepth::Sixteen).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+            let error = write_image(Path::new(name), &[Color::ZERO], 1, 1, 1, &DisplaySettings::default(), BitDepth::Sixteen).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        }
+        assert!(OutputFormat::with_bit_depth(Path::new("out.jpg"), BitDepth::Eight).is_ok());
+        assert!(OutputFormat::with_bit_depth(Path::new("out.png"), BitDepth::Sixteen).is_ok());
+    }
+}
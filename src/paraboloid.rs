@@ -0,0 +1,167 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    material::Material,
+    onb::Onb,
+    ray::Ray,
+    rtweekend::{PI, Sampler},
+    vec3::{Point3, Vec3},
+};
+
+// A dish with its vertex at p0, opening toward p1 where its rim has
+// `radius`; the rim is left open. The outside of the dish is its front.
+// Hits are found with p0 at the origin and the axis along local z. u goes
+// around the axis and v runs from the vertex to the rim.
+pub struct Paraboloid {
+    pub p0: Point3,
+    pub p1: Point3,
+    pub radius: f64,
+    pub material: Arc<dyn Material>,
+    uvw: Onb,
+    height: f64,
+}
+
+impl Paraboloid {
+    pub fn new(p0: Point3, p1: Point3, radius: f64, material: Arc<dyn Material>) -> Self {
+        Self {
+            p0,
+            p1,
+            radius,
+            material,
+            uvw: Onb::build_from_w(p1 - p0),
+            height: (p1 - p0).length(),
+        }
+    }
+}
+
+impl Hittable for Paraboloid {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, _sampler: &mut Sampler) -> Option<HitRecord> {
+        let o = self.uvw.to_local(r.origin - self.p0);
+        let d = self.uvw.to_local(r.direction);
+
+        // x^2 + y^2 = k z along the ray, with k fixed by the rim.
+        let k = self.radius * self.radius / self.height;
+        let a = d.x * d.x + d.y * d.y;
+        let b = 2.0 * (o.x * d.x + o.y * d.y) - k * d.z;
+        let c = o.x * o.x + o.y * o.y - k * o.z;
+
+        let mut roots = [f64::NAN; 2];
+        if a.abs() < 1e-12 {
+            // Parallel to the axis: one crossing at most.
+            if b.abs() > 1e-12 {
+                roots[0] = -c / b;
+            }
+        } else {
+            let discriminant = b * b - 4.0 * a * c;
+            if discriminant < 0.0 {
+                return None;
+            }
+            let sqrt_d = discriminant.sqrt();
+            roots = [(-b - sqrt_d) / (2.0 * a), (-b + sqrt_d) / (2.0 * a)];
+            if roots[0] > roots[1] {
+                roots.swap(0, 1);
+            }
+        }
+
+        let t = roots.into_iter().find(|&t| {
+            let z = o.z + t * d.z;
+            t >= t_min && t <= t_max && (0.0..=self.height).contains(&z)
+        })?;
+
+        let p = o + t * d;
+        let outward_normal = Vec3::new(2.0 * p.x, 2.0 * p.y, -k).unit_vector();
+
+        let mut rec = HitRecord {
+            t,
+            p: r.at(t),
+            u: (p.y.atan2(p.x) + PI) / (2.0 * PI),
+            v: p.z / self.height,
+            normal: Vec3::ZERO,
+            front_face: false,
+            color: None,
+            material: Arc::clone(&self.material),
+        };
+        rec.set_face_normal(r, self.uvw.local_vec(outward_normal));
+
+        Some(rec)
+    }
+
+    // The dish never leaves the cylinder through its rim, so that
+    // cylinder's box is used; see Cylinder.
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        let w = self.uvw.w();
+        let extent = self.radius * Vec3::new(
+            (1.0 - w.x * w.x).max(0.0).sqrt(),
+            (1.0 - w.y * w.y).max(0.0).sqrt(),
+            (1.0 - w.z * w.z).max(0.0).sqrt(),
+        );
+        let min = Point3::new(self.p0.x.min(self.p1.x), self.p0.y.min(self.p1.y), self.p0.z.min(self.p1.z));
+        let max = Point3::new(self.p0.x.max(self.p1.x), self.p0.y.max(self.p1.y), self.p0.z.max(self.p1.z));
+        Some(Aabb::new(min - extent, max + extent))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Lambertian, rtweekend::INFINITY, vec3::Color};
+
+    // Vertex at the origin opening upward to a rim of radius 2 at y = 4,
+    // so y = x^2 + z^2.
+    fn dish() -> Paraboloid {
+        let material = Arc::new(Lambertian::new_from_color(Color::new(0.5, 0.5, 0.5)));
+        Paraboloid::new(Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 4.0, 0.0), 2.0, material)
+    }
+
+    #[test]
+    fn hits_the_outside_from_the_side() {
+        let mut sampler = Sampler::new(0);
+        let r = Ray::new(Point3::new(-5.0, 1.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let rec = dish().hit(&r, 0.001, INFINITY, &mut sampler).unwrap();
+        assert!((rec.t - 4.0).abs() < 1e-9);
+        assert!(rec.front_face);
+        // Gradient of x^2 + z^2 - y at (-1, 1, 0).
+        assert!((rec.normal - Vec3::new(-2.0, -1.0, 0.0).unit_vector()).length() < 1e-9);
+        assert!((rec.v - 0.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn looking_into_the_dish_sees_its_back_face() {
+        let mut sampler = Sampler::new(0);
+        let down = Ray::new(Point3::new(1.0, 10.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let rec = dish().hit(&down, 0.001, INFINITY, &mut sampler).unwrap();
+        assert!((rec.t - 9.0).abs() < 1e-9);
+        assert!(!rec.front_face);
+        assert!(rec.normal.y > 0.0);
+    }
+
+    #[test]
+    fn vertex_and_axis() {
+        let mut sampler = Sampler::new(0);
+        // Straight down the axis, through the open rim to the vertex.
+        let down = Ray::new(Point3::new(0.0, 10.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let rec = dish().hit(&down, 0.001, INFINITY, &mut sampler).unwrap();
+        assert!((rec.t - 10.0).abs() < 1e-9);
+        assert!(rec.v.abs() < 1e-9);
+        let up = Ray::new(Point3::new(0.0, -1.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        assert!(dish().hit(&up, 0.001, INFINITY, &mut sampler).unwrap().front_face);
+    }
+
+    #[test]
+    fn misses_and_grazing() {
+        let mut sampler = Sampler::new(0);
+        // Above the rim, and below the vertex.
+        let above = Ray::new(Point3::new(-5.0, 4.01, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(dish().hit(&above, 0.001, INFINITY, &mut sampler).is_none());
+        let below = Ray::new(Point3::new(-5.0, -0.01, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(dish().hit(&below, 0.001, INFINITY, &mut sampler).is_none());
+        // Straight down just inside the rim hits the inner wall; just
+        // outside it misses the dish entirely.
+        let inside = Ray::new(Point3::new(2.0 - 1e-6, 10.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(!dish().hit(&inside, 0.001, INFINITY, &mut sampler).unwrap().front_face);
+        let outside = Ray::new(Point3::new(2.0 + 1e-6, 10.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(dish().hit(&outside, 0.001, INFINITY, &mut sampler).is_none());
+    }
+}
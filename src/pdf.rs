@@ -1,18 +1,47 @@
 use std::sync::Arc;
 
-use rand::random;
+use crate::{hittable::Hittable, microfacet::{dielectric_pdf, dielectric_sample, Ggx, Gtr1}, onb::Onb, rtweekend::{PI, Sampler}, vec3::{random_cosine_direction, random_unit_vector, reflect, Point3, Vec3}};
 
-use crate::{hittable::Hittable, onb::Onb, rtweekend::{random_double, PI}, vec3::{random_cosine_direction, Point3, Vec3}};
+pub trait Pdf: Send + Sync {
+    fn value(&self, direction: &Vec3, sampler: &mut Sampler) -> f64;
+    // A zero vector means the sample was rejected and the path ends.
+    fn generate(&self, sampler: &mut Sampler) -> Vec3;
+}
 
+pub struct SpherePdf;
 
+pub struct CosinePdf {
+    uvw: Onb,
+}
 
-pub trait Pdf: Send + Sync {
-    fn value(&self, direction: &Vec3) -> f64;
-    fn generate(&self) -> Vec3;
+// Reflections off visible GGX normals, seen from wo (a unit vector in
+// uvw's frame, pointing away from the surface).
+pub struct GgxPdf {
+    uvw: Onb,
+    wo: Vec3,
+    ggx: Ggx,
 }
 
-pub struct CosinePdf {
+// Reflection or refraction through visible GGX normals of an interface
+// with relative index eta (see dielectric_bsdf), seen from wo.
+pub struct GgxDielectricPdf {
     uvw: Onb,
+    wo: Vec3,
+    ggx: Ggx,
+    eta: f64,
+}
+
+// Reflections off GTR1 normals (a clearcoat), seen from wo.
+pub struct Gtr1Pdf {
+    uvw: Onb,
+    wo: Vec3,
+    gtr1: Gtr1,
+}
+
+// One of a material's lobes, picked with the probabilities given (which sum
+// to one).
+pub struct LobePdf {
+    lobes: Vec<(f64, Arc<dyn Pdf>)>,
 }
 
 pub struct HittablePdf {
@@ -30,6 +59,30 @@ impl MixturePdf {
     }
 }
 
+impl GgxPdf {
+    pub fn new(uvw: Onb, wo: Vec3, ggx: Ggx) -> Self {
+        Self { uvw, wo, ggx }
+    }
+}
+
+impl GgxDielectricPdf {
+    pub fn new(uvw: Onb, wo: Vec3, ggx: Ggx, eta: f64) -> Self {
+        Self { uvw, wo, ggx, eta }
+    }
+}
+
+impl Gtr1Pdf {
+    pub fn new(uvw: Onb, wo: Vec3, gtr1: Gtr1) -> Self {
+        Self { uvw, wo, gtr1 }
+    }
+}
+
+impl LobePdf {
+    pub fn new(lobes: Vec<(f64, Arc<dyn Pdf>)>) -> Self {
+        Self { lobes }
+    }
+}
+
 impl HittablePdf {
     pub fn new(ptr: Arc<dyn Hittable>, origin: Point3) -> Self {
         Self {
@@ -48,31 +101,96 @@ impl CosinePdf {
 }
 
 impl Pdf for MixturePdf {
-    fn value(&self, direction: &Vec3) -> f64 {
-        0.5 * self.p[0].value(direction) + 0.5 * self.p[1].value(direction)
+    fn value(&self, direction: &Vec3, sampler: &mut Sampler) -> f64 {
+        0.5 * self.p[0].value(direction, sampler) + 0.5 * self.p[1].value(direction, sampler)
     }
 
-    fn generate(&self) -> Vec3 {
-        if random_double() < 0.5 {
-            self.p[0].generate()
+    fn generate(&self, sampler: &mut Sampler) -> Vec3 {
+        if sampler.random_double() < 0.5 {
+            self.p[0].generate(sampler)
         } else {
-            self.p[1].generate()
+            self.p[1].generate(sampler)
         }
     }
 }
 
+impl Pdf for GgxPdf {
+    fn value(&self, direction: &Vec3, _sampler: &mut Sampler) -> f64 {
+        let h = self.wo + self.uvw.to_local(direction.unit_vector());
+        if h.near_zero() {
+            return 0.0;
+        }
+        self.ggx.reflection_pdf(&self.wo, &h.unit_vector())
+    }
+
+    // Can land below the surface; the material gives those no weight.
+    fn generate(&self, sampler: &mut Sampler) -> Vec3 {
+        let h = self.ggx.sample_visible(&self.wo, sampler);
+        self.uvw.local_vec(reflect(&-self.wo, &h))
+    }
+}
+
+impl Pdf for GgxDielectricPdf {
+    fn value(&self, direction: &Vec3, _sampler: &mut Sampler) -> f64 {
+        dielectric_pdf(&self.ggx, &self.wo, &self.uvw.to_local(direction.unit_vector()), self.eta)
+    }
+
+    // A reflection below the surface or a refraction above it can't be
+    // told apart from a real sample of the other kind, so it is rejected.
+    fn generate(&self, sampler: &mut Sampler) -> Vec3 {
+        match dielectric_sample(&self.ggx, &self.wo, self.eta, sampler) {
+            Some(wi) => self.uvw.local_vec(wi),
+            None => Vec3::ZERO,
+        }
+    }
+}
+
+impl Pdf for Gtr1Pdf {
+    fn value(&self, direction: &Vec3, _sampler: &mut Sampler) -> f64 {
+        let h = self.wo + self.uvw.to_local(direction.unit_vector());
+        if h.near_zero() {
+            return 0.0;
+        }
+        self.gtr1.reflection_pdf(&self.wo, &h.unit_vector())
+    }
+
+    // Like GgxPdf, can land below the surface.
+    fn generate(&self, sampler: &mut Sampler) -> Vec3 {
+        let h = self.gtr1.sample(sampler);
+        self.uvw.local_vec(reflect(&-self.wo, &h))
+    }
+}
+
+impl Pdf for LobePdf {
+    fn value(&self, direction: &Vec3, sampler: &mut Sampler) -> f64 {
+        self.lobes.iter().map(|(weight, pdf)| weight * pdf.value(direction, sampler)).sum()
+    }
+
+    fn generate(&self, sampler: &mut Sampler) -> Vec3 {
+        let mut u = sampler.random_double();
+        for (weight, pdf) in &self.lobes {
+            if u < *weight {
+                return pdf.generate(sampler);
+            }
+            u -= weight;
+        }
+        // Rounding left u past the last weight.
+        self.lobes.last().map_or(Vec3::ZERO, |(_, pdf)| pdf.generate(sampler))
+    }
+}
+
 impl Pdf for HittablePdf {
-    fn value(&self, direction: &Vec3) -> f64 {
-        self.ptr.pdf_value(&self.origin, direction)
+    fn value(&self, direction: &Vec3, sampler: &mut Sampler) -> f64 {
+        self.ptr.pdf_value(&self.origin, direction, sampler)
     }
 
-    fn generate(&self) -> Vec3 {
-        self.ptr.random(&self.origin)
+    fn generate(&self, sampler: &mut Sampler) -> Vec3 {
+        self.ptr.random(&self.origin, sampler)
     }
 }
 
 impl Pdf for CosinePdf {
-    fn value(&self, direction: &Vec3) -> f64 {
+    fn value(&self, direction: &Vec3, _sampler: &mut Sampler) -> f64 {
        let cosine = direction.unit_vector().dot(&self.uvw.w()) ;
         if cosine <= 0.0 {
             0.0
@@ -81,7 +199,29 @@ impl Pdf for CosinePdf {
         }
     }
 
-    fn generate(&self) -> Vec3 {
-        self.uvw.local_vec(random_cosine_direction())
+    fn generate(&self, sampler: &mut Sampler) -> Vec3 {
+        self.uvw.local_vec(random_cosine_direction(sampler))
+    }
+}
+
+impl Pdf for SpherePdf {
+    fn value(&self, _direction: &Vec3, _sampler: &mut Sampler) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn generate(&self, sampler: &mut Sampler) -> Vec3 {
+        random_unit_vector(sampler)
+    }
+}
+
+// Power heuristic (beta = 2) weight for a sample drawn with density pdf_a
+// when pdf_b could also have produced it.
+pub fn power_heuristic(pdf_a: f64, pdf_b: f64) -> f64 {
+    let a = pdf_a * pdf_a;
+    let b = pdf_b * pdf_b;
+    if a + b == 0.0 {
+        0.0
+    } else {
+        a / (a + b)
     }
 }
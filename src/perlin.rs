@@ -1,4 +1,4 @@
-use crate::{rtweekend::random_int, vec3::{dot, Point3, Vec3}};
+use crate::{rtweekend::Sampler, vec3::{dot, Point3, Vec3}};
 
 
 pub struct Perlin {
@@ -12,14 +12,14 @@ pub struct Perlin {
 impl Perlin {
     const POINT_COUNT: usize = 256;
 
-    pub fn new() -> Self {
+    pub fn new(sampler: &mut Sampler) -> Self {
         let ranvec = (0..Self::POINT_COUNT)
-            .map(|_| Vec3::random_range(-1.0, 1.0).unit_vector())
+            .map(|_| Vec3::random_range(-1.0, 1.0, sampler).unit_vector())
             .collect::<Vec<_>>();
 
-        let perm_x = Self::perlin_generate_perm();
-        let perm_y = Self::perlin_generate_perm();
-        let perm_z = Self::perlin_generate_perm();
+        let perm_x = Self::perlin_generate_perm(sampler);
+        let perm_y = Self::perlin_generate_perm(sampler);
+        let perm_z = Self::perlin_generate_perm(sampler);
 
 
         Self {
@@ -122,15 +122,15 @@ impl Perlin {
         accum
     }
 
-    fn perlin_generate_perm() -> Vec<usize> {
+    fn perlin_generate_perm(sampler: &mut Sampler) -> Vec<usize> {
         let mut p: Vec<usize> = (0..Self::POINT_COUNT).collect();
-        Self::permute(&mut p);
+        Self::permute(&mut p, sampler);
         p
     }
 
-    fn permute(p: &mut Vec<usize>) {
+    fn permute(p: &mut Vec<usize>, sampler: &mut Sampler) {
         for i in (1..p.len()).rev() {
-            let target = random_int(0, i as i32);
+            let target = sampler.random_int(0, i as i32);
             p.swap(i, target as usize);
         }
     }
@@ -0,0 +1,532 @@
+use std::{fmt, fs, io, path::{Path, PathBuf}, sync::Arc};
+
+use crate::{
+    material::{Lambertian, Material},
+    texture::VertexColorTexture,
+    triangle::{MeshData, TriangleMesh},
+    vec3::{Color, Point3, Vec3},
+};
+
+// Stanford PLY in ascii, binary_little_endian or binary_big_endian. The
+// vertex element supplies x/y/z, optional nx/ny/nz, u/v (or s/t) and
+// red/green/blue; faces are polygons in vertex_indices (or vertex_index),
+// triangulated as fans. Other elements and properties are skipped.
+
+#[derive(Debug)]
+pub enum PlyError {
+    Io { path: PathBuf, source: io::Error },
+    // Header problems and ascii data carry a line number.
+    Parse { path: PathBuf, line: usize, message: String },
+    // Binary data carries the byte offset it went wrong at.
+    Binary { path: PathBuf, offset: usize, message: String },
+}
+
+impl fmt::Display for PlyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlyError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            PlyError::Parse { path, line, message } => write!(f, "{}:{}: {}", path.display(), line, message),
+            PlyError::Binary { path, offset, message } => {
+                write!(f, "{}: byte {}: {}", path.display(), offset, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PlyError {}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    LittleEndian,
+    BigEndian,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return None,
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+}
+
+struct Property {
+    name: String,
+    kind: Scalar,
+    // Type of the item count for list properties.
+    list: Option<Scalar>,
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+struct Header {
+    format: Format,
+    elements: Vec<Element>,
+    lines: usize,
+    data_start: usize,
+}
+
+fn parse_header(path: &Path, bytes: &[u8]) -> Result<Header, PlyError> {
+    let error = |line: usize, message: String| PlyError::Parse { path: path.to_path_buf(), line, message };
+
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    let mut pos = 0;
+    let mut line = 0;
+
+    loop {
+        let end = match bytes[pos..].iter().position(|&b| b == b'\n') {
+            Some(end) => pos + end,
+            None => return Err(error(line + 1, "header has no end_header".to_string())),
+        };
+        line += 1;
+        let text = std::str::from_utf8(&bytes[pos..end]).map_err(|_| error(line, "header is not text".to_string()))?;
+        pos = end + 1;
+
+        let tokens: Vec<&str> = text.split_whitespace().collect();
+        if line == 1 {
+            if tokens != ["ply"] {
+                return Err(error(line, "not a PLY file".to_string()));
+            }
+            continue;
+        }
+
+        match tokens.as_slice() {
+            [] | ["comment", ..] | ["obj_info", ..] => {}
+            ["format", name, _version] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::LittleEndian,
+                    "binary_big_endian" => Format::BigEndian,
+                    _ => return Err(error(line, format!("unknown format '{}'", name))),
+                });
+            }
+            ["element", name, count] => {
+                let count = count.parse().map_err(|_| error(line, format!("'{}' is not an element count", count)))?;
+                elements.push(Element { name: name.to_string(), count, properties: Vec::new() });
+            }
+            ["property", "list", count, item, name] => {
+                let count = Scalar::parse(count).ok_or_else(|| error(line, format!("unknown type '{}'", count)))?;
+                let kind = Scalar::parse(item).ok_or_else(|| error(line, format!("unknown type '{}'", item)))?;
+                let element = elements.last_mut().ok_or_else(|| error(line, "property before any element".to_string()))?;
+                element.properties.push(Property { name: name.to_string(), kind, list: Some(count) });
+            }
+            ["property", kind, name] => {
+                let kind = Scalar::parse(kind).ok_or_else(|| error(line, format!("unknown type '{}'", kind)))?;
+                let element = elements.last_mut().ok_or_else(|| error(line, "property before any element".to_string()))?;
+                element.properties.push(Property { name: name.to_string(), kind, list: None });
+            }
+            ["end_header"] => break,
+            _ => return Err(error(line, format!("malformed header line '{}'", text.trim()))),
+        }
+    }
+
+    let format = format.ok_or_else(|| error(line, "header has no format line".to_string()))?;
+    Ok(Header { format, elements, lines: line, data_start: pos })
+}
+
+// Reads the element data one scalar at a time, whatever the encoding.
+struct Reader<'a> {
+    path: &'a Path,
+    format: Format,
+    bytes: &'a [u8],
+    pos: usize,
+    line: usize,
+}
+
+impl Reader<'_> {
+    fn error(&self, message: String) -> PlyError {
+        match self.format {
+            Format::Ascii => PlyError::Parse { path: self.path.to_path_buf(), line: self.line, message },
+            _ => PlyError::Binary { path: self.path.to_path_buf(), offset: self.pos, message },
+        }
+    }
+
+    fn read(&mut self, kind: Scalar) -> Result<f64, PlyError> {
+        match self.format {
+            Format::Ascii => self.read_ascii(),
+            _ => self.read_binary(kind),
+        }
+    }
+
+    fn read_ascii(&mut self) -> Result<f64, PlyError> {
+        while self.pos < self.bytes.len() && self.bytes[self.pos].is_ascii_whitespace() {
+            if self.bytes[self.pos] == b'\n' {
+                self.line += 1;
+            }
+            self.pos += 1;
+        }
+        let start = self.pos;
+        while self.pos < self.bytes.len() && !self.bytes[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Err(self.error("unexpected end of file".to_string()));
+        }
+        let token = String::from_utf8_lossy(&self.bytes[start..self.pos]);
+        token.parse().map_err(|_| self.error(format!("'{}' is not a number", token)))
+    }
+
+    fn read_binary(&mut self, kind: Scalar) -> Result<f64, PlyError> {
+        let size = kind.size();
+        if self.pos + size > self.bytes.len() {
+            return Err(self.error("unexpected end of file".to_string()));
+        }
+        let mut raw = [0u8; 8];
+        raw[..size].copy_from_slice(&self.bytes[self.pos..self.pos + size]);
+        if self.format == Format::BigEndian {
+            raw[..size].reverse();
+        }
+        self.pos += size;
+
+        let b2 = [raw[0], raw[1]];
+        let b4 = [raw[0], raw[1], raw[2], raw[3]];
+        Ok(match kind {
+            Scalar::I8 => raw[0] as i8 as f64,
+            Scalar::U8 => raw[0] as f64,
+            Scalar::I16 => i16::from_le_bytes(b2) as f64,
+            Scalar::U16 => u16::from_le_bytes(b2) as f64,
+            Scalar::I32 => i32::from_le_bytes(b4) as f64,
+            Scalar::U32 => u32::from_le_bytes(b4) as f64,
+            Scalar::F32 => f32::from_le_bytes(b4) as f64,
+            Scalar::F64 => f64::from_le_bytes(raw),
+        })
+    }
+
+    fn count(&mut self, kind: Scalar) -> Result<usize, PlyError> {
+        let count = self.read(kind)?;
+        if count < 0.0 || count.fract() != 0.0 {
+            return Err(self.error(format!("'{}' is not a list length", count)));
+        }
+        Ok(count as usize)
+    }
+}
+
+// Where each vertex attribute lives among the vertex element's properties.
+fn find(properties: &[Property], names: &[&str]) -> Option<usize> {
+    properties.iter().position(|p| p.list.is_none() && names.contains(&p.name.as_str()))
+}
+
+// Without a material the mesh is Lambertian, colored by its vertex colors
+// when it has them.
+pub fn load_ply(path: &Path, material: Option<Arc<dyn Material>>) -> Result<TriangleMesh, PlyError> {
+    let bytes = fs::read(path).map_err(|source| PlyError::Io { path: path.to_path_buf(), source })?;
+    Ok(TriangleMesh::new(parse_ply(path, &bytes, material)?))
+}
+
+// `path` only names the file in errors.
+fn parse_ply(path: &Path, bytes: &[u8], material: Option<Arc<dyn Material>>) -> Result<MeshData, PlyError> {
+    let header = parse_header(path, bytes)?;
+
+    let mut reader = Reader {
+        path,
+        format: header.format,
+        bytes,
+        pos: header.data_start,
+        line: header.lines + 1,
+    };
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    let mut colors = Vec::new();
+    let mut indices = Vec::new();
+    let mut has_vertices = false;
+
+    for element in &header.elements {
+        let props = &element.properties;
+        match element.name.as_str() {
+            "vertex" => {
+                has_vertices = true;
+                let xyz = [find(props, &["x"]), find(props, &["y"]), find(props, &["z"])];
+                let [Some(x), Some(y), Some(z)] = xyz else {
+                    return Err(PlyError::Parse {
+                        path: path.to_path_buf(),
+                        line: header.lines,
+                        message: "vertex element needs x, y and z".to_string(),
+                    });
+                };
+                let normal = [find(props, &["nx"]), find(props, &["ny"]), find(props, &["nz"])];
+                let uv = [
+                    find(props, &["u", "s", "texture_u", "texture_s"]),
+                    find(props, &["v", "t", "texture_v", "texture_t"]),
+                ];
+                let rgb = [find(props, &["red", "r"]), find(props, &["green", "g"]), find(props, &["blue", "b"])];
+
+                let mut values = vec![0.0; props.len()];
+                for _ in 0..element.count {
+                    for (i, prop) in props.iter().enumerate() {
+                        values[i] = match prop.list {
+                            Some(count) => {
+                                let n = reader.count(count)?;
+                                for _ in 0..n {
+                                    reader.read(prop.kind)?;
+                                }
+                                0.0
+                            }
+                            None => reader.read(prop.kind)?,
+                        };
+                    }
+
+                    positions.push(Point3::new(values[x], values[y], values[z]));
+                    if let [Some(nx), Some(ny), Some(nz)] = normal {
+                        normals.push(Vec3::new(values[nx], values[ny], values[nz]));
+                    }
+                    if let [Some(u), Some(v)] = uv {
+                        uvs.push((values[u], values[v]));
+                    }
+                    if let [Some(r), Some(g), Some(b)] = rgb {
+                        // Integer channels are 0..255 (or 0..65535); floats are already 0..1.
+                        let channel = |i: usize| match props[i].kind {
+                            Scalar::U8 => values[i] / 255.0,
+                            Scalar::U16 => values[i] / 65535.0,
+                            _ => values[i],
+                        };
+                        colors.push(Color::new(channel(r), channel(g), channel(b)));
+                    }
+                }
+            }
+            "face" => {
+                let list = props
+                    .iter()
+                    .position(|p| p.list.is_some() && (p.name == "vertex_indices" || p.name == "vertex_index"));
+                for _ in 0..element.count {
+                    for (i, prop) in props.iter().enumerate() {
+                        let n = match prop.list {
+                            Some(count) => reader.count(count)?,
+                            None => {
+                                reader.read(prop.kind)?;
+                                continue;
+                            }
+                        };
+                        if Some(i) != list {
+                            for _ in 0..n {
+                                reader.read(prop.kind)?;
+                            }
+                            continue;
+                        }
+                        if n < 3 {
+                            return Err(reader.error(format!("face needs at least 3 vertices but has {}", n)));
+                        }
+                        let mut polygon = Vec::with_capacity(n);
+                        for _ in 0..n {
+                            let index = reader.read(prop.kind)?;
+                            if !has_vertices || index < 0.0 || index as usize >= positions.len() {
+                                return Err(reader.error(format!("vertex index {} is out of range ({} vertices)", index, positions.len())));
+                            }
+                            polygon.push(index as usize);
+                        }
+                        for pair in polygon[1..].windows(2) {
+                            indices.push([polygon[0], pair[0], pair[1]]);
+                        }
+                    }
+                }
+            }
+            _ => {
+                for _ in 0..element.count {
+                    for prop in props {
+                        let n = match prop.list {
+                            Some(count) => reader.count(count)?,
+                            None => 1,
+                        };
+                        for _ in 0..n {
+                            reader.read(prop.kind)?;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if indices.is_empty() {
+        return Err(PlyError::Parse { path: path.to_path_buf(), line: header.lines, message: "no faces".to_string() });
+    }
+
+    let material = material.unwrap_or_else(|| {
+        if colors.is_empty() {
+            Arc::new(Lambertian::new_from_color(Color::new(0.73, 0.73, 0.73)))
+        } else {
+            Arc::new(Lambertian::new_from_texture(Arc::new(VertexColorTexture)))
+        }
+    });
+
+    Ok(MeshData {
+        positions,
+        normals,
+        uvs,
+        colors,
+        indices,
+        material,
+        normal_map: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORMATS: [(&str, Format); 3] = [
+        ("ascii", Format::Ascii),
+        ("binary_little_endian", Format::LittleEndian),
+        ("binary_big_endian", Format::BigEndian),
+    ];
+
+    fn put(out: &mut Vec<u8>, format: Format, kind: Scalar, value: f64) {
+        let bytes = match kind {
+            Scalar::I8 => (value as i8).to_le_bytes().to_vec(),
+            Scalar::U8 => (value as u8).to_le_bytes().to_vec(),
+            Scalar::I16 => (value as i16).to_le_bytes().to_vec(),
+            Scalar::U16 => (value as u16).to_le_bytes().to_vec(),
+            Scalar::I32 => (value as i32).to_le_bytes().to_vec(),
+            Scalar::U32 => (value as u32).to_le_bytes().to_vec(),
+            Scalar::F32 => (value as f32).to_le_bytes().to_vec(),
+            Scalar::F64 => value.to_le_bytes().to_vec(),
+        };
+        match format {
+            Format::Ascii => out.extend(format!("{} ", value).bytes()),
+            Format::LittleEndian => out.extend(bytes),
+            Format::BigEndian => out.extend(bytes.into_iter().rev()),
+        }
+    }
+
+    // A unit square as one quad, with colors in `color_type`. The vertices
+    // carry a list the loader has no use for, the face has a list of
+    // texcoords and a flag, and an edge element follows; all are skipped.
+    fn square(name: &str, format: Format, color_type: &str) -> Vec<u8> {
+        let color_kind = Scalar::parse(color_type).unwrap();
+        let color_max = match color_kind {
+            Scalar::U8 => 255.0,
+            Scalar::U16 => 65535.0,
+            _ => 1.0,
+        };
+        let mut out = format!(
+            "ply\nformat {} 1.0\ncomment written by the tests\n\
+             element vertex 4\nproperty float x\nproperty float y\nproperty float z\n\
+             property list uchar int tags\n\
+             property {t} red\nproperty {t} green\nproperty {t} blue\n\
+             element face 1\nproperty list uchar int vertex_indices\nproperty list uchar float texcoord\nproperty int flags\n\
+             element edge 1\nproperty int vertex1\nproperty int vertex2\nend_header\n",
+            name,
+            t = color_type
+        )
+        .into_bytes();
+
+        let corners = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]];
+        for (i, corner) in corners.iter().enumerate() {
+            for &c in corner {
+                put(&mut out, format, Scalar::F32, c);
+            }
+            put(&mut out, format, Scalar::U8, i as f64);
+            for tag in 0..i {
+                put(&mut out, format, Scalar::I32, tag as f64 * 100.0);
+            }
+            for channel in [1.0, 0.2, 0.0] {
+                put(&mut out, format, color_kind, channel * color_max);
+            }
+            if format == Format::Ascii {
+                out.push(b'\n');
+            }
+        }
+
+        put(&mut out, format, Scalar::U8, 4.0);
+        for index in 0..4 {
+            put(&mut out, format, Scalar::I32, index as f64);
+        }
+        put(&mut out, format, Scalar::U8, 2.0);
+        put(&mut out, format, Scalar::F32, 0.5);
+        put(&mut out, format, Scalar::F32, 0.25);
+        put(&mut out, format, Scalar::I32, 7.0);
+        if format == Format::Ascii {
+            out.push(b'\n');
+        }
+
+        put(&mut out, format, Scalar::I32, 0.0);
+        put(&mut out, format, Scalar::I32, 1.0);
+        out
+    }
+
+    #[test]
+    fn reads_every_format() {
+        for color_type in ["uchar", "ushort", "float"] {
+            for (name, format) in FORMATS {
+                let bytes = square(name, format, color_type);
+                let mesh = parse_ply(Path::new("square.ply"), &bytes, None).unwrap();
+                let context = format!("{} with {} colors", name, color_type);
+
+                assert_eq!(
+                    mesh.positions,
+                    vec![
+                        Point3::new(0.0, 0.0, 0.0),
+                        Point3::new(1.0, 0.0, 0.0),
+                        Point3::new(1.0, 1.0, 0.0),
+                        Point3::new(0.0, 1.0, 0.0),
+                    ],
+                    "{}",
+                    context
+                );
+                assert_eq!(mesh.indices, vec![[0, 1, 2], [0, 2, 3]], "{}", context);
+                assert!(mesh.normals.is_empty() && mesh.uvs.is_empty());
+
+                // Integer channels scale to 0..1 by their type's maximum.
+                assert_eq!(mesh.colors.len(), 4);
+                for color in &mesh.colors {
+                    let expected = Color::new(1.0, 0.2, 0.0);
+                    assert!((*color - expected).length() < 1e-6, "{}: {:?}", context, color);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn truncated_data_reports_where() {
+        let bytes = square("binary_little_endian", Format::LittleEndian, "uchar");
+        let cut = &bytes[..bytes.len() - 2];
+        match parse_ply(Path::new("square.ply"), cut, None) {
+            Err(PlyError::Binary { offset, .. }) => assert_eq!(offset, bytes.len() - 4),
+            _ => panic!("expected a binary error"),
+        }
+
+        let bytes = square("ascii", Format::Ascii, "uchar");
+        let text = String::from_utf8(bytes).unwrap().replacen("0.25", "x", 1);
+        match parse_ply(Path::new("square.ply"), text.as_bytes(), None) {
+            // Header lines, four vertex lines, then the face.
+            Err(PlyError::Parse { line, message, .. }) => {
+                assert_eq!((line, message.as_str()), (24, "'x' is not a number"))
+            }
+            _ => panic!("expected a parse error"),
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    material::Material,
+    ray::Ray,
+    rtweekend::{INFINITY, Sampler},
+    vec3::{cross, dot, Point3, Vec3},
+};
+
+// A parallelogram with corner q and edges u and v. The outward normal is
+// u x v (except for Quad::xz), and (u, v) texture coordinates run along the
+// edges from q.
+pub struct Quad {
+    pub q: Point3,
+    pub u: Vec3,
+    pub v: Vec3,
+    pub material: Arc<dyn Material>,
+    normal: Vec3,
+    d: f64,
+    // n / (n . n) for n = u x v, which turns a point on the plane into its
+    // coordinates along u and v.
+    w: Vec3,
+    area: f64,
+}
+
+impl Quad {
+    pub fn new(q: Point3, u: Vec3, v: Vec3, material: Arc<dyn Material>) -> Self {
+        let n = cross(&u, &v);
+        let normal = n.unit_vector();
+        Self {
+            q,
+            u,
+            v,
+            material,
+            normal,
+            d: dot(&normal, &q),
+            w: n / dot(&n, &n),
+            area: n.length(),
+        }
+    }
+
+    // Axis-aligned rectangles at a constant coordinate k; each faces the
+    // positive direction of the remaining axis.
+    pub fn xy(x0: f64, x1: f64, y0: f64, y1: f64, k: f64, material: Arc<dyn Material>) -> Self {
+        Self::new(Point3::new(x0, y0, k), Vec3::new(x1 - x0, 0.0, 0.0), Vec3::new(0.0, y1 - y0, 0.0), material)
+    }
+
+    // u runs along x and v along z like the other two, but x cross z faces
+    // down, so the plane is turned over to face +y.
+    pub fn xz(x0: f64, x1: f64, z0: f64, z1: f64, k: f64, material: Arc<dyn Material>) -> Self {
+        let mut quad = Self::new(Point3::new(x0, k, z0), Vec3::new(x1 - x0, 0.0, 0.0), Vec3::new(0.0, 0.0, z1 - z0), material);
+        quad.normal = -quad.normal;
+        quad.d = -quad.d;
+        quad
+    }
+
+    pub fn yz(y0: f64, y1: f64, z0: f64, z1: f64, k: f64, material: Arc<dyn Material>) -> Self {
+        Self::new(Point3::new(k, y0, z0), Vec3::new(0.0, y1 - y0, 0.0), Vec3::new(0.0, 0.0, z1 - z0), material)
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, _sampler: &mut Sampler) -> Option<HitRecord> {
+        let denom = dot(&self.normal, &r.direction);
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = (self.d - dot(&self.normal, &r.origin)) / denom;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        let p = r.at(t);
+        let planar = p - self.q;
+        let alpha = dot(&self.w, &cross(&planar, &self.v));
+        let beta = dot(&self.w, &cross(&self.u, &planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        let mut rec = HitRecord {
+            t,
+            p,
+            u: alpha,
+            v: beta,
+            normal: Vec3::ZERO,
+            front_face: false,
+            color: None,
+            material: Arc::clone(&self.material),
+        };
+        rec.set_face_normal(r, self.normal);
+
+        Some(rec)
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        let corners = [self.q, self.q + self.u, self.q + self.v, self.q + self.u + self.v];
+        let mut min = corners[0];
+        let mut max = corners[0];
+        for corner in &corners[1..] {
+            for a in 0..3 {
+                min[a] = min[a].min(corner[a]);
+                max[a] = max[a].max(corner[a]);
+            }
+        }
+
+        // A quad in an axis plane has no thickness, which the slab test
+        // needs, so pad that axis a little.
+        for a in 0..3 {
+            if max[a] - min[a] < 0.0002 {
+                min[a] -= 0.0001;
+                max[a] += 0.0001;
+            }
+        }
+        Some(Aabb::new(min, max))
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, sampler: &mut Sampler) -> f64 {
+        let ray = Ray::new(*origin, *direction);
+        if let Some(rec) = self.hit(&ray, 0.001, INFINITY, sampler) {
+            let distance_squared = rec.t * rec.t * direction.length_squared();
+            let cosine = dot(direction, &rec.normal).abs() / direction.length();
+            return distance_squared / (cosine * self.area);
+        }
+
+        0.0
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut Sampler) -> Vec3 {
+        let random_point = self.q + sampler.random_double() * self.u + sampler.random_double() * self.v;
+        random_point - *origin
+    }
+
+    fn can_sample(&self) -> bool {
+        true
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        material::Lambertian,
+        rtweekend::Sampler,
+        vec3::{random_unit_vector, Color},
+    };
+    use std::f64::consts::PI;
+
+    fn gray() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new_from_color(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    #[test]
+    fn axis_rectangles_face_the_positive_axis() {
+        let mut sampler = Sampler::new(0);
+        let rects = [
+            (Quad::xy(0.0, 2.0, 0.0, 4.0, 1.0, gray()), Vec3::new(0.0, 0.0, 1.0), Point3::new(1.0, 3.0, 1.0)),
+            (Quad::xz(0.0, 2.0, 0.0, 4.0, 1.0, gray()), Vec3::new(0.0, 1.0, 0.0), Point3::new(1.0, 1.0, 3.0)),
+            (Quad::yz(0.0, 2.0, 0.0, 4.0, 1.0, gray()), Vec3::new(1.0, 0.0, 0.0), Point3::new(1.0, 1.0, 3.0)),
+        ];
+        for (quad, axis, target) in rects {
+            // From the positive side the ray meets the front face.
+            let rec = quad.hit(&Ray::new(target + 5.0 * axis, -axis), 0.001, INFINITY, &mut sampler).unwrap();
+            assert!(rec.front_face);
+            assert_eq!(rec.normal, axis);
+            assert!((rec.t - 5.0).abs() < 1e-12);
+
+            // From behind, the normal is flipped to face the ray.
+            let rec = quad.hit(&Ray::new(target - 5.0 * axis, axis), 0.001, INFINITY, &mut sampler).unwrap();
+            assert!(!rec.front_face);
+            assert_eq!(rec.normal, -axis);
+        }
+    }
+
+    #[test]
+    fn uvs_run_along_the_edges() {
+        let mut sampler = Sampler::new(0);
+        // The point 1 along the first axis and 3 along the second of a 2 by 4 rectangle.
+        let down = |p: Point3, axis: Vec3| Ray::new(p + axis, -axis);
+        let xy = Quad::xy(0.0, 2.0, 0.0, 4.0, 0.0, gray()).hit(&down(Point3::new(1.0, 3.0, 0.0), Vec3::new(0.0, 0.0, 1.0)), 0.001, INFINITY, &mut sampler).unwrap();
+        assert_eq!((xy.u, xy.v), (0.5, 0.75));
+        let xz = Quad::xz(0.0, 2.0, 0.0, 4.0, 0.0, gray()).hit(&down(Point3::new(1.0, 0.0, 3.0), Vec3::new(0.0, 1.0, 0.0)), 0.001, INFINITY, &mut sampler).unwrap();
+        assert_eq!((xz.u, xz.v), (0.5, 0.75));
+        assert!(xz.front_face);
+        let yz = Quad::yz(0.0, 2.0, 0.0, 4.0, 0.0, gray()).hit(&down(Point3::new(0.0, 1.0, 3.0), Vec3::new(1.0, 0.0, 0.0)), 0.001, INFINITY, &mut sampler).unwrap();
+        assert_eq!((yz.u, yz.v), (0.5, 0.75));
+
+        // A slanted quad uses the same edge coordinates.
+        let quad = Quad::new(Point3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 2.0, 2.0), Vec3::new(-1.0, 0.0, 0.0), gray());
+        let p = quad.q + 0.25 * quad.u + 0.6 * quad.v;
+        let rec = quad.hit(&Ray::new(p + Vec3::new(0.3, 2.0, -2.0), Vec3::new(-0.3, -2.0, 2.0)), 0.001, INFINITY, &mut sampler).unwrap();
+        assert!((rec.u - 0.25).abs() < 1e-12 && (rec.v - 0.6).abs() < 1e-12);
+    }
+
+    #[test]
+    fn wall_light_pdf_agrees_with_its_samples() {
+        let mut sampler = Sampler::new(0);
+        // A 2 by 1 light on the x = 5 wall, seen from 2 in front of its center.
+        let light = Quad::yz(1.0, 2.0, -1.0, 1.0, 5.0, gray());
+        let origin = Point3::new(3.0, 1.5, 0.0);
+        let (a, b, d): (f64, f64, f64) = (1.0, 0.5, 2.0);
+        let solid_angle = 4.0 * (a * b / ((a * a + d * d) * (b * b + d * d)).sqrt()).asin();
+
+        // Sampled directions hit the light, and 1 / pdf averages to its solid angle.
+        let n = 100_000;
+        let mut total = 0.0;
+        for _ in 0..n {
+            let direction = light.random(&origin, &mut sampler);
+            assert!(light.hit(&Ray::new(origin, direction), 0.001, INFINITY, &mut sampler).is_some());
+            total += 1.0 / light.pdf_value(&origin, &direction, &mut sampler);
+        }
+        let estimate = total / n as f64;
+        assert!((estimate - solid_angle).abs() < 0.01 * solid_angle, "{} vs {}", estimate, solid_angle);
+
+        // Over all directions the pdf integrates to one.
+        let total: f64 = (0..n).map(|_| light.pdf_value(&origin, &random_unit_vector(&mut sampler), &mut sampler)).sum();
+        let integral = total / n as f64 * 4.0 * PI;
+        assert!((integral - 1.0).abs() < 0.05, "{}", integral);
+
+        // The pdf doesn't depend on the length of the direction.
+        let direction = Vec3::new(2.0, 0.2, 0.3);
+        assert!((light.pdf_value(&origin, &direction, &mut sampler) - light.pdf_value(&origin, &(3.0 * direction), &mut sampler)).abs() < 1e-9);
+    }
+}
@@ -5,22 +5,20 @@ pub struct Ray {
     pub origin: Point3,
     pub direction: Vec3,
     pub time: f64,
+    // In nanometres, once the path has been split by dispersion; the
+    // integrator sets it on every ray it passes to a material.
+    pub wavelength: Option<f64>,
 }
 
 impl Ray {
     pub fn new(origin: Point3, direction: Vec3) -> Self {
-        Self { origin, direction, time: 0.0}
+        Self { origin, direction, time: 0.0, wavelength: None }
     }
 
     pub fn with_time(origin: Point3, direction: Vec3, time: f64) -> Self {
-        Self { origin, direction, time}
+        Self { origin, direction, time, wavelength: None }
     }
 
-    pub fn origin(&self) -> Point3 {
-        self.origin
-    }
-
-
     pub fn direction(&self) -> Vec3 {
         self.direction
     }
@@ -41,6 +39,7 @@ impl Default for Ray {
             origin: Point3::default(),
             direction: Vec3::default(),
             time: 0.0,
+            wavelength: None,
         }
     }
 }
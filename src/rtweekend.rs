@@ -1,4 +1,4 @@
-use rand::prelude::*;
+use rand::{prelude::*, rngs::SmallRng};
 
 use crate::vec3::Point3;
 
@@ -9,14 +9,45 @@ pub fn degrees_to_radians(degrees: f64) -> f64 {
     degrees * PI / 180.0
 }
 
-pub fn random_double() -> f64 {
-    let mut rng = rand::rng();
-    rng.random::<f64>()
+// The source of every random number. Whatever draws randoms takes one as
+// an argument: the camera, materials, pdfs, hittables (ConstantMedium::hit,
+// light sampling) and median BVH builds. Rendering makes a fresh one for
+// each sample of each pixel, so an image depends only on the seed, never on
+// which thread rendered what; scene construction draws from one seeded
+// directly with the render seed.
+pub struct Sampler {
+    rng: SmallRng,
 }
 
-pub fn random_double_range(min: f64, max:f64) -> f64 {
-    let mut rng = rand::rng();
-    rng.gen_range(min..max)
+impl Sampler {
+    pub fn new(seed: u64) -> Self {
+        Self { rng: SmallRng::seed_from_u64(seed) }
+    }
+
+    // The stream for one sample of one pixel.
+    pub fn for_sample(seed: u64, pixel: u64, sample: u64) -> Self {
+        Self::new(mix(mix(seed ^ mix(pixel)) ^ sample))
+    }
+
+    pub fn random_double(&mut self) -> f64 {
+        self.rng.random::<f64>()
+    }
+
+    pub fn random_double_range(&mut self, min: f64, max: f64) -> f64 {
+        self.rng.random_range(min..max)
+    }
+
+    pub fn random_int(&mut self, min: i32, max: i32) -> i32 {
+        (self.random_double_range(min as f64, (max + 1) as f64)) as i32
+    }
+}
+
+// SplitMix64 finalizer.
+fn mix(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
 }
 
 pub fn clamp(x: f64, min: f64, max: f64) -> f64 {
@@ -25,10 +56,6 @@ pub fn clamp(x: f64, min: f64, max: f64) -> f64 {
     x
 }
 
-pub fn random_int(min: i32, max: i32) -> i32 {
-    (random_double_range(min as f64, (max + 1) as f64)) as i32
-}
-
 pub fn get_sphere_uv(p: &Point3) -> (f64, f64) {
     // p: a given point on the sphere of radius one, centered at the origin.
     // u: returned value [0,1] of angle around the Y axis from X=-1.
@@ -0,0 +1,150 @@
+use std::sync::Arc;
+
+use crate::{bvh::{LinearBvh, SplitMethod}, camera::Camera, color::DisplaySettings, hittable::Hittable, hittable_list::HittableList, rtweekend::Sampler, vec3::{Color, Point3, Vec3}};
+
+pub struct CameraSettings {
+    pub lookfrom: Point3,
+    pub lookat: Point3,
+    pub vup: Vec3,
+    pub vfov: f64,
+    pub aperture: f64,
+    pub focus_dist: f64,
+    pub time0: f64, // shutter open
+    pub time1: f64, // shutter close
+}
+
+pub struct RenderSettings {
+    pub aspect_ratio: f64,
+    pub image_width: i32,
+    pub samples_per_pixel: u32,
+    pub max_depth: u32,   // hard cap on path length
+    pub min_bounces: u32, // bounces before Russian roulette may end a path
+    pub display: DisplaySettings,
+}
+
+// What the integrator samples toward besides the material's own pdf.
+// Emitters get a shadow ray at every diffuse vertex; the sampled objects
+// (glass for caustics, say) are only mixed into the scattering pdf.
+pub struct Lights {
+    pub emitters: Option<Arc<dyn Hittable>>,
+    pub sampled: Option<Arc<dyn Hittable>>,
+}
+
+pub struct Scene {
+    pub world: Arc<LinearBvh>,
+    pub lights: Lights,
+    pub background: Color,
+    pub camera: CameraSettings,
+    pub render: RenderSettings,
+}
+
+// Collects the objects of a scene and, alongside them, the lists used for
+// importance sampling: every emissive object is added automatically and
+// anything else (glass for caustics, say) can be tagged with add_sampled.
+pub struct SceneBuilder {
+    objects: Vec<Arc<dyn Hittable>>,
+    emitters: Vec<Arc<dyn Hittable>>,
+    sampled: Vec<Arc<dyn Hittable>>,
+}
+
+impl SceneBuilder {
+    pub fn new() -> Self {
+        Self {
+            objects: Vec::new(),
+            emitters: Vec::new(),
+            sampled: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, object: Arc<dyn Hittable>) {
+        if object.is_emissive() {
+            self.emitters.push(Arc::clone(&object));
+        }
+        self.objects.push(object);
+    }
+
+    pub fn add_sampled(&mut self, object: Arc<dyn Hittable>) {
+        if !object.is_emissive() {
+            self.sampled.push(Arc::clone(&object));
+        }
+        self.add(object);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    pub fn build(mut self, time0: f64, time1: f64) -> (Arc<LinearBvh>, Lights) {
+        let world = Arc::new(LinearBvh::new(&mut self.objects, time0, time1));
+        let lights = Lights {
+            emitters: list_or_none(self.emitters),
+            sampled: list_or_none(self.sampled),
+        };
+        (world, lights)
+    }
+}
+
+fn list_or_none(objects: Vec<Arc<dyn Hittable>>) -> Option<Arc<dyn Hittable>> {
+    if objects.is_empty() {
+        None
+    } else {
+        Some(Arc::new(HittableList { objects }))
+    }
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            lookfrom: Point3::new(13.0, 2.0, 3.0),
+            lookat: Point3::new(0.0, 0.0, 0.0),
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            vfov: 40.0,
+            aperture: 0.0,
+            focus_dist: 10.0,
+            time0: 0.0,
+            time1: 1.0,
+        }
+    }
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            aspect_ratio: 16.0 / 9.0,
+            image_width: 400,
+            samples_per_pixel: 100,
+            max_depth: 50,
+            min_bounces: 3,
+            display: DisplaySettings::default(),
+        }
+    }
+}
+
+impl RenderSettings {
+    pub fn image_height(&self) -> i32 {
+        (self.image_width as f64 / self.aspect_ratio) as i32
+    }
+}
+
+impl Scene {
+    pub fn camera(&self) -> Camera {
+        Camera::new(
+            self.camera.lookfrom,
+            self.camera.lookat,
+            self.camera.vup,
+            self.camera.vfov,
+            self.render.aspect_ratio,
+            self.camera.aperture,
+            self.camera.focus_dist,
+            self.camera.time0,
+            self.camera.time1,
+        )
+    }
+
+    // Rebuilds the top level of the world with another split method; the
+    // trees inside meshes and prototypes keep the SAH.
+    pub fn split_world(&mut self, method: SplitMethod, sampler: &mut Sampler) {
+        let mut objects = self.world.objects().to_vec();
+        self.world = Arc::new(LinearBvh::with_split(&mut objects, self.camera.time0, self.camera.time1, method, sampler));
+    }
+}
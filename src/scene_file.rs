@@ -0,0 +1,1349 @@
+use std::{collections::{HashMap, HashSet}, fmt, fs, io, path::{Path, PathBuf}, sync::Arc};
+
+use serde::Deserialize;
+use toml::{Spanned, Table, Value};
+
+use crate::{
+    animation::{AnimatedTransform, Keyframe},
+    capsule::Capsule,
+    color::{DisplaySettings, ToneMap},
+    cone::Cone,
+    constant_medium::ConstantMedium,
+    bvh::LinearBvh,
+    cuboid::Cuboid,
+    cylinder::Cylinder,
+    disk::Disk,
+    hittable::{FlipFace, Hittable, Transform},
+    instance::{prototype, Instance},
+    matrix::{Mat4, Quat},
+    material::{Dielectric, DiffuseLight, Dispersion, Principled, Isotropic, Lambertian, Material, Metal},
+    moving_sphere::MovingSphere,
+    obj::load_obj,
+    paraboloid::Paraboloid,
+    ply::load_ply,
+    quad::Quad,
+    rtweekend::Sampler,
+    scene::{CameraSettings, RenderSettings, Scene, SceneBuilder},
+    sphere::Sphere,
+    texture::{CheckerTexture, ImageTexture, NoiseTexture, SolidColor, Texture, VertexColorTexture},
+    torus::Torus,
+    triangle::{MeshData, Triangle, TriangleMesh},
+    vec3::{cross, Color, Vec3},
+};
+
+// A scene file is TOML. Textures and materials are named tables that objects
+// refer to by name, so one material can be shared by any number of objects:
+//
+//   background = [0.0, 0.0, 0.0]
+//
+//   [camera]
+//   lookfrom = [278.0, 278.0, -800.0]
+//   lookat = [278.0, 278.0, 0.0]
+//
+//   [render]
+//   aspect_ratio = 1.0
+//   image_width = 600
+//   max_depth = 50        # hard cap on path length
+//   min_bounces = 3       # bounces before Russian roulette
+//   tone_map = "aces"     # clamp, reinhard, reinhard-extended, aces or agx
+//   exposure = 0.5        # in stops
+//
+//   [materials.white]
+//   type = "lambertian"
+//   albedo = [0.73, 0.73, 0.73]
+//
+//   [[objects]]
+//   type = "cuboid"
+//   p0 = [0.0, 0.0, 0.0]
+//   p1 = [165.0, 330.0, 165.0]
+//   material = "white"
+//   transform = [{ rotate_y = 15.0 }, { translate = [265.0, 0.0, 295.0] }]
+//
+// Transform steps apply in order: translate, rotate_x/y/z (degrees), rotate
+// = { axis, angle }, scale = [x, y, z], matrix (four rows) and look_at =
+// { from, to, up }, which points the object's +Z axis from `from` at `to`.
+//
+// `animation` moves an object over the shutter interval, on top of its
+// transform. Each key gives the pose at one time; translate and scale are
+// interpolated linearly and rotate is slerped the shorter way round, so
+// keys more than half a turn apart need one in between:
+//
+//   animation = [
+//     { time = 0.0 },
+//     { time = 1.0, translate = [0.0, 50.0, 0.0], rotate = { axis = [0.0, 1.0, 0.0], angle = 45.0 } },
+//   ]
+//
+// `type = "obj"` loads a Wavefront model with its MTL materials; `path` is
+// relative to the scene file and `material` covers faces without usemtl.
+// `type = "ply"` loads a PLY mesh the same way; without a material its
+// vertex colors are used (also available as a `vertex_color` texture).
+//
+// A prototype is a group of objects built once and placed any number of
+// times by `type = "instance"` objects, each with its own transform and an
+// optional material that replaces the prototype's:
+//
+//   [prototypes.chair]
+//   objects = [{ type = "obj", path = "chair.obj" }]
+//
+//   [[objects]]
+//   type = "instance"
+//   prototype = "chair"
+//   material = "red"
+//   transform = [{ rotate_y = 30.0 }, { translate = [1.0, 0.0, 2.0] }]
+//
+// Emissive objects are importance sampled automatically; `sample = true` on
+// an object adds it to the sampled list as well (glass spheres, for caustics).
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io { path: PathBuf, source: io::Error },
+    Parse { path: PathBuf, source: toml::de::Error },
+    Invalid { path: PathBuf, line: usize, key: String, message: String },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            SceneError::Parse { path, source } => write!(f, "{}: {}", path.display(), source),
+            SceneError::Invalid { path, line, key, message } => {
+                write!(f, "{}:{}: {}: {}", path.display(), line, key, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneFile {
+    #[serde(default)]
+    background: [f64; 3],
+    #[serde(default)]
+    camera: CameraDesc,
+    #[serde(default)]
+    render: RenderDesc,
+    #[serde(default)]
+    textures: HashMap<String, Spanned<TextureDesc>>,
+    #[serde(default)]
+    materials: HashMap<String, Spanned<MaterialDesc>>,
+    #[serde(default)]
+    prototypes: HashMap<String, Spanned<PrototypeDesc>>,
+    #[serde(default)]
+    objects: Vec<Spanned<Table>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PrototypeDesc {
+    objects: Vec<Table>,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct CameraDesc {
+    lookfrom: [f64; 3],
+    lookat: [f64; 3],
+    vup: [f64; 3],
+    vfov: f64,
+    aperture: f64,
+    focus_dist: f64,
+    time0: f64,
+    time1: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RenderDesc {
+    aspect_ratio: f64,
+    image_width: i32,
+    samples_per_pixel: u32,
+    max_depth: u32,
+    min_bounces: u32,
+    tone_map: ToneMap,
+    exposure: f64,
+    white_point: f64,
+}
+
+// A texture slot is either an inline color, a gray value or the name of a
+// texture.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TextureRef {
+    Color([f64; 3]),
+    Value(f64),
+    Name(String),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum TextureDesc {
+    Solid { color: [f64; 3] },
+    Checker { even: TextureRef, odd: TextureRef },
+    Noise { scale: f64 },
+    Image { path: String },
+    // Interpolated mesh vertex colors, see ply meshes.
+    VertexColor,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialDesc {
+    Lambertian { albedo: TextureRef },
+    // Colored by exactly one of albedo, a preset (gold, copper, aluminium or
+    // silver) or a complex index of refraction eta and k. roughness_v and a
+    // tangent make it anisotropic; fuzz is roughness's old name.
+    Metal {
+        albedo: Option<[f64; 3]>,
+        preset: Option<String>,
+        eta: Option<[f64; 3]>,
+        k: Option<[f64; 3]>,
+        #[serde(default, alias = "fuzz")]
+        roughness: f64,
+        roughness_v: Option<f64>,
+        tangent: Option<[f64; 3]>,
+    },
+    // Frosted above zero roughness. The inside absorbs `absorption` per unit
+    // length, or enough to leave `color` after `distance`. The index is
+    // either a constant `ir` or dispersive: a glass preset (bk7, sf11,
+    // fused_silica, diamond), cauchy = [a, b] or sellmeier = { b, c }, with
+    // coefficients in micrometres.
+    Dielectric {
+        ir: Option<f64>,
+        dispersion: Option<String>,
+        cauchy: Option<[f64; 2]>,
+        sellmeier: Option<SellmeierDesc>,
+        #[serde(default)]
+        roughness: f64,
+        absorption: Option<[f64; 3]>,
+        color: Option<[f64; 3]>,
+        distance: Option<f64>,
+    },
+    // Disney's principled BSDF. Parameters left out keep their defaults,
+    // and any of them can be a texture.
+    Principled {
+        base_color: TextureRef,
+        metallic: Option<TextureRef>,
+        roughness: Option<TextureRef>,
+        specular: Option<TextureRef>,
+        specular_tint: Option<TextureRef>,
+        sheen: Option<TextureRef>,
+        clearcoat: Option<TextureRef>,
+        clearcoat_gloss: Option<TextureRef>,
+        transmission: Option<TextureRef>,
+        ior: Option<TextureRef>,
+    },
+    DiffuseLight { emit: TextureRef },
+    Isotropic { albedo: TextureRef },
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SellmeierDesc {
+    b: [f64; 3],
+    c: [f64; 3],
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ShapeDesc {
+    Sphere { center: [f64; 3], radius: f64, material: String },
+    MovingSphere {
+        center0: [f64; 3],
+        center1: [f64; 3],
+        #[serde(default)]
+        time0: f64,
+        #[serde(default = "one")]
+        time1: f64,
+        radius: f64,
+        material: String,
+    },
+    // Corner q and edges u and v; the front face is on the u x v side.
+    Quad { q: [f64; 3], u: [f64; 3], v: [f64; 3], material: String },
+    // Axis-aligned shorthands for quads, facing the positive axis.
+    XyRect { x0: f64, x1: f64, y0: f64, y1: f64, k: f64, material: String },
+    XzRect { x0: f64, x1: f64, z0: f64, z1: f64, k: f64, material: String },
+    YzRect { y0: f64, y1: f64, z0: f64, z1: f64, k: f64, material: String },
+    Cuboid { p0: [f64; 3], p1: [f64; 3], material: String },
+    // Upright from y0 to y1, or around the segment from p0 to p1; an open
+    // cylinder has no caps.
+    Cylinder {
+        y0: Option<f64>,
+        y1: Option<f64>,
+        p0: Option<[f64; 3]>,
+        p1: Option<[f64; 3]>,
+        radius: f64,
+        #[serde(default)]
+        open: bool,
+        material: String,
+    },
+    // An annulus when inner_radius is above zero.
+    Disk {
+        center: [f64; 3],
+        normal: [f64; 3],
+        radius: f64,
+        #[serde(default)]
+        inner_radius: f64,
+        material: String,
+    },
+    // Either radius may be zero for a point.
+    Cone {
+        p0: [f64; 3],
+        radius0: f64,
+        p1: [f64; 3],
+        radius1: f64,
+        #[serde(default)]
+        open: bool,
+        material: String,
+    },
+    Torus {
+        center: [f64; 3],
+        #[serde(default = "up")]
+        axis: [f64; 3],
+        major_radius: f64,
+        minor_radius: f64,
+        material: String,
+    },
+    Capsule { p0: [f64; 3], p1: [f64; 3], radius: f64, material: String },
+    // Vertex at p0, opening toward a rim of `radius` at p1.
+    Paraboloid { p0: [f64; 3], p1: [f64; 3], radius: f64, material: String },
+    Triangle {
+        vertices: [[f64; 3]; 3],
+        normals: Option<[[f64; 3]; 3]>,
+        uvs: Option<[[f64; 2]; 3]>,
+        material: String,
+    },
+    Mesh {
+        positions: Vec<[f64; 3]>,
+        indices: Vec<[usize; 3]>,
+        #[serde(default)]
+        normals: Vec<[f64; 3]>,
+        #[serde(default)]
+        uvs: Vec<[f64; 2]>,
+        #[serde(default)]
+        colors: Vec<[f64; 3]>,
+        material: String,
+    },
+    ConstantMedium { boundary: Table, density: f64, albedo: TextureRef },
+    // Path is relative to the scene file; the material is used for faces
+    // without a usemtl.
+    Obj { path: String, material: Option<String> },
+    // Without a material, vertex colors (if any) go on a Lambertian.
+    Ply { path: String, material: Option<String> },
+    // The transform places the instance rather than wrapping it.
+    Instance { prototype: String, material: Option<String> },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum TransformDesc {
+    Translate([f64; 3]),
+    RotateX(f64),
+    RotateY(f64),
+    RotateZ(f64),
+    Rotate { axis: [f64; 3], angle: f64 },
+    Scale([f64; 3]),
+    // Rows of an affine matrix.
+    Matrix([[f64; 4]; 4]),
+    LookAt {
+        from: [f64; 3],
+        to: [f64; 3],
+        #[serde(default = "up")]
+        up: [f64; 3],
+    },
+}
+
+impl TransformDesc {
+    fn matrix(&self) -> Mat4 {
+        match *self {
+            TransformDesc::Translate(offset) => Mat4::translation(vec3(offset)),
+            TransformDesc::RotateX(angle) => Mat4::rotation(Vec3::new(1.0, 0.0, 0.0), angle),
+            TransformDesc::RotateY(angle) => Mat4::rotation(Vec3::new(0.0, 1.0, 0.0), angle),
+            TransformDesc::RotateZ(angle) => Mat4::rotation(Vec3::new(0.0, 0.0, 1.0), angle),
+            TransformDesc::Rotate { axis, angle } => Mat4::rotation(vec3(axis), angle),
+            TransformDesc::Scale(factors) => Mat4::scaling(vec3(factors)),
+            TransformDesc::Matrix(m) => Mat4 { m },
+            TransformDesc::LookAt { from, to, up } => Mat4::look_at(vec3(from), vec3(to), vec3(up)),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyframeDesc {
+    time: f64,
+    #[serde(default)]
+    translate: [f64; 3],
+    rotate: Option<RotateDesc>,
+    #[serde(default = "ones")]
+    scale: [f64; 3],
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RotateDesc {
+    axis: [f64; 3],
+    angle: f64,
+}
+
+impl KeyframeDesc {
+    fn keyframe(&self) -> Keyframe {
+        let rotate = match &self.rotate {
+            Some(r) => Quat::from_axis_angle(vec3(r.axis), r.angle),
+            None => Quat::IDENTITY,
+        };
+        Keyframe::new(self.time, vec3(self.translate), rotate, vec3(self.scale))
+    }
+}
+
+fn ones() -> [f64; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+fn up() -> [f64; 3] {
+    [0.0, 1.0, 0.0]
+}
+
+fn one() -> f64 {
+    1.0
+}
+
+fn vec3(a: [f64; 3]) -> Vec3 {
+    Vec3::new(a[0], a[1], a[2])
+}
+
+impl Default for CameraDesc {
+    fn default() -> Self {
+        let c = CameraSettings::default();
+        Self {
+            lookfrom: [c.lookfrom.x, c.lookfrom.y, c.lookfrom.z],
+            lookat: [c.lookat.x, c.lookat.y, c.lookat.z],
+            vup: [c.vup.x, c.vup.y, c.vup.z],
+            vfov: c.vfov,
+            aperture: c.aperture,
+            focus_dist: c.focus_dist,
+            time0: c.time0,
+            time1: c.time1,
+        }
+    }
+}
+
+impl Default for RenderDesc {
+    fn default() -> Self {
+        let r = RenderSettings::default();
+        Self {
+            aspect_ratio: r.aspect_ratio,
+            image_width: r.image_width,
+            samples_per_pixel: r.samples_per_pixel,
+            max_depth: r.max_depth,
+            min_bounces: r.min_bounces,
+            tone_map: r.display.tone_map,
+            exposure: r.display.exposure,
+            white_point: r.display.white_point,
+        }
+    }
+}
+
+impl From<CameraDesc> for CameraSettings {
+    fn from(c: CameraDesc) -> Self {
+        Self {
+            lookfrom: vec3(c.lookfrom),
+            lookat: vec3(c.lookat),
+            vup: vec3(c.vup),
+            vfov: c.vfov,
+            aperture: c.aperture,
+            focus_dist: c.focus_dist,
+            time0: c.time0,
+            time1: c.time1,
+        }
+    }
+}
+
+impl From<RenderDesc> for RenderSettings {
+    fn from(r: RenderDesc) -> Self {
+        Self {
+            aspect_ratio: r.aspect_ratio,
+            image_width: r.image_width,
+            samples_per_pixel: r.samples_per_pixel,
+            max_depth: r.max_depth,
+            min_bounces: r.min_bounces,
+            display: DisplaySettings {
+                tone_map: r.tone_map,
+                exposure: r.exposure,
+                white_point: r.white_point,
+            },
+        }
+    }
+}
+
+struct Loader<'a> {
+    path: &'a Path,
+    source: &'a str,
+    texture_descs: HashMap<String, Spanned<TextureDesc>>,
+    textures: HashMap<String, Arc<dyn Texture>>,
+    materials: HashMap<String, Arc<dyn Material>>,
+    prototypes: HashMap<String, Prototype>,
+    // The camera's shutter interval, which moving objects are bounded over.
+    shutter: (f64, f64),
+    sampler: &'a mut Sampler,
+}
+
+struct Prototype {
+    // The objects as loaded, and the bottom-level BVH over them that
+    // instances share.
+    parts: Vec<Arc<dyn Hittable>>,
+    shared: Arc<dyn Hittable>,
+}
+
+pub fn load_scene(path: &Path, sampler: &mut Sampler) -> Result<Scene, SceneError> {
+    let source = fs::read_to_string(path).map_err(|source| SceneError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    parse_scene(path, &source, sampler)
+}
+
+// `path` names the file in errors and anchors the relative paths of models.
+fn parse_scene(path: &Path, source: &str, sampler: &mut Sampler) -> Result<Scene, SceneError> {
+    let file: SceneFile = toml::from_str(source).map_err(|source| SceneError::Parse {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    let mut loader = Loader {
+        path,
+        source,
+        texture_descs: file.textures,
+        textures: HashMap::new(),
+        materials: HashMap::new(),
+        prototypes: HashMap::new(),
+        shutter: (file.camera.time0, file.camera.time1),
+        sampler,
+    };
+
+    let mut names: Vec<String> = loader.texture_descs.keys().cloned().collect();
+    names.sort();
+    for name in names {
+        loader.texture(&name, &mut HashSet::new())?;
+    }
+
+    let mut materials: Vec<(String, Spanned<MaterialDesc>)> = file.materials.into_iter().collect();
+    materials.sort_by_key(|(_, desc)| desc.span().start);
+    for (name, desc) in materials {
+        let line = loader.line(desc.span().start);
+        let key = format!("materials.{}", name);
+        let material = loader.material(desc.into_inner(), &key, line)?;
+        loader.materials.insert(name, material);
+    }
+
+    // In file order, so a prototype can place instances of earlier ones.
+    let mut prototypes: Vec<(String, Spanned<PrototypeDesc>)> = file.prototypes.into_iter().collect();
+    prototypes.sort_by_key(|(_, desc)| desc.span().start);
+    for (name, desc) in prototypes {
+        let line = loader.line(desc.span().start);
+        let key = format!("prototypes.{}", name);
+        let mut parts = Vec::new();
+        for (i, table) in desc.into_inner().objects.into_iter().enumerate() {
+            parts.extend(loader.objects(table, &format!("{}.objects[{}]", key, i), line)?);
+        }
+        if parts.is_empty() {
+            return Err(loader.invalid(line, &key, "prototype has no objects".to_string()));
+        }
+        let shared = prototype(parts.clone(), loader.shutter.0, loader.shutter.1);
+        loader.prototypes.insert(name, Prototype { parts, shared });
+    }
+
+    let mut objects = SceneBuilder::new();
+    for (i, table) in file.objects.into_iter().enumerate() {
+        let line = loader.line(table.span().start);
+        let key = format!("objects[{}]", i);
+        let mut table = table.into_inner();
+        let sample: bool = loader.take(&mut table, "sample", &key, line)?.unwrap_or(false);
+        for object in loader.objects(table, &key, line)? {
+            if sample {
+                objects.add_sampled(object);
+            } else {
+                objects.add(object);
+            }
+        }
+    }
+
+    if objects.is_empty() {
+        return Err(loader.invalid(1, "objects", "scene has no objects".to_string()));
+    }
+
+    let (world, lights) = objects.build(file.camera.time0, file.camera.time1);
+
+    Ok(Scene {
+        world,
+        lights,
+        background: vec3(file.background),
+        camera: file.camera.into(),
+        render: file.render.into(),
+    })
+}
+
+impl Loader<'_> {
+    fn line(&self, offset: usize) -> usize {
+        self.source[..offset.min(self.source.len())].matches('\n').count() + 1
+    }
+
+    fn invalid(&self, line: usize, key: &str, message: String) -> SceneError {
+        SceneError::Invalid {
+            path: self.path.to_path_buf(),
+            line,
+            key: key.to_string(),
+            message,
+        }
+    }
+
+    fn texture(&mut self, name: &str, resolving: &mut HashSet<String>) -> Result<Arc<dyn Texture>, SceneError> {
+        if let Some(texture) = self.textures.get(name) {
+            return Ok(Arc::clone(texture));
+        }
+
+        let key = format!("textures.{}", name);
+        let (start, desc) = match self.texture_descs.remove(name) {
+            Some(desc) => (desc.span().start, desc.into_inner()),
+            None => return Err(self.invalid(1, &key, format!("unknown texture '{}'", name))),
+        };
+        let line = self.line(start);
+        resolving.insert(name.to_string());
+
+        let texture: Arc<dyn Texture> = match desc {
+            TextureDesc::Solid { color } => Arc::new(SolidColor::new(vec3(color))),
+            TextureDesc::Checker { even, odd } => {
+                let even = self.texture_ref(even, &format!("{}.even", key), line, resolving)?;
+                let odd = self.texture_ref(odd, &format!("{}.odd", key), line, resolving)?;
+                Arc::new(CheckerTexture::new(even, odd))
+            }
+            TextureDesc::Noise { scale } => Arc::new(NoiseTexture::new(scale, self.sampler)),
+            TextureDesc::VertexColor => Arc::new(VertexColorTexture),
+            TextureDesc::Image { path } => {
+                let full = self.path.parent().unwrap_or(Path::new("")).join(&path);
+                if !full.is_file() {
+                    return Err(self.invalid(line, &format!("{}.path", key), format!("image '{}' not found", full.display())));
+                }
+                Arc::new(ImageTexture::new(&full.to_string_lossy()))
+            }
+        };
+
+        resolving.remove(name);
+        self.textures.insert(name.to_string(), Arc::clone(&texture));
+        Ok(texture)
+    }
+
+    fn texture_ref(
+        &mut self,
+        texture: TextureRef,
+        key: &str,
+        line: usize,
+        resolving: &mut HashSet<String>,
+    ) -> Result<Arc<dyn Texture>, SceneError> {
+        match texture {
+            TextureRef::Color(color) => Ok(Arc::new(SolidColor::new(vec3(color)))),
+            TextureRef::Value(value) => Ok(Arc::new(SolidColor::new(Color::new(value, value, value)))),
+            TextureRef::Name(name) => {
+                if resolving.contains(&name) {
+                    return Err(self.invalid(line, key, format!("texture '{}' refers back to itself", name)));
+                }
+                if !self.textures.contains_key(&name) && !self.texture_descs.contains_key(&name) {
+                    return Err(self.invalid(line, key, format!("unknown texture '{}'", name)));
+                }
+                self.texture(&name, resolving)
+            }
+        }
+    }
+
+    fn material(&mut self, desc: MaterialDesc, key: &str, line: usize) -> Result<Arc<dyn Material>, SceneError> {
+        let resolving = &mut HashSet::new();
+        let material: Arc<dyn Material> = match desc {
+            MaterialDesc::Lambertian { albedo } => {
+                let albedo = self.texture_ref(albedo, &format!("{}.albedo", key), line, resolving)?;
+                Arc::new(Lambertian::new_from_texture(albedo))
+            }
+            MaterialDesc::Metal { albedo, preset, eta, k, roughness, roughness_v, tangent } => {
+                let mut metal = match (albedo, preset, eta, k) {
+                    (Some(albedo), None, None, None) => Metal::new(vec3(albedo), roughness),
+                    (None, Some(preset), None, None) => Metal::preset(&preset, roughness)
+                        .ok_or_else(|| self.invalid(line, key, format!("unknown metal preset '{}'", preset)))?,
+                    (None, None, Some(eta), Some(k)) => Metal::conductor(vec3(eta), vec3(k), roughness),
+                    _ => return Err(self.invalid(line, key, "needs exactly one of albedo, preset, or eta and k".to_string())),
+                };
+                if let Some(roughness_v) = roughness_v {
+                    metal.roughness_v = roughness_v;
+                }
+                if let Some(tangent) = tangent {
+                    metal.tangent = vec3(tangent);
+                }
+                Arc::new(metal)
+            }
+            MaterialDesc::Dielectric { ir, dispersion, cauchy, sellmeier, roughness, absorption, color, distance } => {
+                let mut dielectric = match (ir, dispersion, cauchy, sellmeier) {
+                    (Some(ir), None, None, None) => Dielectric::new(ir),
+                    (None, Some(preset), None, None) => Dispersion::preset(&preset)
+                        .map(Dielectric::dispersive)
+                        .ok_or_else(|| self.invalid(line, key, format!("unknown dispersion preset '{}'", preset)))?,
+                    (None, None, Some([a, b]), None) => Dielectric::dispersive(Dispersion::Cauchy { a, b }),
+                    (None, None, None, Some(SellmeierDesc { b, c })) => Dielectric::dispersive(Dispersion::Sellmeier { b, c }),
+                    _ => return Err(self.invalid(line, key, "needs exactly one of ir, dispersion, cauchy or sellmeier".to_string())),
+                };
+                dielectric.roughness = roughness;
+                match (absorption, color, distance) {
+                    (None, None, None) => {}
+                    (Some(absorption), None, None) => dielectric.absorption = vec3(absorption),
+                    (None, Some(color), Some(distance)) if distance > 0.0 => {
+                        dielectric.absorption = Dielectric::absorption_for(vec3(color), distance);
+                    }
+                    _ => return Err(self.invalid(line, key, "needs either absorption or color and a positive distance".to_string())),
+                }
+                Arc::new(dielectric)
+            }
+            MaterialDesc::Principled {
+                base_color,
+                metallic,
+                roughness,
+                specular,
+                specular_tint,
+                sheen,
+                clearcoat,
+                clearcoat_gloss,
+                transmission,
+                ior,
+            } => {
+                let base_color = self.texture_ref(base_color, &format!("{}.base_color", key), line, resolving)?;
+                let mut principled = Principled::new(base_color);
+                let slots = [
+                    (metallic, "metallic", &mut principled.metallic),
+                    (roughness, "roughness", &mut principled.roughness),
+                    (specular, "specular", &mut principled.specular),
+                    (specular_tint, "specular_tint", &mut principled.specular_tint),
+                    (sheen, "sheen", &mut principled.sheen),
+                    (clearcoat, "clearcoat", &mut principled.clearcoat),
+                    (clearcoat_gloss, "clearcoat_gloss", &mut principled.clearcoat_gloss),
+                    (transmission, "transmission", &mut principled.transmission),
+                    (ior, "ior", &mut principled.ior),
+                ];
+                for (texture, name, slot) in slots {
+                    if let Some(texture) = texture {
+                        *slot = self.texture_ref(texture, &format!("{}.{}", key, name), line, resolving)?;
+                    }
+                }
+                Arc::new(principled)
+            }
+            MaterialDesc::DiffuseLight { emit } => {
+                let emit = self.texture_ref(emit, &format!("{}.emit", key), line, resolving)?;
+                Arc::new(DiffuseLight::new(emit))
+            }
+            MaterialDesc::Isotropic { albedo } => {
+                let albedo = self.texture_ref(albedo, &format!("{}.albedo", key), line, resolving)?;
+                Arc::new(Isotropic::new(albedo))
+            }
+        };
+        Ok(material)
+    }
+
+    fn material_ref(&self, name: &str, key: &str, line: usize) -> Result<Arc<dyn Material>, SceneError> {
+        self.materials
+            .get(name)
+            .map(Arc::clone)
+            .ok_or_else(|| self.invalid(line, &format!("{}.material", key), format!("unknown material '{}'", name)))
+    }
+
+    fn take<T: for<'de> Deserialize<'de>>(
+        &self,
+        table: &mut Table,
+        field: &str,
+        key: &str,
+        line: usize,
+    ) -> Result<Option<T>, SceneError> {
+        match table.remove(field) {
+            Some(value) => value
+                .try_into()
+                .map(Some)
+                .map_err(|e: toml::de::Error| self.invalid(line, &format!("{}.{}", key, field), e.message().to_string())),
+            None => Ok(None),
+        }
+    }
+
+    // Most shapes are a single object; a model file gives one per mesh, each
+    // placed by the same transform so its lights stay separate.
+    fn objects(&mut self, mut table: Table, key: &str, line: usize) -> Result<Vec<Arc<dyn Hittable>>, SceneError> {
+        // Placement keys are shared by every shape, so pull them out before
+        // the remaining table is matched against the shape itself.
+        let transform: Vec<TransformDesc> = self.take(&mut table, "transform", key, line)?.unwrap_or_default();
+        let animation: Vec<KeyframeDesc> = self.take(&mut table, "animation", key, line)?.unwrap_or_default();
+        let flip_face: bool = self.take(&mut table, "flip_face", key, line)?.unwrap_or(false);
+
+        let shape: ShapeDesc = Value::Table(table)
+            .try_into()
+            .map_err(|e: toml::de::Error| self.invalid(line, key, e.message().to_string()))?;
+
+        // Steps apply in order, so later ones multiply on the left.
+        let matrix = transform.iter().fold(Mat4::IDENTITY, |m, step| step.matrix() * m);
+        if !transform.is_empty() && matrix.inverse().is_none() {
+            return Err(self.invalid(line, &format!("{}.transform", key), "transform is singular".to_string()));
+        }
+
+        let mut keys: Vec<Keyframe> = animation.iter().map(KeyframeDesc::keyframe).collect();
+        keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+        for (i, k) in keys.iter().enumerate() {
+            let zero = (0..3).any(|a| k.scale[a] == 0.0);
+            let flips = i > 0 && (0..3).any(|a| k.scale[a].signum() != keys[i - 1].scale[a].signum());
+            if zero || flips {
+                let message = "scale must not be zero or change sign between keys".to_string();
+                return Err(self.invalid(line, &format!("{}.animation", key), message));
+            }
+        }
+
+        let mut placed = false;
+        let parts: Vec<Arc<dyn Hittable>> = match shape {
+            ShapeDesc::Obj { path, material } => {
+                let material = match material {
+                    Some(name) => self.material_ref(&name, key, line)?,
+                    None => Arc::new(Lambertian::new_from_color(Color::new(0.73, 0.73, 0.73))),
+                };
+                let full = self.path.parent().unwrap_or(Path::new("")).join(&path);
+                load_obj(&full, material)
+                    .map_err(|e| self.invalid(line, &format!("{}.path", key), e.to_string()))?
+                    .into_iter()
+                    .map(|mesh| Arc::new(mesh) as Arc<dyn Hittable>)
+                    .collect()
+            }
+            ShapeDesc::Instance { prototype, material } => {
+                placed = true;
+                self.instances(&prototype, material, matrix, key, line)?
+            }
+            shape => vec![self.shape(shape, key, line)?],
+        };
+
+        let mut objects = Vec::with_capacity(parts.len());
+        for mut object in parts {
+            if !placed && !transform.is_empty() {
+                object = Arc::new(Transform::new(object, matrix));
+            }
+
+            if !keys.is_empty() {
+                object = Arc::new(AnimatedTransform::new(object, keys.clone()));
+            }
+
+            if flip_face {
+                object = Arc::new(FlipFace::new(object));
+            }
+            objects.push(object);
+        }
+
+        Ok(objects)
+    }
+
+    // An emissive instance of a prototype with several parts is split into
+    // an instance per part, since lights are sampled object by object.
+    fn instances(
+        &self,
+        name: &str,
+        material: Option<String>,
+        matrix: Mat4,
+        key: &str,
+        line: usize,
+    ) -> Result<Vec<Arc<dyn Hittable>>, SceneError> {
+        let prototype = self
+            .prototypes
+            .get(name)
+            .ok_or_else(|| self.invalid(line, &format!("{}.prototype", key), format!("unknown prototype '{}'", name)))?;
+        let material = material.map(|name| self.material_ref(&name, key, line)).transpose()?;
+
+        let emissive = match &material {
+            Some(material) => material.is_emissive(),
+            None => prototype.parts.iter().any(|part| part.is_emissive()),
+        };
+        let shared = if emissive { prototype.parts.clone() } else { vec![Arc::clone(&prototype.shared)] };
+        Ok(shared
+            .into_iter()
+            .map(|part| Arc::new(Instance::new(part, matrix, material.clone())) as Arc<dyn Hittable>)
+            .collect())
+    }
+
+    fn object(&mut self, table: Table, key: &str, line: usize) -> Result<Arc<dyn Hittable>, SceneError> {
+        let mut objects = self.objects(table, key, line)?;
+        if objects.len() == 1 {
+            Ok(objects.pop().unwrap())
+        } else {
+            Ok(Arc::new(LinearBvh::new(&mut objects, self.shutter.0, self.shutter.1)))
+        }
+    }
+
+    fn shape(&mut self, shape: ShapeDesc, key: &str, line: usize) -> Result<Arc<dyn Hittable>, SceneError> {
+        let object: Arc<dyn Hittable> = match shape {
+            ShapeDesc::Sphere { center, radius, material } => {
+                let material = self.material_ref(&material, key, line)?;
+                if radius <= 0.0 {
+                    return Err(self.invalid(line, key, "radius must be above zero".to_string()));
+                }
+                Arc::new(Sphere::new(vec3(center), radius, material))
+            }
+            ShapeDesc::MovingSphere { center0, center1, time0, time1, radius, material } => {
+                let material = self.material_ref(&material, key, line)?;
+                if radius <= 0.0 {
+                    return Err(self.invalid(line, key, "radius must be above zero".to_string()));
+                }
+                Arc::new(MovingSphere::new(vec3(center0), vec3(center1), time0, time1, radius, material))
+            }
+            ShapeDesc::Quad { q, u, v, material } => {
+                let material = self.material_ref(&material, key, line)?;
+                if cross(&vec3(u), &vec3(v)).near_zero() {
+                    return Err(self.invalid(line, key, "u and v must not be parallel".to_string()));
+                }
+                Arc::new(Quad::new(vec3(q), vec3(u), vec3(v), material))
+            }
+            ShapeDesc::XyRect { x0, x1, y0, y1, k, material } => {
+                let material = self.material_ref(&material, key, line)?;
+                Arc::new(Quad::xy(x0, x1, y0, y1, k, material))
+            }
+            ShapeDesc::XzRect { x0, x1, z0, z1, k, material } => {
+                let material = self.material_ref(&material, key, line)?;
+                Arc::new(Quad::xz(x0, x1, z0, z1, k, material))
+            }
+            ShapeDesc::YzRect { y0, y1, z0, z1, k, material } => {
+                let material = self.material_ref(&material, key, line)?;
+                Arc::new(Quad::yz(y0, y1, z0, z1, k, material))
+            }
+            ShapeDesc::Cuboid { p0, p1, material } => {
+                let material = self.material_ref(&material, key, line)?;
+                Arc::new(Cuboid::new(vec3(p0), vec3(p1), material))
+            }
+            ShapeDesc::Cylinder { y0, y1, p0, p1, radius, open, material } => {
+                let material = self.material_ref(&material, key, line)?;
+                if radius <= 0.0 {
+                    return Err(self.invalid(line, key, "radius must be above zero".to_string()));
+                }
+                let mut cylinder = match (y0, y1, p0, p1) {
+                    (Some(y0), Some(y1), None, None) => Cylinder::new(y0, y1, radius, material),
+                    (None, None, Some(p0), Some(p1)) if p0 != p1 => Cylinder::between(vec3(p0), vec3(p1), radius, material),
+                    _ => return Err(self.invalid(line, key, "needs either y0 and y1 or two different points p0 and p1".to_string())),
+                };
+                cylinder.open = open;
+                Arc::new(cylinder)
+            }
+            ShapeDesc::Disk { center, normal, radius, inner_radius, material } => {
+                let material = self.material_ref(&material, key, line)?;
+                if vec3(normal).near_zero() {
+                    return Err(self.invalid(line, key, "normal must not be zero".to_string()));
+                }
+                if !(0.0..radius).contains(&inner_radius) {
+                    return Err(self.invalid(line, key, "inner_radius must be at least zero and less than radius".to_string()));
+                }
+                if inner_radius > 0.0 {
+                    Arc::new(Disk::annulus(vec3(center), vec3(normal), inner_radius, radius, material))
+                } else {
+                    Arc::new(Disk::new(vec3(center), vec3(normal), radius, material))
+                }
+            }
+            ShapeDesc::Cone { p0, radius0, p1, radius1, open, material } => {
+                let material = self.material_ref(&material, key, line)?;
+                if p0 == p1 {
+                    return Err(self.invalid(line, key, "p0 and p1 must be different points".to_string()));
+                }
+                if radius0 < 0.0 || radius1 < 0.0 || radius0 + radius1 <= 0.0 {
+                    return Err(self.invalid(line, key, "radii must not be negative or both zero".to_string()));
+                }
+                let mut cone = Cone::new(vec3(p0), radius0, vec3(p1), radius1, material);
+                cone.open = open;
+                Arc::new(cone)
+            }
+            ShapeDesc::Torus { center, axis, major_radius, minor_radius, material } => {
+                let material = self.material_ref(&material, key, line)?;
+                if vec3(axis).near_zero() {
+                    return Err(self.invalid(line, key, "axis must not be zero".to_string()));
+                }
+                if minor_radius <= 0.0 || major_radius <= 0.0 {
+                    return Err(self.invalid(line, key, "radii must be above zero".to_string()));
+                }
+                Arc::new(Torus::new(vec3(center), vec3(axis), major_radius, minor_radius, material))
+            }
+            ShapeDesc::Capsule { p0, p1, radius, material } => {
+                let material = self.material_ref(&material, key, line)?;
+                if p0 == p1 {
+                    return Err(self.invalid(line, key, "p0 and p1 must be different points".to_string()));
+                }
+                if radius <= 0.0 {
+                    return Err(self.invalid(line, key, "radius must be above zero".to_string()));
+                }
+                Arc::new(Capsule::new(vec3(p0), vec3(p1), radius, material))
+            }
+            ShapeDesc::Paraboloid { p0, p1, radius, material } => {
+                let material = self.material_ref(&material, key, line)?;
+                if p0 == p1 {
+                    return Err(self.invalid(line, key, "p0 and p1 must be different points".to_string()));
+                }
+                if radius <= 0.0 {
+                    return Err(self.invalid(line, key, "radius must be above zero".to_string()));
+                }
+                Arc::new(Paraboloid::new(vec3(p0), vec3(p1), radius, material))
+            }
+            ShapeDesc::Triangle { vertices, normals, uvs, material } => {
+                let material = self.material_ref(&material, key, line)?;
+                let mut triangle = Triangle::new(vec3(vertices[0]), vec3(vertices[1]), vec3(vertices[2]), material);
+                triangle.normals = normals.map(|n| n.map(vec3));
+                triangle.uvs = uvs.map(|uv| uv.map(|[u, v]| (u, v)));
+                Arc::new(triangle)
+            }
+            ShapeDesc::Mesh { positions, indices, normals, uvs, colors, material } => {
+                let material = self.material_ref(&material, key, line)?;
+                if indices.is_empty() {
+                    return Err(self.invalid(line, key, "mesh has no faces".to_string()));
+                }
+                if let Some(index) = indices.iter().flatten().find(|&&i| i >= positions.len()) {
+                    return Err(self.invalid(line, key, format!("index {} is out of range for {} positions", index, positions.len())));
+                }
+                for (name, len) in [("normals", normals.len()), ("uvs", uvs.len()), ("colors", colors.len())] {
+                    if len != 0 && len != positions.len() {
+                        return Err(self.invalid(line, key, format!("{} has {} entries but there are {} positions", name, len, positions.len())));
+                    }
+                }
+                Arc::new(TriangleMesh::new(MeshData {
+                    positions: positions.into_iter().map(vec3).collect(),
+                    normals: normals.into_iter().map(vec3).collect(),
+                    uvs: uvs.into_iter().map(|[u, v]| (u, v)).collect(),
+                    colors: colors.into_iter().map(vec3).collect(),
+                    indices,
+                    material,
+                    normal_map: None,
+                }))
+            }
+            ShapeDesc::ConstantMedium { boundary, density, albedo } => {
+                let boundary = self.object(boundary, &format!("{}.boundary", key), line)?;
+                let albedo = self.texture_ref(albedo, &format!("{}.albedo", key), line, &mut HashSet::new())?;
+                Arc::new(ConstantMedium::from_texture(boundary, density, albedo))
+            }
+            ShapeDesc::Ply { path, material } => {
+                let material = material.map(|name| self.material_ref(&name, key, line)).transpose()?;
+                let full = self.path.parent().unwrap_or(Path::new("")).join(&path);
+                let mesh = load_ply(&full, material).map_err(|e| self.invalid(line, &format!("{}.path", key), e.to_string()))?;
+                Arc::new(mesh)
+            }
+            ShapeDesc::Obj { .. } | ShapeDesc::Instance { .. } => unreachable!("loaded by objects()"),
+        };
+        Ok(object)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ray::Ray, rtweekend::{INFINITY, Sampler}, vec3::Point3};
+
+    const WHITE: &str = "[materials.white]\ntype = \"lambertian\"\nalbedo = [0.5, 0.5, 0.5]\n\n";
+
+    fn parse(objects: &str) -> Result<Scene, SceneError> {
+        parse_scene(Path::new("test.toml"), &format!("{}{}", WHITE, objects), &mut Sampler::new(0))
+    }
+
+    fn first_hit(scene: &Scene, origin: Point3, direction: Vec3) -> Option<f64> {
+        let mut sampler = Sampler::new(0);
+        scene.world.hit(&Ray::new(origin, direction), 0.001, INFINITY, &mut sampler).map(|rec| rec.t)
+    }
+
+    fn invalid(result: Result<Scene, SceneError>) -> (usize, String, String) {
+        match result {
+            Err(SceneError::Invalid { line, key, message, .. }) => (line, key, message),
+            Err(e) => panic!("expected an invalid scene, got {}", e),
+            Ok(_) => panic!("expected an invalid scene"),
+        }
+    }
+
+    #[test]
+    fn parses_every_shape() {
+        let down_z = Vec3::new(0.0, 0.0, -1.0);
+        let cases = [
+            ("type = \"sphere\"\ncenter = [0.0, 0.0, 0.0]\nradius = 1.0", Point3::new(0.0, 0.0, 10.0), down_z, 9.0),
+            (
+                "type = \"moving_sphere\"\ncenter0 = [0.0, 0.0, 0.0]\ncenter1 = [5.0, 0.0, 0.0]\nradius = 1.0",
+                Point3::new(0.0, 0.0, 10.0),
+                down_z,
+                9.0,
+            ),
+            (
+                "type = \"quad\"\nq = [-1.0, -1.0, 0.0]\nu = [2.0, 0.0, 0.0]\nv = [0.0, 2.0, 0.0]",
+                Point3::new(0.0, 0.0, 10.0),
+                down_z,
+                10.0,
+            ),
+            ("type = \"xy_rect\"\nx0 = -1.0\nx1 = 1.0\ny0 = -1.0\ny1 = 1.0\nk = 0.0", Point3::new(0.0, 0.0, 10.0), down_z, 10.0),
+            (
+                "type = \"xz_rect\"\nx0 = -1.0\nx1 = 1.0\nz0 = -1.0\nz1 = 1.0\nk = 0.0",
+                Point3::new(0.0, 10.0, 0.0),
+                Vec3::new(0.0, -1.0, 0.0),
+                10.0,
+            ),
+            (
+                "type = \"yz_rect\"\ny0 = -1.0\ny1 = 1.0\nz0 = -1.0\nz1 = 1.0\nk = 0.0",
+                Point3::new(10.0, 0.0, 0.0),
+                Vec3::new(-1.0, 0.0, 0.0),
+                10.0,
+            ),
+            ("type = \"cuboid\"\np0 = [-1.0, -1.0, -1.0]\np1 = [1.0, 1.0, 1.0]", Point3::new(0.0, 0.0, 10.0), down_z, 9.0),
+            ("type = \"cylinder\"\ny0 = -1.0\ny1 = 1.0\nradius = 1.0", Point3::new(0.0, 0.0, 10.0), down_z, 9.0),
+            (
+                "type = \"cylinder\"\np0 = [0.0, 0.0, -1.0]\np1 = [0.0, 0.0, 1.0]\nradius = 0.5",
+                Point3::new(0.0, 0.0, 10.0),
+                down_z,
+                9.0,
+            ),
+            ("type = \"disk\"\ncenter = [0.0, 0.0, 0.0]\nnormal = [0.0, 0.0, 1.0]\nradius = 1.0", Point3::new(0.0, 0.0, 10.0), down_z, 10.0),
+            (
+                "type = \"disk\"\ncenter = [0.0, 0.0, 0.0]\nnormal = [0.0, 0.0, 1.0]\nradius = 1.0\ninner_radius = 0.5",
+                Point3::new(0.75, 0.0, 10.0),
+                down_z,
+                10.0,
+            ),
+            (
+                "type = \"cone\"\np0 = [0.0, -1.0, 0.0]\nradius0 = 1.0\np1 = [0.0, 1.0, 0.0]\nradius1 = 0.0",
+                Point3::new(0.0, 0.0, 10.0),
+                down_z,
+                9.5,
+            ),
+            (
+                "type = \"torus\"\ncenter = [0.0, 0.0, 0.0]\nmajor_radius = 3.0\nminor_radius = 1.0",
+                Point3::new(10.0, 0.0, 0.0),
+                Vec3::new(-1.0, 0.0, 0.0),
+                6.0,
+            ),
+            (
+                "type = \"capsule\"\np0 = [0.0, -1.0, 0.0]\np1 = [0.0, 1.0, 0.0]\nradius = 1.0",
+                Point3::new(0.0, 10.0, 0.0),
+                Vec3::new(0.0, -1.0, 0.0),
+                8.0,
+            ),
+            (
+                "type = \"paraboloid\"\np0 = [0.0, 0.0, 0.0]\np1 = [0.0, 1.0, 0.0]\nradius = 1.0",
+                Point3::new(0.0, -10.0, 0.0),
+                Vec3::new(0.0, 1.0, 0.0),
+                10.0,
+            ),
+            (
+                "type = \"triangle\"\nvertices = [[-1.0, -1.0, 0.0], [1.0, -1.0, 0.0], [0.0, 1.0, 0.0]]",
+                Point3::new(0.0, 0.0, 10.0),
+                down_z,
+                10.0,
+            ),
+            (
+                "type = \"mesh\"\npositions = [[-1.0, -1.0, 0.0], [1.0, -1.0, 0.0], [1.0, 1.0, 0.0], [-1.0, 1.0, 0.0]]\n\
+                 indices = [[0, 1, 2], [0, 2, 3]]",
+                Point3::new(0.5, -0.5, 10.0),
+                down_z,
+                10.0,
+            ),
+        ];
+
+        for (shape, origin, direction, t) in cases {
+            let scene = parse(&format!("[[objects]]\n{}\nmaterial = \"white\"\n", shape)).unwrap();
+            let hit = first_hit(&scene, origin, direction);
+            assert!(hit.is_some_and(|hit| (hit - t).abs() < 1e-6), "{}: {:?}", shape, hit);
+        }
+    }
+
+    #[test]
+    fn parses_media_and_instances() {
+        let scene = parse(
+            "[[objects]]\n\
+             type = \"constant_medium\"\n\
+             boundary = { type = \"sphere\", center = [0.0, 0.0, 0.0], radius = 1.0, material = \"white\" }\n\
+             density = 1e9\n\
+             albedo = [0.5, 0.5, 0.5]\n",
+        )
+        .unwrap();
+        let hit = first_hit(&scene, Point3::new(0.0, 0.0, 10.0), Vec3::new(0.0, 0.0, -1.0)).unwrap();
+        assert!((9.0..9.001).contains(&hit), "{}", hit);
+
+        let scene = parse(
+            "[prototypes.ball]\n\
+             objects = [{ type = \"sphere\", center = [0.0, 0.0, 0.0], radius = 1.0, material = \"white\" }]\n\n\
+             [[objects]]\n\
+             type = \"instance\"\n\
+             prototype = \"ball\"\n\
+             transform = [{ translate = [3.0, 0.0, 0.0] }]\n",
+        )
+        .unwrap();
+        let hit = first_hit(&scene, Point3::new(3.0, 0.0, 10.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(hit.is_some_and(|hit| (hit - 9.0).abs() < 1e-9));
+        assert!(first_hit(&scene, Point3::new(0.0, 0.0, 10.0), Vec3::new(0.0, 0.0, -1.0)).is_none());
+    }
+
+    #[test]
+    fn emissive_instances_are_split_per_part() {
+        let prototypes = "[materials.lamp]\ntype = \"diffuse_light\"\nemit = [4.0, 4.0, 4.0]\n\n\
+                          [prototypes.pair]\nobjects = [\n\
+                          { type = \"sphere\", center = [-1.0, 0.0, 0.0], radius = 0.5, material = \"white\" },\n\
+                          { type = \"sphere\", center = [1.0, 0.0, 0.0], radius = 0.5, material = \"MATERIAL\" },\n]\n\n";
+        // (material of the second part, the instance's material, objects placed)
+        let cases = [("white", None, 1), ("white", Some("lamp"), 2), ("lamp", None, 2), ("lamp", Some("white"), 1)];
+        for (part, material, placed) in cases {
+            let instance = match material {
+                Some(name) => format!("material = \"{}\"\n", name),
+                None => String::new(),
+            };
+            let scene = parse(&format!(
+                "{}[[objects]]\ntype = \"instance\"\nprototype = \"pair\"\n{}",
+                prototypes.replace("MATERIAL", part),
+                instance
+            ))
+            .unwrap();
+            assert_eq!(scene.world.objects().len(), placed, "{} {:?}", part, material);
+            assert_eq!(scene.lights.emitters.is_some(), (part == "lamp" && material.is_none()) || material == Some("lamp"));
+            // Split or not, the instance still shows both parts.
+            for x in [-1.0, 1.0] {
+                assert!(first_hit(&scene, Point3::new(x, 0.0, 10.0), Vec3::new(0.0, 0.0, -1.0)).is_some());
+            }
+        }
+    }
+
+    #[test]
+    fn split_boundaries_are_bounded_over_the_shutter() {
+        // An emissive instance of a two-part prototype is split, so the
+        // medium's boundary gets a BVH of its own; it moves during a shutter
+        // that opens at time 2.
+        let mut sampler = Sampler::new(0);
+        let scene = parse(
+            "[camera]\ntime0 = 2.0\ntime1 = 3.0\n\n\
+             [materials.lamp]\ntype = \"diffuse_light\"\nemit = [4.0, 4.0, 4.0]\n\n\
+             [prototypes.pair]\nobjects = [\n\
+             { type = \"sphere\", center = [-1.0, 0.0, 0.0], radius = 0.5, material = \"white\" },\n\
+             { type = \"sphere\", center = [1.0, 0.0, 0.0], radius = 0.5, material = \"white\" },\n]\n\n\
+             [[objects]]\ntype = \"constant_medium\"\ndensity = 1e9\nalbedo = [0.5, 0.5, 0.5]\n\
+             boundary = { type = \"instance\", prototype = \"pair\", material = \"lamp\", \
+             animation = [{ time = 2.0 }, { time = 3.0, translate = [0.0, 10.0, 0.0] }] }\n",
+        )
+        .unwrap();
+
+        for (time, y) in [(2.0, 0.0), (2.5, 5.0), (3.0, 10.0)] {
+            for x in [-1.0, 1.0] {
+                let r = Ray::with_time(Point3::new(x, y, 10.0), Vec3::new(0.0, 0.0, -1.0), time);
+                let hit = scene.world.hit(&r, 0.001, INFINITY, &mut sampler).map(|rec| rec.t);
+                assert!(hit.is_some_and(|t| (9.5..9.501).contains(&t)), "time {} x {}: {:?}", time, x, hit);
+            }
+        }
+    }
+
+    #[test]
+    fn loads_models_next_to_the_scene_file() {
+        let dir = std::env::temp_dir().join(format!("scene_file_models_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("tri.obj"), "v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+        fs::write(
+            dir.join("tri.ply"),
+            "ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\nproperty float y\nproperty float z\n\
+             element face 1\nproperty list uchar int vertex_indices\nend_header\n\
+             -1 -1 0\n1 -1 0\n0 1 0\n3 0 1 2\n",
+        )
+        .unwrap();
+
+        for path in ["tri.obj", "tri.ply"] {
+            let extension = &path[4..];
+            let source = format!("{}[[objects]]\ntype = \"{}\"\npath = \"{}\"\nmaterial = \"white\"\n", WHITE, extension, path);
+            let scene = parse_scene(&dir.join("scene.toml"), &source, &mut Sampler::new(0)).unwrap();
+            let hit = first_hit(&scene, Point3::new(0.0, 0.0, 10.0), Vec3::new(0.0, 0.0, -1.0));
+            assert!(hit.is_some_and(|hit| (hit - 10.0).abs() < 1e-6), "{}", path);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn transform_steps_place_the_object() {
+        // A unit sphere at `center`, moved by `transform`, is then found
+        // around `expected`.
+        let cases = [
+            ("{ translate = [1.0, 2.0, 3.0] }", [0.0, 0.0, 0.0], Point3::new(1.0, 2.0, 3.0)),
+            ("{ rotate_x = 90.0 }", [0.0, 3.0, 0.0], Point3::new(0.0, 0.0, 3.0)),
+            ("{ rotate_y = 90.0 }", [3.0, 0.0, 0.0], Point3::new(0.0, 0.0, -3.0)),
+            ("{ rotate_z = 90.0 }", [3.0, 0.0, 0.0], Point3::new(0.0, 3.0, 0.0)),
+            ("{ rotate = { axis = [0.0, 0.0, 2.0], angle = 90.0 } }", [3.0, 0.0, 0.0], Point3::new(0.0, 3.0, 0.0)),
+            ("{ scale = [2.0, 1.0, 1.0] }", [1.0, 0.0, 0.0], Point3::new(2.0, 0.0, 0.0)),
+            (
+                "{ matrix = [[1.0, 0.0, 0.0, 1.0], [0.0, 1.0, 0.0, 2.0], [0.0, 0.0, 1.0, 3.0], [0.0, 0.0, 0.0, 1.0]] }",
+                [0.0, 0.0, 0.0],
+                Point3::new(1.0, 2.0, 3.0),
+            ),
+            // Local +Z points along world +X, from (1, 2, 3).
+            ("{ look_at = { from = [1.0, 2.0, 3.0], to = [11.0, 2.0, 3.0] } }", [0.0, 0.0, 2.0], Point3::new(3.0, 2.0, 3.0)),
+            // Steps apply in order.
+            ("{ translate = [3.0, 0.0, 0.0] }, { rotate_y = 90.0 }", [0.0, 0.0, 0.0], Point3::new(0.0, 0.0, -3.0)),
+        ];
+
+        let direction = Vec3::new(0.0, 0.0, -1.0);
+        for (transform, center, expected) in cases {
+            let scene = parse(&format!(
+                "[[objects]]\ntype = \"sphere\"\ncenter = {:?}\nradius = 1.0\nmaterial = \"white\"\ntransform = [{}]\n",
+                center, transform
+            ))
+            .unwrap();
+            let hit = first_hit(&scene, expected + Vec3::new(0.0, 0.0, 10.0), direction);
+            assert!(hit.is_some_and(|hit| (hit - 9.0).abs() < 1e-9), "{}: {:?}", transform, hit);
+        }
+
+        // Non-uniform scale stretches the sphere to twice its width.
+        let scene = parse(
+            "[[objects]]\ntype = \"sphere\"\ncenter = [0.0, 0.0, 0.0]\nradius = 1.0\nmaterial = \"white\"\n\
+             transform = [{ scale = [2.0, 1.0, 1.0] }]\n",
+        )
+        .unwrap();
+        let hit = first_hit(&scene, Point3::new(-10.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(hit.is_some_and(|hit| (hit - 8.0).abs() < 1e-9));
+    }
+
+    #[test]
+    fn unknown_material_names_its_line() {
+        let (line, key, message) = invalid(parse(
+            "[[objects]]\ntype = \"sphere\"\ncenter = [0.0, 0.0, 0.0]\nradius = 1.0\nmaterial = \"white\"\n\n\
+             [[objects]]\ntype = \"sphere\"\ncenter = [0.0, 0.0, 0.0]\nradius = 1.0\nmaterial = \"chrome\"\n",
+        ));
+        assert_eq!((line, key.as_str()), (11, "objects[1].material"));
+        assert_eq!(message, "unknown material 'chrome'");
+    }
+
+    #[test]
+    fn singular_transform_is_rejected() {
+        let (line, key, message) = invalid(parse(
+            "[[objects]]\ntype = \"sphere\"\ncenter = [0.0, 0.0, 0.0]\nradius = 1.0\nmaterial = \"white\"\n\
+             transform = [{ translate = [1.0, 0.0, 0.0] }, { scale = [0.0, 1.0, 1.0] }]\n",
+        ));
+        assert_eq!((line, key.as_str()), (5, "objects[0].transform"));
+        assert_eq!(message, "transform is singular");
+    }
+
+    #[test]
+    fn radii_must_be_above_zero() {
+        let shapes = [
+            "type = \"sphere\"\ncenter = [0.0, 0.0, 0.0]",
+            "type = \"moving_sphere\"\ncenter0 = [0.0, 0.0, 0.0]\ncenter1 = [1.0, 0.0, 0.0]",
+            "type = \"cylinder\"\ny0 = 0.0\ny1 = 1.0",
+            "type = \"capsule\"\np0 = [0.0, 0.0, 0.0]\np1 = [0.0, 1.0, 0.0]",
+            "type = \"paraboloid\"\np0 = [0.0, 0.0, 0.0]\np1 = [0.0, 1.0, 0.0]",
+        ];
+        for shape in shapes {
+            for radius in ["0.0", "-1.0"] {
+                let (line, key, message) = invalid(parse(&format!(
+                    "[[objects]]\ntype = \"sphere\"\ncenter = [0.0, 0.0, 0.0]\nradius = 1.0\nmaterial = \"white\"\n\n\
+                     [[objects]]\n{}\nradius = {}\nmaterial = \"white\"\n",
+                    shape, radius
+                )));
+                assert_eq!((line, key.as_str(), message.as_str()), (11, "objects[1]", "radius must be above zero"), "{}", shape);
+            }
+        }
+
+        for (major, minor) in [("0.0", "1.0"), ("3.0", "-1.0")] {
+            let (line, key, message) = invalid(parse(&format!(
+                "[[objects]]\ntype = \"torus\"\ncenter = [0.0, 0.0, 0.0]\nmajor_radius = {}\nminor_radius = {}\nmaterial = \"white\"\n",
+                major, minor
+            )));
+            assert_eq!((line, key.as_str(), message.as_str()), (5, "objects[0]", "radii must be above zero"));
+        }
+    }
+
+    #[test]
+    fn missing_field_is_reported() {
+        let (line, key, message) = invalid(parse("[[objects]]\ntype = \"sphere\"\ncenter = [0.0, 0.0, 0.0]\nmaterial = \"white\"\n"));
+        assert_eq!((line, key.as_str()), (5, "objects[0]"));
+        assert!(message.contains("radius"), "{}", message);
+    }
+}
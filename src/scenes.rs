@@ -0,0 +1,785 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    bvh::LinearBvh,
+    constant_medium::ConstantMedium,
+    cuboid::Cuboid,
+    cylinder::Cylinder,
+    hittable::{FlipFace, Hittable, Transform},
+    instance::Instance,
+    material::{Dielectric, DiffuseLight, Lambertian, Material, Metal},
+    matrix::Mat4,
+    moving_sphere::MovingSphere,
+    quad::Quad,
+    rtweekend::Sampler,
+    scene::{CameraSettings, RenderSettings, Scene, SceneBuilder},
+    sphere::Sphere,
+    texture::{CheckerTexture, ImageTexture, NoiseTexture, SolidColor, Texture},
+    triangle::{MeshData, TriangleMesh},
+    vec3::{random_unit_vector, Color, Point3, Vec3},
+};
+
+pub struct BuiltinScene {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub build: fn(&mut Sampler) -> Scene,
+}
+
+pub const SCENES: &[BuiltinScene] = &[
+    BuiltinScene { name: "random_scene", description: "Final render of Ray Tracing in One Weekend", build: random_scene },
+    BuiltinScene { name: "two_spheres", description: "Two checker textured spheres", build: two_spheres },
+    BuiltinScene { name: "two_perlin_spheres", description: "Two Perlin noise spheres", build: two_perlin_spheres },
+    BuiltinScene { name: "earth", description: "Image textured globe", build: earth },
+    BuiltinScene { name: "simple_light", description: "Noise spheres lit by a rectangle and two glowing balls", build: simple_light },
+    BuiltinScene { name: "cornell_box", description: "Cornell box with an aluminum cylinder and a glass ball", build: cornell_box },
+    BuiltinScene { name: "cornell_smoke", description: "Cornell box with two smoke blocks", build: cornell_smoke },
+    BuiltinScene { name: "final_scene", description: "Final render of Ray Tracing The Next Week", build: final_scene },
+    BuiltinScene { name: "pebbles", description: "Thousands of instances of one icosphere mesh", build: pebbles },
+];
+
+pub fn find(name: &str) -> Option<&'static BuiltinScene> {
+    SCENES.iter().find(|scene| scene.name == name)
+}
+
+pub fn final_scene(sampler: &mut Sampler) -> Scene {
+    let mut objects = SceneBuilder::new();
+
+    // Ground: grid of boxes, each an instance of one unit cube
+    let mut boxes1: Vec<Arc<dyn Hittable>> = vec![];
+    let ground: Arc<dyn Material> = Arc::new(Lambertian {
+        albedo: Arc::new(SolidColor::new(Color::new(0.48, 0.83, 0.53))),
+    });
+    let unit_box: Arc<dyn Hittable> = Arc::new(Cuboid::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Point3::new(1.0, 1.0, 1.0),
+        ground,
+    ));
+    let boxes_per_side = 20;
+
+    for i in 0..boxes_per_side {
+        for j in 0..boxes_per_side {
+            let w = 100.0;
+            let x0 = -1000.0 + i as f64 * w;
+            let z0 = -1000.0 + j as f64 * w;
+            let y1 = sampler.random_double_range(1.0, 101.0);
+
+            let placement = Mat4::translation(Vec3::new(x0, 0.0, z0)) * Mat4::scaling(Vec3::new(w, y1, w));
+            boxes1.push(Arc::new(Instance::new(Arc::clone(&unit_box), placement, None)));
+        }
+    }
+
+    objects.add(Arc::new(LinearBvh::new(&mut boxes1, 0.0, 1.0)));
+
+    // Light
+    let light: Arc<dyn Material> = Arc::new(DiffuseLight {
+        emit: Arc::new(SolidColor::new(Color::new(7.0, 7.0, 7.0))),
+    });
+    objects.add(Arc::new(Quad::xz(123.0, 423.0, 147.0, 412.0, 554.0, Arc::clone(&light))));
+
+    // Moving sphere
+    let center1 = Point3::new(400.0, 400.0, 200.0);
+    let center2 = center1 + Vec3::new(30.0, 0.0, 0.0);
+    let moving_mat: Arc<dyn Material> = Arc::new(Lambertian {
+        albedo: Arc::new(SolidColor::new(Color::new(0.7, 0.3, 0.1))),
+    });
+    objects.add(Arc::new(MovingSphere::new(center1, center2, 0.0, 1.0, 50.0, moving_mat)));
+
+    // Glass ball
+    objects.add(Arc::new(Sphere::new(
+        Point3::new(260.0, 150.0, 45.0),
+        50.0,
+        Arc::new(Dielectric::new(1.5)),
+    )));
+
+    // Metal ball
+    objects.add(Arc::new(Sphere::new(
+        Point3::new(0.0, 150.0, 145.0),
+        50.0,
+        Arc::new(Metal::new(Color::new(0.8, 0.8, 0.9), 1.0)),
+    )));
+
+    // Subsurface blue fog inside glass
+    let boundary1: Arc<dyn Hittable> = Arc::new(Sphere::new(
+        Point3::new(360.0, 150.0, 145.0),
+        70.0,
+        Arc::new(Dielectric::new(1.5)),
+    ));
+    objects.add(Arc::clone(&boundary1));
+    objects.add(Arc::new(ConstantMedium::from_color(
+        boundary1,
+        0.2,
+        Color::new(0.2, 0.4, 0.9),
+    )));
+
+    // Global white fog
+    let boundary2 = Arc::new(Sphere::new(
+        Point3::new(0.0, 0.0, 0.0),
+        5000.0,
+        Arc::new(Dielectric::new(1.5)),
+    ));
+    objects.add(Arc::new(ConstantMedium::from_color(
+        boundary2,
+        0.0001,
+        Color::new(1.0, 1.0, 1.0),
+    )));
+
+    // Textured Earth sphere
+    let earth_texture = Arc::new(ImageTexture::new("earthmap.jpg"));
+    let earth_surface: Arc<dyn Material> = Arc::new(Lambertian {
+        albedo: earth_texture,
+    });
+    objects.add(Arc::new(Sphere::new(
+        Point3::new(400.0, 200.0, 400.0),
+        100.0,
+        Arc::clone(&earth_surface),
+    )));
+
+    // Perlin noise sphere
+    let noise_texture = Arc::new(NoiseTexture::new(0.1, sampler));
+    let noise_material: Arc<dyn Material> = Arc::new(Lambertian {
+        albedo: noise_texture,
+    });
+    objects.add(Arc::new(Sphere::new(
+        Point3::new(220.0, 280.0, 300.0),
+        80.0,
+        noise_material,
+    )));
+
+    // Small spheres cluster
+    let mut boxes2: Vec<Arc<dyn Hittable>> = vec![];
+    let white: Arc<dyn Material> = Arc::new(Lambertian {
+        albedo: Arc::new(SolidColor::new(Color::new(0.73, 0.73, 0.73))),
+    });
+
+    for _ in 0..1000 {
+        boxes2.push(Arc::new(Sphere::new(
+            Point3::random_range(0.0, 165.0, sampler),
+            10.0,
+            Arc::clone(&white),
+        )));
+    }
+
+    let cluster = Arc::new(Transform::translate(
+        Arc::new(Transform::rotate(
+            Arc::new(LinearBvh::new(&mut boxes2, 0.0, 1.0)),
+            Vec3::new(0.0, 1.0, 0.0),
+            15.0,
+        )),
+        Vec3::new(-100.0, 270.0, 395.0),
+    ));
+    objects.add(cluster);
+
+    let (world, lights) = objects.build(0.0, 1.0);
+
+    Scene {
+        world,
+        lights,
+        background: Color::new(0.0, 0.0, 0.0),
+        camera: CameraSettings {
+            lookfrom: Point3::new(478.0, 278.0, -600.0),
+            lookat: Point3::new(278.0, 278.0, 0.0),
+            vfov: 40.0,
+            ..Default::default()
+        },
+        render: RenderSettings {
+            aspect_ratio: 1.0,
+            image_width: 800,
+            samples_per_pixel: 100,
+            ..Default::default()
+        },
+    }
+}
+
+pub fn cornell_smoke(_sampler: &mut Sampler) -> Scene {
+    let mut objects = SceneBuilder::new();
+
+    let red: Arc<dyn Material> = Arc::new(Lambertian {
+        albedo: Arc::new(SolidColor::new(Color::new(0.65, 0.05, 0.05))),
+    });
+
+    let white: Arc<dyn Material> = Arc::new(Lambertian {
+        albedo: Arc::new(SolidColor::new(Color::new(0.73, 0.73, 0.73))),
+    });
+
+    let green: Arc<dyn Material> = Arc::new(Lambertian {
+        albedo: Arc::new(SolidColor::new(Color::new(0.12, 0.45, 0.15))),
+    });
+
+    let light: Arc<dyn Material> = Arc::new(DiffuseLight {
+        emit: Arc::new(SolidColor::new(Color::new(15.0, 15.0, 15.0))),
+    });
+
+    objects.add(Arc::new(Quad::yz(0.0, 555.0, 0.0, 555.0, 555.0, Arc::clone(&green))));
+    objects.add(Arc::new(Quad::yz(0.0, 555.0, 0.0, 555.0, 0.0, Arc::clone(&red))));
+
+    objects.add(Arc::new(Quad::xz(213.0, 343.0, 227.0, 332.0, 554.0, Arc::clone(&light))));
+
+    objects.add(Arc::new(Quad::xz(0.0, 555.0, 0.0, 555.0, 0.0, Arc::clone(&white))));
+    objects.add(Arc::new(Quad::xz(0.0, 555.0, 0.0, 555.0, 555.0, Arc::clone(&white))));
+    objects.add(Arc::new(Quad::xy(0.0, 555.0, 0.0, 555.0, 555.0, Arc::clone(&white))));
+
+    // two boxes
+
+    let box1: Arc<dyn Hittable> = Arc::new(Cuboid::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Point3::new(165.0, 330.0, 165.0),
+        Arc::clone(&white),
+    ));
+    let box1 = Arc::new(Transform::rotate(box1, Vec3::new(0.0, 1.0, 0.0), 15.0));
+    let box1 = Arc::new(Transform::translate(box1, Vec3::new(265.0, 0.0, 295.0)));
+    let smoke1 = Arc::new(ConstantMedium::from_color(box1, 0.01, Color::new(0.0, 0.0, 0.0)));
+    objects.add(smoke1);
+
+
+    let box2: Arc<dyn Hittable> = Arc::new(Cuboid::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Point3::new(165.0, 165.0, 165.0),
+        Arc::clone(&white),
+    ));
+    let box2 = Arc::new(Transform::rotate(box2, Vec3::new(0.0, 1.0, 0.0), -18.0));
+    let box2 = Arc::new(Transform::translate(box2, Vec3::new(130.0, 0.0, 65.0)));
+    let smoke2 = Arc::new(ConstantMedium::from_color(box2, 0.01, Color::new(1.0, 1.0, 1.0)));
+    objects.add(smoke2);
+
+    let (world, lights) = objects.build(0.0, 1.0);
+
+    Scene {
+        world,
+        lights,
+        background: Color::new(0.0, 0.0, 0.0),
+        camera: CameraSettings {
+            lookfrom: Point3::new(278.0, 278.0, -800.0),
+            lookat: Point3::new(278.0, 278.0, 0.0),
+            vfov: 40.0,
+            ..Default::default()
+        },
+        render: RenderSettings {
+            aspect_ratio: 1.0,
+            image_width: 600,
+            samples_per_pixel: 200,
+            ..Default::default()
+        },
+    }
+}
+
+pub fn cornell_box(_sampler: &mut Sampler) -> Scene {
+    let mut objects = SceneBuilder::new();
+
+    let red: Arc<dyn Material> = Arc::new(Lambertian {
+        albedo: Arc::new(SolidColor::new(Color::new(0.65, 0.05, 0.05))),
+    });
+
+    let white: Arc<dyn Material> = Arc::new(Lambertian {
+        albedo: Arc::new(SolidColor::new(Color::new(0.73, 0.73, 0.73))),
+    });
+
+    let green: Arc<dyn Material> = Arc::new(Lambertian {
+        albedo: Arc::new(SolidColor::new(Color::new(0.12, 0.45, 0.15))),
+    });
+
+    let light: Arc<dyn Material> = Arc::new(DiffuseLight {
+        emit: Arc::new(SolidColor::new(Color::new(15.0, 15.0, 15.0))),
+    });
+
+    objects.add(Arc::new(Quad::yz(0.0, 555.0, 0.0, 555.0, 555.0, Arc::clone(&green))));
+    objects.add(Arc::new(Quad::yz(0.0, 555.0, 0.0, 555.0, 0.0, Arc::clone(&red))));
+
+    objects.add(Arc::new(
+        FlipFace::new(Arc::new(
+            Quad::xz(213.0, 343.0, 227.0, 332.0, 554.0, Arc::clone(&light))
+        ))
+    ));
+
+    objects.add(Arc::new(Quad::xz(0.0, 555.0, 0.0, 555.0, 0.0, Arc::clone(&white))));
+    objects.add(Arc::new(Quad::xz(0.0, 555.0, 0.0, 555.0, 555.0, Arc::clone(&white))));
+    objects.add(Arc::new(Quad::xy(0.0, 555.0, 0.0, 555.0, 555.0, Arc::clone(&white))));
+
+    // room objects
+
+    let aluminum = Arc::new(Metal::aluminium(0.1));
+
+    let box1: Arc<dyn Hittable> = Arc::new(Cuboid::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Point3::new(165.0, 330.0, 165.0),
+        //Arc::clone(&white),
+        aluminum.clone(),
+    ));
+    let box1 = Arc::new(Transform::rotate(box1, Vec3::new(0.0, 1.0, 0.0), 15.0));
+    let box1 = Arc::new(Transform::translate(box1, Vec3::new(265.0, 0.0, 295.0)));
+    //objects.add(box1);
+
+    // cylinder
+
+    let cyl: Arc<dyn Hittable> = Arc::new(Cylinder::new(
+        0.0,
+        330.0,
+        83.0,
+        aluminum.clone(),
+        //Arc::clone(&white),
+    ));
+    let cyl = Arc::new(Transform::translate(cyl, Vec3::new(348.0, 0.0, 378.0)));
+    objects.add(cyl);
+
+    // front small box
+
+    let box2: Arc<dyn Hittable> = Arc::new(Cuboid::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Point3::new(165.0, 165.0, 165.0),
+        Arc::clone(&white),
+    ));
+    let box2 = Arc::new(Transform::rotate(box2, Vec3::new(0.0, 1.0, 0.0), -18.0));
+    let box2 = Arc::new(Transform::translate(box2, Vec3::new(130.0, 0.0, 65.0)));
+    //objects.add(box2);
+
+    // glass ball
+    let glass = Arc::new(Dielectric::new(1.5));
+    let ball = Arc::new(Sphere::new(
+        Point3::new(190.0, 90.0, 190.0),
+        90.0,
+        glass,
+    ));
+    objects.add_sampled(ball);
+
+    let (world, lights) = objects.build(0.0, 1.0);
+
+    Scene {
+        world,
+        lights,
+        background: Color::new(0.0, 0.0, 0.0),
+        camera: CameraSettings {
+            lookfrom: Point3::new(278.0, 278.0, -800.0),
+            lookat: Point3::new(278.0, 278.0, 0.0),
+            vfov: 40.0,
+            ..Default::default()
+        },
+        render: RenderSettings {
+            aspect_ratio: 1.0,
+            image_width: 600,
+            samples_per_pixel: 1000,
+            ..Default::default()
+        },
+    }
+}
+
+pub fn earth(_sampler: &mut Sampler) -> Scene {
+    let earth_texture: Arc<dyn Texture> = Arc::new(ImageTexture::new("earthmap.jpg"));
+    let earth_surface: Arc<dyn Material> = Arc::new(Lambertian { albedo: earth_texture });
+    let mut objects = SceneBuilder::new();
+    objects.add(Arc::new(Sphere {
+        center: Point3::new(0.0, 0.0, 0.0),
+        radius: 2.0,
+        material: earth_surface,
+    }));
+
+    let (world, lights) = objects.build(0.0, 1.0);
+
+    Scene {
+        world,
+        lights,
+        background: Color::new(0.70, 0.80, 1.00),
+        camera: CameraSettings {
+            lookfrom: Point3::new(13.0, 2.0, 3.0),
+            lookat: Point3::new(0.0, 0.0, 0.0),
+            vfov: 20.0,
+            ..Default::default()
+        },
+        render: RenderSettings::default(),
+    }
+}
+
+/*
+pub fn simple_light(sampler: &mut Sampler) -> Scene {
+    let mut objects = SceneBuilder::new();
+
+    let pertext = Arc::new(NoiseTexture::new(4.0, sampler));
+    let lambert: Arc<dyn Material> = Arc::new(Lambertian { albedo: pertext });
+
+    objects.add(Arc::new(Sphere {
+        center: Point3::new(0.0, -1000.0, 0.0),
+        radius: 1000.0,
+        material: Arc::clone(&lambert),
+    }));
+
+    objects.add(Arc::new(Sphere {
+        center: Point3::new(0.0, 2.0, 0.0),
+        radius: 2.0,
+        material: Arc::clone(&lambert),
+    }));
+
+    let light: Arc<dyn Material> = Arc::new(DiffuseLight {
+        emit: Arc::new(SolidColor::new(Color::new(4.0, 4.0, 4.0))),
+    });
+
+    objects.add(Arc::new(Quad::xy(3.0, 5.0, 1.0, 3.0, -2.0, light)));
+
+    let (world, lights) = objects.build(0.0, 1.0);
+
+    Scene {
+        world,
+        lights,
+        background: Color::new(0.0, 0.0, 0.0),
+        camera: CameraSettings {
+            lookfrom: Point3::new(26.0, 3.0, 6.0),
+            lookat: Point3::new(0.0, 2.0, 0.0),
+            vfov: 20.0,
+            aperture: 0.1,
+            ..Default::default()
+        },
+        render: RenderSettings {
+            samples_per_pixel: 400,
+            ..Default::default()
+        },
+    }
+}
+*/
+
+pub fn simple_light(sampler: &mut Sampler) -> Scene {
+    let mut objects = SceneBuilder::new();
+
+    let pertext = Arc::new(NoiseTexture::new(4.0, sampler));
+    let lambert: Arc<dyn Material> = Arc::new(Lambertian { albedo: pertext });
+
+    objects.add(Arc::new(Sphere {
+        center: Point3::new(0.0, -1000.0, 0.0),
+        radius: 1000.0,
+        material: Arc::clone(&lambert),
+    }));
+
+    objects.add(Arc::new(Sphere {
+        center: Point3::new(0.0, 2.0, 0.0),
+        radius: 2.0,
+        material: Arc::clone(&lambert),
+    }));
+
+    let light_color = Arc::new(SolidColor::new(Color::new(4.0, 4.0, 4.0)));
+    let light: Arc<dyn Material> = Arc::new(DiffuseLight { emit: light_color });
+
+    objects.add(Arc::new(Quad::xy(3.0, 5.0, 3.0, 5.0, -2.0, Arc::clone(&light))));
+
+    // Glowing sphere nearly under main sphere
+    objects.add(Arc::new(Sphere {
+        center: Point3::new(2.0, 0.5, -0.2),
+        radius: 0.5,
+        material: Arc::clone(&light),
+    }));
+
+    // Glowing ball on the left
+    objects.add(Arc::new(Sphere {
+        center: Point3::new(-3.5, 0.5, 2.0),
+        radius: 0.5,
+        material: Arc::clone(&light),
+    }));
+
+    let (world, lights) = objects.build(0.0, 1.0);
+
+    Scene {
+        world,
+        lights,
+        background: Color::new(0.0, 0.0, 0.0),
+        camera: CameraSettings {
+            lookfrom: Point3::new(26.0, 3.0, 6.0),
+            lookat: Point3::new(0.0, 2.0, 0.0),
+            vfov: 20.0,
+            aperture: 0.1,
+            ..Default::default()
+        },
+        render: RenderSettings {
+            samples_per_pixel: 400,
+            ..Default::default()
+        },
+    }
+}
+
+pub fn two_perlin_spheres(sampler: &mut Sampler) -> Scene {
+    let mut objects = SceneBuilder::new();
+
+    let pertext = Arc::new(NoiseTexture::new(4.0, sampler));
+
+    let pertext_material: Arc<dyn Material> = Arc::new(Lambertian {
+        albedo: pertext,
+    });
+
+    objects.add(Arc::new(Sphere {
+        center: Point3::new(0.0, -1000.0, 0.0),
+        radius: 1000.0,
+        material: Arc::clone(&pertext_material),
+    }));
+
+    objects.add(Arc::new(Sphere {
+        center: Point3::new(0.0, 2.0, 0.0),
+        radius: 2.0,
+        material: Arc::clone(&pertext_material),
+    }));
+
+    let (world, lights) = objects.build(0.0, 1.0);
+
+    Scene {
+        world,
+        lights,
+        background: Color::new(0.70, 0.80, 1.00),
+        camera: CameraSettings {
+            lookfrom: Point3::new(13.0, 2.0, 3.0),
+            lookat: Point3::new(0.0, 0.0, 0.0),
+            vfov: 20.0,
+            ..Default::default()
+        },
+        render: RenderSettings::default(),
+    }
+}
+
+pub fn two_spheres(_sampler: &mut Sampler) -> Scene {
+    let mut objects = SceneBuilder::new();
+
+    let checker = Arc::new(CheckerTexture::from_colors(
+        Color::new(0.2, 0.3, 0.1),
+        Color::new(0.9, 0.9, 0.9),
+    ));
+
+    let checker_material: Arc<dyn Material> = Arc::new(Lambertian {
+        albedo: checker,
+    });
+
+    objects.add(Arc::new(Sphere {
+        center: Point3::new(0.0, -10.0, 0.0),
+        radius: 10.0,
+        material: Arc::clone(&checker_material),
+    }));
+
+    objects.add(Arc::new(Sphere {
+        center: Point3::new(0.0, 10.0, 0.0),
+        radius: 10.0,
+        material: Arc::clone(&checker_material),
+    }));
+
+    let (world, lights) = objects.build(0.0, 1.0);
+
+    Scene {
+        world,
+        lights,
+        background: Color::new(0.70, 0.80, 1.00),
+        camera: CameraSettings {
+            lookfrom: Point3::new(13.0, 2.0, 3.0),
+            lookat: Point3::new(0.0, 0.0, 0.0),
+            vfov: 20.0,
+            ..Default::default()
+        },
+        render: RenderSettings::default(),
+    }
+}
+
+pub fn random_scene(sampler: &mut Sampler) -> Scene {
+    let mut objects = SceneBuilder::new();
+
+    let checker = Arc::new(CheckerTexture::from_colors(
+        Color::new(0.2, 0.3, 0.1),
+        Color::new(0.9, 0.9, 0.9),
+    ));
+
+    let checker_material = Arc::new(Lambertian {
+        albedo: checker,
+    });
+
+    objects.add(Arc::new(Sphere {
+        center: Point3::new(0.0, -1000.0, 0.0),
+        radius: 1000.0,
+        material: checker_material,
+    }));
+
+    for a in -11..11 {
+        for b in -11..11 {
+            let choose_mat = sampler.random_double();
+            let center = Point3::new(
+                a as f64 + 0.9 * sampler.random_double(),
+                0.2,
+                b as f64 + 0.9 * sampler.random_double(),
+            );
+
+            if (center - Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
+                let sphere_material: Arc<dyn Material>;
+
+                if choose_mat < 0.8 {
+                    // Diffuse
+                    let albedo = Color::random(sampler) * Color::random(sampler);
+                    sphere_material = Arc::new(Lambertian {
+                        albedo: Arc::new(SolidColor::new(albedo)),
+                    });
+                    let center2 = center + Vec3::new(0.0, sampler.random_double_range(0.0, 0.5), 0.0);
+                    objects.add(Arc::new(MovingSphere {
+                        center0: center,
+                        center1: center2,
+                        time0: 0.0,
+                        time1: 1.0,
+                        radius: 0.2,
+                        material: Arc::clone(&sphere_material),
+                    }));
+                } else if choose_mat < 0.95 {
+                    let albedo = Color::random_range(0.5, 1.0, sampler);
+                    let fuzz = sampler.random_double_range(0.0, 0.5);
+                    sphere_material = Arc::new(Metal::new(albedo, fuzz));
+
+                    objects.add(Arc::new(Sphere {
+                        center,
+                        radius: 0.2,
+                        material: sphere_material,
+                    }));
+                } else {
+                    // Glass
+                    sphere_material = Arc::new(Dielectric::new(1.5));
+
+                    objects.add(Arc::new(Sphere {
+                        center,
+                        radius: 0.2,
+                        material: sphere_material,
+                    }));
+                }
+            }
+        }
+    }
+
+    // Three big spheres
+    let material1: Arc<dyn Material> = Arc::new(Dielectric::new(1.5));
+    objects.add(Arc::new(Sphere {
+        center: Point3::new(0.0, 1.0, 0.0),
+        radius: 1.0,
+        material: material1,
+    }));
+
+    let material2: Arc<dyn Material> = Arc::new(Lambertian {
+        albedo: Arc::new(SolidColor::new(Color::new(0.4, 0.2, 0.1))),
+    });
+    objects.add(Arc::new(Sphere {
+        center: Point3::new(-4.0, 1.0, 0.0),
+        radius: 1.0,
+        material: material2,
+    }));
+
+    let material3: Arc<dyn Material> = Arc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0));
+    objects.add(Arc::new(Sphere {
+        center: Point3::new(4.0, 1.0, 0.0),
+        radius: 1.0,
+        material: material3,
+    }));
+
+    let (world, lights) = objects.build(0.0, 1.0);
+
+    Scene {
+        world,
+        lights,
+        background: Color::new(0.70, 0.80, 1.00),
+        camera: CameraSettings {
+            lookfrom: Point3::new(13.0, 2.0, 3.0),
+            lookat: Point3::new(0.0, 0.0, 0.0),
+            vfov: 20.0,
+            aperture: 0.1,
+            ..Default::default()
+        },
+        render: RenderSettings::default(),
+    }
+}
+
+pub fn pebbles(sampler: &mut Sampler) -> Scene {
+    let mut objects = SceneBuilder::new();
+
+    let checker = Arc::new(CheckerTexture::from_colors(
+        Color::new(0.2, 0.3, 0.1),
+        Color::new(0.9, 0.9, 0.9),
+    ));
+    objects.add(Arc::new(Sphere {
+        center: Point3::new(0.0, -1000.0, 0.0),
+        radius: 1000.0,
+        material: Arc::new(Lambertian { albedo: checker }),
+    }));
+
+    // Every pebble shares this mesh and its BVH; each one only adds a
+    // matrix and a material.
+    let white: Arc<dyn Material> = Arc::new(Lambertian::new_from_color(Color::new(0.73, 0.73, 0.73)));
+    let pebble: Arc<dyn Hittable> = Arc::new(icosphere(3, white));
+    let palette: [Arc<dyn Material>; 4] = [
+        Arc::new(Lambertian::new_from_color(Color::new(0.65, 0.25, 0.15))),
+        Arc::new(Lambertian::new_from_color(Color::new(0.25, 0.35, 0.6))),
+        Arc::new(Metal::new(Color::new(0.8, 0.7, 0.5), 0.2)),
+        Arc::new(Dielectric::new(1.5)),
+    ];
+
+    let per_side = 60;
+    for a in 0..per_side {
+        for b in 0..per_side {
+            let x = -15.0 + 30.0 * (a as f64 + sampler.random_double()) / per_side as f64;
+            let z = -15.0 + 30.0 * (b as f64 + sampler.random_double()) / per_side as f64;
+            let size = Vec3::new(
+                sampler.random_double_range(0.12, 0.25),
+                sampler.random_double_range(0.06, 0.15),
+                sampler.random_double_range(0.12, 0.25),
+            );
+            let placement = Mat4::translation(Vec3::new(x, size.y * 0.7, z))
+                * Mat4::rotation(Vec3::new(0.0, 1.0, 0.0), sampler.random_double_range(0.0, 360.0))
+                * Mat4::rotation(random_unit_vector(sampler), sampler.random_double_range(0.0, 15.0))
+                * Mat4::scaling(size);
+            let material = Arc::clone(&palette[sampler.random_int(0, 3) as usize]);
+            objects.add(Arc::new(Instance::new(Arc::clone(&pebble), placement, Some(material))));
+        }
+    }
+
+    let (world, lights) = objects.build(0.0, 1.0);
+
+    Scene {
+        world,
+        lights,
+        background: Color::new(0.70, 0.80, 1.00),
+        camera: CameraSettings {
+            lookfrom: Point3::new(12.0, 3.0, 5.0),
+            lookat: Point3::new(0.0, 0.0, 0.0),
+            vfov: 25.0,
+            ..Default::default()
+        },
+        render: RenderSettings::default(),
+    }
+}
+
+// A unit sphere made by splitting each face of an icosahedron into four,
+// `subdivisions` times.
+fn icosphere(subdivisions: u32, material: Arc<dyn Material>) -> TriangleMesh {
+    let t = (1.0 + 5.0_f64.sqrt()) / 2.0;
+    let mut positions: Vec<Point3> = [
+        (-1.0, t, 0.0), (1.0, t, 0.0), (-1.0, -t, 0.0), (1.0, -t, 0.0),
+        (0.0, -1.0, t), (0.0, 1.0, t), (0.0, -1.0, -t), (0.0, 1.0, -t),
+        (t, 0.0, -1.0), (t, 0.0, 1.0), (-t, 0.0, -1.0), (-t, 0.0, 1.0),
+    ]
+    .iter()
+    .map(|&(x, y, z)| Point3::new(x, y, z).unit_vector())
+    .collect();
+    let mut indices = vec![
+        [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+        [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+        [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+        [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        // Edges are shared by two faces, so each midpoint is made once.
+        let mut midpoints: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut midpoint = |a: usize, b: usize| {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                positions.push(((positions[a] + positions[b]) / 2.0).unit_vector());
+                positions.len() - 1
+            })
+        };
+        indices = indices
+            .iter()
+            .flat_map(|&[a, b, c]| {
+                let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            })
+            .collect();
+    }
+
+    TriangleMesh::new(MeshData {
+        normals: positions.clone(),
+        positions,
+        uvs: Vec::new(),
+        colors: Vec::new(),
+        indices,
+        material,
+        normal_map: None,
+    })
+}
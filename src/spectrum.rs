@@ -0,0 +1,149 @@
+use std::sync::LazyLock;
+
+use crate::vec3::Color;
+
+// The visible range wavelengths are sampled from, in nanometres.
+pub const LAMBDA_MIN: f64 = 380.0;
+pub const LAMBDA_MAX: f64 = 780.0;
+
+// Wavelengths carried by one path (hero wavelength sampling, Wilkie et al.
+// 2014): the hero and three companions spaced evenly around the visible
+// range, so together they cover it with one random number. Dispersion that
+// sends each wavelength its own way can only follow the hero; the
+// companions are then terminated and the hero counts for all four.
+#[derive(Clone, Copy)]
+pub struct SampledWavelengths {
+    pub lambda: [f64; 4],
+    pub pdf: [f64; 4],
+}
+
+impl SampledWavelengths {
+    pub fn sample(u: f64) -> Self {
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let lambda = [0.0, 1.0, 2.0, 3.0].map(|i| LAMBDA_MIN + ((u + i / 4.0) % 1.0) * range);
+        Self {
+            lambda,
+            pdf: [1.0 / range; 4],
+        }
+    }
+
+    pub fn hero(&self) -> f64 {
+        self.lambda[0]
+    }
+
+    pub fn terminate_secondary(&mut self) {
+        if self.secondary_terminated() {
+            return;
+        }
+        self.pdf = [self.pdf[0] / 4.0, 0.0, 0.0, 0.0];
+    }
+
+    pub fn secondary_terminated(&self) -> bool {
+        self.pdf[1..].iter().all(|&pdf| pdf == 0.0)
+    }
+
+    // Linear sRGB estimate of a spectrum known at the sampled wavelengths.
+    // A spectrum that is one everywhere comes out white.
+    pub fn to_rgb(self, values: [f64; 4]) -> Color {
+        let mut rgb = Color::ZERO;
+        for ((value, pdf), lambda) in values.into_iter().zip(self.pdf).zip(self.lambda) {
+            if pdf > 0.0 {
+                rgb += value / pdf * sensor_rgb(lambda);
+            }
+        }
+        rgb / 4.0
+    }
+}
+
+// The CIE 1931 colour matching functions, as the multi-lobe fit of Wyman,
+// Sloan and Shirley (2013).
+pub fn cie_xyz(lambda: f64) -> Color {
+    let g = |mu: f64, below: f64, above: f64| {
+        let t = (lambda - mu) / if lambda < mu { below } else { above };
+        (-0.5 * t * t).exp()
+    };
+    Color::new(
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    )
+}
+
+pub fn xyz_to_srgb(c: Color) -> Color {
+    Color::new(
+        3.2406 * c.x - 1.5372 * c.y - 0.4986 * c.z,
+        -0.9689 * c.x + 1.8758 * c.y + 0.0415 * c.z,
+        0.0557 * c.x - 0.2040 * c.y + 1.0570 * c.z,
+    )
+}
+
+// Each channel's response integrated over the visible range, so that the
+// sensor can be balanced to keep an equal-energy spectrum white: RGB scenes
+// treat (1, 1, 1) as white and dispersion should only spread it out.
+static WHITE: LazyLock<Color> = LazyLock::new(|| {
+    let steps = 4000;
+    let step = (LAMBDA_MAX - LAMBDA_MIN) / steps as f64;
+    let mut sum = Color::ZERO;
+    for i in 0..steps {
+        sum += step * xyz_to_srgb(cie_xyz(LAMBDA_MIN + (i as f64 + 0.5) * step));
+    }
+    sum
+});
+
+// Linear sRGB response of the film to light at one wavelength.
+pub fn sensor_rgb(lambda: f64) -> Color {
+    let rgb = xyz_to_srgb(cie_xyz(lambda));
+    Color::new(rgb.x / WHITE.x, rgb.y / WHITE.y, rgb.z / WHITE.z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtweekend::Sampler;
+
+    #[test]
+    fn flat_spectrum_is_white() {
+        let mut sampler = Sampler::new(0);
+        let n = 20_000;
+        let mut sum = Color::ZERO;
+        for _ in 0..n {
+            sum += SampledWavelengths::sample(sampler.random_double()).to_rgb([1.0; 4]);
+        }
+        assert!((sum / n as f64 - Color::new(1.0, 1.0, 1.0)).length() < 0.01);
+    }
+
+    #[test]
+    fn terminating_keeps_the_estimate_unbiased() {
+        let mut sampler = Sampler::new(0);
+        let n = 100_000;
+        let mut sum = Color::ZERO;
+        for _ in 0..n {
+            let mut wavelengths = SampledWavelengths::sample(sampler.random_double());
+            wavelengths.terminate_secondary();
+            assert!(wavelengths.secondary_terminated());
+            sum += wavelengths.to_rgb([1.0; 4]);
+        }
+        assert!((sum / n as f64 - Color::new(1.0, 1.0, 1.0)).length() < 0.03);
+    }
+
+    #[test]
+    fn companions_are_spread_over_the_range() {
+        let wavelengths = SampledWavelengths::sample(0.9);
+        let mut lambda = wavelengths.lambda;
+        lambda.sort_by(f64::total_cmp);
+        for pair in lambda.windows(2) {
+            assert!((pair[1] - pair[0] - 100.0).abs() < 1e-9);
+        }
+        assert!(lambda.iter().all(|l| (LAMBDA_MIN..LAMBDA_MAX).contains(l)));
+    }
+
+    #[test]
+    fn monochromatic_light_has_the_expected_hue() {
+        let red = sensor_rgb(650.0);
+        assert!(red.x > red.y && red.x > red.z);
+        let green = sensor_rgb(530.0);
+        assert!(green.y > green.x && green.y > green.z);
+        let blue = sensor_rgb(450.0);
+        assert!(blue.z > blue.x && blue.z > blue.y);
+    }
+}
@@ -5,8 +5,8 @@ use crate::hittable::{HitRecord, Hittable};
 use crate::material::Material;
 use crate::onb::Onb;
 use crate::ray::Ray;
-use crate::rtweekend::{get_sphere_uv, random_double, random_double_range, INFINITY, PI};
-use crate::vec3::{dot, Point3, Vec3};
+use crate::rtweekend::{get_sphere_uv, INFINITY, PI, Sampler};
+use crate::vec3::{dot, random_unit_vector, Point3, Vec3};
 
 pub struct Sphere {
     pub center: Point3,
@@ -23,9 +23,9 @@ impl Sphere {
         }
     }
 
-    fn random_to_sphere(radius: f64, distance_squared: f64) -> Vec3 {
-        let r1 = random_double();
-        let r2 = random_double();
+    fn random_to_sphere(radius: f64, distance_squared: f64, sampler: &mut Sampler) -> Vec3 {
+        let r1 = sampler.random_double();
+        let r2 = sampler.random_double();
         let z = 1.0 + r2 * ((1.0 - radius * radius / distance_squared).sqrt() - 1.0);
         let phi = 2.0 * PI * r1;
         let x = phi.cos() * (1.0 - z*z).sqrt();
@@ -36,7 +36,7 @@ impl Sphere {
 
 
 impl Hittable for Sphere {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, _sampler: &mut Sampler) -> Option<HitRecord> {
         let oc: Vec3 = r.origin - self.center;
         let a = r.direction().length_squared();
         let half_b = dot(&oc, &r.direction());
@@ -70,6 +70,7 @@ impl Hittable for Sphere {
             v,
             normal: Vec3::new(0.0, 0.0, 0.0),
             front_face: false,
+            color: None,
             material: Arc::clone(&self.material),
         };
         rec.set_face_normal(r, outward_normal);
@@ -84,25 +85,40 @@ impl Hittable for Sphere {
         ))
     }
 
-    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
-        let ray = Ray::with_time(*origin, *direction, 0.0);
-        let rec = match self.hit(&ray, 0.001, INFINITY) {
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, sampler: &mut Sampler) -> f64 {
+        let ray = Ray::new(*origin, *direction);
+        let rec = match self.hit(&ray, 0.001, INFINITY, sampler) {
             Some(rec) => rec,
             None => return 0.0,
         };
 
         let dist_sq = (self.center - origin).length_squared();
+        // From inside, the sphere covers every direction.
+        if dist_sq <= self.radius * self.radius {
+            return 1.0 / (4.0 * PI);
+        }
         let cos_theta_max = (1.0 - self.radius * self.radius / dist_sq).sqrt();
         let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
 
         1.0 / solid_angle
     }
 
-    fn random(&self, origin: &Point3) -> Vec3 {
+    fn random(&self, origin: &Point3, sampler: &mut Sampler) -> Vec3 {
         let direction = self.center - origin;
         let distance_squared = direction.length_squared();
+        if distance_squared <= self.radius * self.radius {
+            return random_unit_vector(sampler);
+        }
         let uvw = Onb::build_from_w(direction);
-        uvw.local_vec(Self::random_to_sphere(self.radius, distance_squared))
+        uvw.local_vec(Self::random_to_sphere(self.radius, distance_squared, sampler))
+    }
+
+    fn can_sample(&self) -> bool {
+        true
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
     }
 
 }
@@ -2,11 +2,17 @@ use std::{path::Path, sync::Arc};
 
 use image::{DynamicImage, GenericImageView, ImageReader, Pixel};
 
-use crate::{perlin::Perlin, vec3::{Color, Point3}};
+use crate::{color::srgb_to_linear, hittable::HitRecord, perlin::Perlin, rtweekend::Sampler, vec3::{Color, Point3}};
 
 
 pub trait Texture: Send + Sync {
     fn value(&self, u: f64, v: f64, p: &Point3) -> Color;
+
+    // Materials look textures up through the hit record, so textures that
+    // need more than (u, v, p) can get at it.
+    fn value_at(&self, rec: &HitRecord) -> Color {
+        self.value(rec.u, rec.v, &rec.p)
+    }
 }
 
 pub struct SolidColor {
@@ -23,16 +29,28 @@ pub struct NoiseTexture {
     scale: f64,
 }
 
+// Color interpolated from the vertices of a mesh, white where there is none.
+pub struct VertexColorTexture;
+
+// Another texture multiplied by a constant color, like the factors glTF
+// applies to its texture lookups.
+pub struct ScaledTexture {
+    pub texture: Arc<dyn Texture>,
+    pub scale: Color,
+}
+
 pub struct ImageTexture {
     image: Option<DynamicImage>,
     width: usize,
     height: usize,
+    srgb: bool,   // decode the sRGB transfer function on lookup
+    repeat: bool, // wrap coordinates outside [0,1] instead of clamping
 }
 
 impl NoiseTexture {
-    pub fn new(scale: f64) -> Self {
+    pub fn new(scale: f64, sampler: &mut Sampler) -> Self {
         Self {
-            noise: Perlin::new(),
+            noise: Perlin::new(sampler),
             scale,
         }
     }
@@ -50,6 +68,8 @@ impl ImageTexture {
                         image: Some(img),
                         width: width as usize,
                         height: height as usize,
+                        srgb: false,
+                        repeat: false,
                     }
                 }
                 Err(e) => {
@@ -58,6 +78,8 @@ impl ImageTexture {
                         image: None,
                         width: 0,
                         height: 0,
+                        srgb: false,
+                        repeat: false,
                     }
                 }
             },
@@ -67,10 +89,29 @@ impl ImageTexture {
                     image: None,
                     width: 0,
                     height: 0,
+                    srgb: false,
+                    repeat: false,
                 }
             }
         }
     }
+
+    pub fn from_image(image: DynamicImage, srgb: bool, repeat: bool) -> Self {
+        let (width, height) = image.dimensions();
+        Self {
+            image: Some(image),
+            width: width as usize,
+            height: height as usize,
+            srgb,
+            repeat,
+        }
+    }
+}
+
+impl ScaledTexture {
+    pub fn new(texture: Arc<dyn Texture>, scale: Color) -> Self {
+        Self { texture, scale }
+    }
 }
 
 impl CheckerTexture {
@@ -98,14 +139,20 @@ impl SolidColor {
     }
 }
 
+impl CheckerTexture {
+    fn pick(&self, p: &Point3) -> &Arc<dyn Texture> {
+        let sines = (10.0 * p.x).sin() * (10.0 * p.y).sin() * (10.0 * p.z).sin();
+        if sines < 0.0 { &self.odd } else { &self.even }
+    }
+}
+
 impl Texture for CheckerTexture {
     fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
-        let sines = (10.0 * p.x).sin() * (10.0 * p.y).sin() * (10.0 * p.z).sin();
-        if sines < 0.0 {
-            self.odd.value(u, v, p)
-        } else {
-            self.even.value(u, v, p)
-        }
+        self.pick(p).value(u, v, p)
+    }
+
+    fn value_at(&self, rec: &HitRecord) -> Color {
+        self.pick(&rec.p).value_at(rec)
     }
 }
 
@@ -121,6 +168,16 @@ impl Texture for NoiseTexture {
     }
 }
 
+impl Texture for VertexColorTexture {
+    fn value(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+        Color::new(1.0, 1.0, 1.0)
+    }
+
+    fn value_at(&self, rec: &HitRecord) -> Color {
+        rec.color.unwrap_or(Color::new(1.0, 1.0, 1.0))
+    }
+}
+
 impl Texture for ImageTexture {
     fn value(&self, mut u: f64, mut v: f64, _p: &Point3) -> Color {
         // If we have no texture data, then return solid cyan as a debugging aid.
@@ -129,7 +186,11 @@ impl Texture for ImageTexture {
             None => return Color::new(0.0, 1.0, 1.0),
         };
 
-        // Clamp input texture coordinates to [0,1] x [1,0]
+        // Clamp (or wrap) input texture coordinates to [0,1] x [1,0]
+        if self.repeat {
+            u -= u.floor();
+            v -= v.floor();
+        }
         u = u.clamp(0.0, 1.0);
         v = 1.0 - v.clamp(0.0, 1.0);
 
@@ -147,10 +208,20 @@ impl Texture for ImageTexture {
         let [r, g, b] = pixel.0;
 
         let color_scale = 1.0 / 255.0;
-        Color::new(
-            r as f64 * color_scale,
-            g as f64 * color_scale,
-            b as f64 * color_scale,
-        )
+        let decode = |c: u8| {
+            let c = c as f64 * color_scale;
+            if self.srgb { srgb_to_linear(c) } else { c }
+        };
+        Color::new(decode(r), decode(g), decode(b))
+    }
+}
+
+impl Texture for ScaledTexture {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        self.scale * self.texture.value(u, v, p)
+    }
+
+    fn value_at(&self, rec: &HitRecord) -> Color {
+        self.scale * self.texture.value_at(rec)
     }
 }
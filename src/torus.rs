@@ -0,0 +1,320 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    material::Material,
+    onb::Onb,
+    ray::Ray,
+    rtweekend::{Sampler, PI},
+    vec3::{dot, Point3, Vec3},
+};
+
+// A ring of radius `major_radius` around `axis` through the center, swept
+// by a circle of radius `minor_radius`. u goes around the axis and v
+// around the tube, starting from its inner edge.
+pub struct Torus {
+    pub center: Point3,
+    pub major_radius: f64,
+    pub minor_radius: f64,
+    pub material: Arc<dyn Material>,
+    uvw: Onb,
+}
+
+impl Torus {
+    pub fn new(center: Point3, axis: Vec3, major_radius: f64, minor_radius: f64, material: Arc<dyn Material>) -> Self {
+        Self {
+            center,
+            major_radius,
+            minor_radius,
+            material,
+            uvw: Onb::build_from_w(axis),
+        }
+    }
+}
+
+impl Hittable for Torus {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, _sampler: &mut Sampler) -> Option<HitRecord> {
+        // Solved for a torus scaled to fit the unit sphere and a unit
+        // direction, so the quartic's coefficients stay near one whatever
+        // the scene's units; s is distance in that space.
+        let bound = self.major_radius + self.minor_radius;
+        let direction = self.uvw.to_local(r.direction);
+        let speed = direction.length() / bound;
+        let d = direction.unit_vector();
+        let mut o = self.uvw.to_local(r.origin - self.center) / bound;
+
+        // The quartic also loses precision when the origin is far away, so
+        // start from where the ray enters the bounding sphere.
+        let half_b = dot(&o, &d);
+        let discriminant = half_b * half_b - (dot(&o, &o) - 1.0);
+        if discriminant < 0.0 {
+            return None;
+        }
+        let s_shift = (-half_b - discriminant.sqrt()).max(0.0);
+        if s_shift / speed > t_max {
+            return None;
+        }
+        o += s_shift * d;
+
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + y^2) along the ray.
+        let big = (self.major_radius / bound).powi(2);
+        let small = (self.minor_radius / bound).powi(2);
+        let od = dot(&o, &d);
+        let k = dot(&o, &o) + big - small;
+        let coefficients = [
+            k * k - 4.0 * big * (o.x * o.x + o.y * o.y),
+            4.0 * od * k - 8.0 * big * (o.x * d.x + o.y * d.y),
+            2.0 * k + 4.0 * od * od - 4.0 * big * (d.x * d.x + d.y * d.y),
+            4.0 * od,
+            1.0,
+        ];
+
+        let t = solve_quartic(coefficients)
+            .into_iter()
+            .map(|s| (s + s_shift) / speed)
+            .filter(|&t| t >= t_min && t <= t_max)
+            .min_by(|a, b| a.total_cmp(b))?;
+
+        // The normal points from the nearest point of the ring.
+        let p = self.uvw.to_local(r.at(t) - self.center);
+        let around = p.y.atan2(p.x);
+        let ring = self.major_radius * Vec3::new(around.cos(), around.sin(), 0.0);
+        let outward_normal = (p - ring) / self.minor_radius;
+        let distance = (p.x * p.x + p.y * p.y).sqrt() - self.major_radius;
+
+        let mut rec = HitRecord {
+            t,
+            p: r.at(t),
+            u: (around + PI) / (2.0 * PI),
+            v: (p.z.atan2(distance) + PI) / (2.0 * PI),
+            normal: Vec3::ZERO,
+            front_face: false,
+            color: None,
+            material: Arc::clone(&self.material),
+        };
+        rec.set_face_normal(r, self.uvw.local_vec(outward_normal).unit_vector());
+
+        Some(rec)
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        // The ring's extent (see Cylinder) grown by the tube in every direction.
+        let w = self.uvw.w();
+        let extent = Vec3::new(
+            self.major_radius * (1.0 - w.x * w.x).max(0.0).sqrt() + self.minor_radius,
+            self.major_radius * (1.0 - w.y * w.y).max(0.0).sqrt() + self.minor_radius,
+            self.major_radius * (1.0 - w.z * w.z).max(0.0).sqrt() + self.minor_radius,
+        );
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+}
+
+const EPSILON: f64 = 1e-9;
+
+fn is_zero(x: f64) -> bool {
+    x.abs() < EPSILON
+}
+
+// Real roots of c[2] x^2 + c[1] x + c[0].
+fn solve_quadratic(c: [f64; 3]) -> Vec<f64> {
+    let p = c[1] / (2.0 * c[2]);
+    let q = c[0] / c[2];
+    let discriminant = p * p - q;
+
+    if is_zero(discriminant) {
+        vec![-p]
+    } else if discriminant < 0.0 {
+        vec![]
+    } else {
+        let sqrt_d = discriminant.sqrt();
+        vec![sqrt_d - p, -sqrt_d - p]
+    }
+}
+
+// Real roots of c[3] x^3 + ... + c[0], by Cardano's formula.
+fn solve_cubic(c: [f64; 4]) -> Vec<f64> {
+    // Normal form x^3 + A x^2 + B x + C, then x = y - A/3 removes the
+    // square: y^3 + 3 p y + 2 q.
+    let a = c[2] / c[3];
+    let b = c[1] / c[3];
+    let cc = c[0] / c[3];
+
+    let sq_a = a * a;
+    let p = (-sq_a / 3.0 + b) / 3.0;
+    let q = (2.0 / 27.0 * a * sq_a - a * b / 3.0 + cc) / 2.0;
+    let cb_p = p * p * p;
+    let discriminant = q * q + cb_p;
+
+    let mut roots = if is_zero(discriminant) {
+        if is_zero(q) {
+            vec![0.0]
+        } else {
+            let u = (-q).cbrt();
+            vec![2.0 * u, -u]
+        }
+    } else if discriminant < 0.0 {
+        // Three real roots.
+        let phi = (-q / (-cb_p).sqrt()).clamp(-1.0, 1.0).acos() / 3.0;
+        let t = 2.0 * (-p).sqrt();
+        vec![t * phi.cos(), -t * (phi + PI / 3.0).cos(), -t * (phi - PI / 3.0).cos()]
+    } else {
+        let sqrt_d = discriminant.sqrt();
+        vec![(sqrt_d - q).cbrt() - (sqrt_d + q).cbrt()]
+    };
+
+    for root in &mut roots {
+        *root -= a / 3.0;
+    }
+    roots
+}
+
+// Real roots of c[4] x^4 + ... + c[0] by Ferrari's method, each polished
+// with a few Newton steps since the closed form loses digits.
+pub fn solve_quartic(c: [f64; 5]) -> Vec<f64> {
+    // Normal form x^4 + A x^3 + B x^2 + C x + D, then x = y - A/4 removes
+    // the cube: y^4 + p y^2 + q y + r.
+    let a = c[3] / c[4];
+    let b = c[2] / c[4];
+    let cc = c[1] / c[4];
+    let d = c[0] / c[4];
+
+    let sq_a = a * a;
+    let p = -3.0 / 8.0 * sq_a + b;
+    let q = sq_a * a / 8.0 - a * b / 2.0 + cc;
+    let r = -3.0 / 256.0 * sq_a * sq_a + sq_a * b / 16.0 - a * cc / 4.0 + d;
+
+    let mut roots = if is_zero(r) {
+        // y (y^3 + p y + q) = 0
+        let mut roots = solve_cubic([q, p, 0.0, 1.0]);
+        roots.push(0.0);
+        roots
+    } else {
+        // One real root z of the resolvent cubic splits the quartic into
+        // two quadratics.
+        let z = solve_cubic([r * p / 2.0 - q * q / 8.0, -r, -p / 2.0, 1.0])[0];
+        let mut u = z * z - r;
+        let mut v = 2.0 * z - p;
+
+        if is_zero(u) {
+            u = 0.0;
+        } else if u > 0.0 {
+            u = u.sqrt();
+        } else {
+            return vec![];
+        }
+        if is_zero(v) {
+            v = 0.0;
+        } else if v > 0.0 {
+            v = v.sqrt();
+        } else {
+            return vec![];
+        }
+
+        let v = if q < 0.0 { -v } else { v };
+        let mut roots = solve_quadratic([z - u, v, 1.0]);
+        roots.extend(solve_quadratic([z + u, -v, 1.0]));
+        roots
+    };
+
+    for root in &mut roots {
+        *root -= a / 4.0;
+        for _ in 0..3 {
+            let value = (((*root + a) * *root + b) * *root + cc) * *root + d;
+            let slope = ((4.0 * *root + 3.0 * a) * *root + 2.0 * b) * *root + cc;
+            if slope.abs() < EPSILON {
+                break;
+            }
+            *root -= value / slope;
+        }
+    }
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Lambertian, rtweekend::INFINITY, vec3::Color};
+
+    // Lying flat around the y axis: the tube is 1 thick and centered 3 out.
+    fn torus() -> Torus {
+        let material = Arc::new(Lambertian::new_from_color(Color::new(0.5, 0.5, 0.5)));
+        Torus::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 3.0, 1.0, material)
+    }
+
+    fn sorted(mut roots: Vec<f64>) -> Vec<f64> {
+        roots.sort_by(|a, b| a.total_cmp(b));
+        roots
+    }
+
+    #[test]
+    fn quartic_with_four_roots() {
+        // (x - 1)(x - 2)(x - 3)(x - 4)
+        let roots = sorted(solve_quartic([24.0, -50.0, 35.0, -10.0, 1.0]));
+        assert_eq!(roots.len(), 4);
+        for (root, expected) in roots.iter().zip([1.0, 2.0, 3.0, 4.0]) {
+            assert!((root - expected).abs() < 1e-9, "{:?}", roots);
+        }
+    }
+
+    #[test]
+    fn quartic_with_two_and_no_roots() {
+        // (x^2 + 1)(x - 2)(x + 3)
+        let roots = sorted(solve_quartic([-6.0, 1.0, -5.0, 1.0, 1.0]));
+        assert_eq!(roots.len(), 2);
+        assert!((roots[0] + 3.0).abs() < 1e-9 && (roots[1] - 2.0).abs() < 1e-9);
+        // x^4 + 1
+        assert!(solve_quartic([1.0, 0.0, 0.0, 0.0, 1.0]).is_empty());
+    }
+
+    #[test]
+    fn hits_the_outer_wall_first() {
+        let mut sampler = Sampler::new(0);
+        let r = Ray::new(Point3::new(-10.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let rec = torus().hit(&r, 0.001, INFINITY, &mut sampler).unwrap();
+        assert!((rec.t - 6.0).abs() < 1e-9);
+        assert!(rec.front_face);
+        assert!((rec.normal - Vec3::new(-1.0, 0.0, 0.0)).length() < 1e-9);
+
+        // From the hole outward, the first hit is the inner wall's front.
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let rec = torus().hit(&r, 0.001, INFINITY, &mut sampler).unwrap();
+        assert!((rec.t - 2.0).abs() < 1e-9);
+        assert!(rec.front_face);
+        assert!((rec.normal - Vec3::new(-1.0, 0.0, 0.0)).length() < 1e-9);
+        assert!(rec.v.abs() < 1e-9 || (rec.v - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn inside_the_tube_and_through_the_hole() {
+        let mut sampler = Sampler::new(0);
+        let r = Ray::new(Point3::new(3.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let rec = torus().hit(&r, 0.001, INFINITY, &mut sampler).unwrap();
+        assert!((rec.t - 1.0).abs() < 1e-9);
+        assert!(!rec.front_face);
+
+        let down = Ray::new(Point3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(torus().hit(&down, 0.001, INFINITY, &mut sampler).is_none());
+    }
+
+    #[test]
+    fn grazing_the_top_of_the_tube() {
+        let mut sampler = Sampler::new(0);
+        // Tangent to the top at (3, 1, 0); slightly above misses.
+        let above = Ray::new(Point3::new(3.0, 1.0 + 1e-4, -10.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(torus().hit(&above, 0.001, INFINITY, &mut sampler).is_none());
+        let below = Ray::new(Point3::new(3.0, 1.0 - 1e-4, -10.0), Vec3::new(0.0, 0.0, 1.0));
+        let rec = torus().hit(&below, 0.001, INFINITY, &mut sampler).unwrap();
+        let rho: f64 = 3.0 + (1.0 - (1.0 - 1e-4_f64).powi(2)).sqrt();
+        assert!((rec.t - (10.0 - (rho * rho - 9.0).sqrt())).abs() < 1e-6);
+    }
+
+    #[test]
+    fn far_away_rays_stay_accurate() {
+        let mut sampler = Sampler::new(0);
+        let r = Ray::new(Point3::new(-1e5, 0.5, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let rec = torus().hit(&r, 0.001, INFINITY, &mut sampler).unwrap();
+        let expected = 1e5 - 3.0 - 0.75_f64.sqrt();
+        assert!((rec.t - expected).abs() < 1e-6);
+    }
+}
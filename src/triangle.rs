@@ -0,0 +1,499 @@
+use std::sync::{Arc, OnceLock};
+
+use crate::{aabb::Aabb, bvh::LinearBvh, hittable::{HitRecord, Hittable}, material::Material, ray::Ray, rtweekend::{INFINITY, Sampler}, texture::Texture, vec3::{cross, dot, Color, Point3, Vec3}};
+
+pub struct Triangle {
+    pub vertices: [Point3; 3],
+    pub normals: Option<[Vec3; 3]>,
+    pub uvs: Option<[(f64, f64); 3]>,
+    pub material: Arc<dyn Material>,
+}
+
+impl Triangle {
+    pub fn new(v0: Point3, v1: Point3, v2: Point3, material: Arc<dyn Material>) -> Self {
+        Self {
+            vertices: [v0, v1, v2],
+            normals: None,
+            uvs: None,
+            material,
+        }
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, _sampler: &mut Sampler) -> Option<HitRecord> {
+        let attributes = VertexAttributes {
+            normals: self.normals,
+            uvs: self.uvs,
+            colors: None,
+            normal_map: None,
+        };
+        hit_triangle(&self.vertices, &attributes, &self.material, r, t_min, t_max)
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        Some(triangle_box(&self.vertices))
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, _sampler: &mut Sampler) -> f64 {
+        let flat = |r: &Ray, t_min| hit_triangle(&self.vertices, &VertexAttributes::flat(), &self.material, r, t_min, INFINITY);
+        surface_pdf_value(flat, triangle_area(&self.vertices), origin, direction)
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut Sampler) -> Vec3 {
+        random_point(&self.vertices, sampler) - *origin
+    }
+
+    fn can_sample(&self) -> bool {
+        true
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+}
+
+// Per-corner data interpolated across a face.
+struct VertexAttributes<'a> {
+    normals: Option<[Vec3; 3]>,
+    uvs: Option<[(f64, f64); 3]>,
+    colors: Option<[Color; 3]>,
+    normal_map: Option<&'a NormalMap>,
+}
+
+impl VertexAttributes<'_> {
+    // None at all, so hits report the geometric normal.
+    fn flat() -> Self {
+        Self {
+            normals: None,
+            uvs: None,
+            colors: None,
+            normal_map: None,
+        }
+    }
+}
+
+// Tangent-space normals looked up by uv, with x and y scaled by `scale`.
+// Green points along increasing v, which is the OpenGL convention glTF uses.
+pub struct NormalMap {
+    pub texture: Arc<dyn Texture>,
+    pub scale: f64,
+}
+
+// Vertex buffers shared by every triangle of a mesh. Normals, uvs and colors
+// are either empty or hold one entry per position; each face indexes all of them.
+pub struct MeshData {
+    pub positions: Vec<Point3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(f64, f64)>,
+    pub colors: Vec<Color>,
+    pub indices: Vec<[usize; 3]>,
+    pub material: Arc<dyn Material>,
+    pub normal_map: Option<NormalMap>,
+}
+
+impl MeshData {
+    fn vertices(&self, face: usize) -> [Point3; 3] {
+        let [a, b, c] = self.indices[face];
+        [self.positions[a], self.positions[b], self.positions[c]]
+    }
+
+    fn is_smooth(&self) -> bool {
+        !self.normals.is_empty() || self.normal_map.is_some()
+    }
+
+    fn attributes(&self, face: usize) -> VertexAttributes<'_> {
+        let [a, b, c] = self.indices[face];
+        VertexAttributes {
+            normals: (!self.normals.is_empty()).then(|| [self.normals[a], self.normals[b], self.normals[c]]),
+            uvs: (!self.uvs.is_empty()).then(|| [self.uvs[a], self.uvs[b], self.uvs[c]]),
+            colors: (!self.colors.is_empty()).then(|| [self.colors[a], self.colors[b], self.colors[c]]),
+            normal_map: self.normal_map.as_ref(),
+        }
+    }
+}
+
+// One face of a mesh, looked up in the shared buffers when hit. A flat face
+// ignores the mesh's normals and normal map.
+struct MeshTriangle {
+    mesh: Arc<MeshData>,
+    face: usize,
+    flat: bool,
+}
+
+impl Hittable for MeshTriangle {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, _sampler: &mut Sampler) -> Option<HitRecord> {
+        let attributes = if self.flat { VertexAttributes::flat() } else { self.mesh.attributes(self.face) };
+        hit_triangle(&self.mesh.vertices(self.face), &attributes, &self.mesh.material, r, t_min, t_max)
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        Some(triangle_box(&self.mesh.vertices(self.face)))
+    }
+}
+
+pub struct TriangleMesh {
+    mesh: Arc<MeshData>,
+    bvh: LinearBvh,
+    // The faces again without smooth shading, built the first time a
+    // smooth mesh is sampled as a light.
+    flat_bvh: OnceLock<LinearBvh>,
+    // Running sum of face areas, for picking a face proportional to its area.
+    area_cdf: Vec<f64>,
+}
+
+impl TriangleMesh {
+    pub fn new(mesh: MeshData) -> Self {
+        assert!(!mesh.indices.is_empty(), "Triangle mesh has no faces");
+        assert!(mesh.normals.is_empty() || mesh.normals.len() == mesh.positions.len(), "Mesh needs one normal per position");
+        assert!(mesh.uvs.is_empty() || mesh.uvs.len() == mesh.positions.len(), "Mesh needs one uv per position");
+        assert!(mesh.colors.is_empty() || mesh.colors.len() == mesh.positions.len(), "Mesh needs one color per position");
+
+        let mesh = Arc::new(mesh);
+
+        let mut area_cdf = Vec::with_capacity(mesh.indices.len());
+        let mut total = 0.0;
+        for face in 0..mesh.indices.len() {
+            total += triangle_area(&mesh.vertices(face));
+            area_cdf.push(total);
+        }
+
+        let bvh = mesh_bvh(&mesh, false);
+
+        Self { mesh, bvh, flat_bvh: OnceLock::new(), area_cdf }
+    }
+
+    // The faces hit with their geometric normals, whose cosine converts
+    // area density to solid angle.
+    fn flat_bvh(&self) -> &LinearBvh {
+        if self.mesh.is_smooth() {
+            self.flat_bvh.get_or_init(|| mesh_bvh(&self.mesh, true))
+        } else {
+            &self.bvh
+        }
+    }
+
+    pub fn area(&self) -> f64 {
+        *self.area_cdf.last().unwrap()
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, sampler: &mut Sampler) -> Option<HitRecord> {
+        self.bvh.hit(r, t_min, t_max, sampler)
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+        self.bvh.bounding_box(time0, time1)
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, sampler: &mut Sampler) -> f64 {
+        surface_pdf_value(|r: &Ray, t_min| self.flat_bvh().hit(r, t_min, INFINITY, sampler), self.area(), origin, direction)
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut Sampler) -> Vec3 {
+        let target = sampler.random_double() * self.area();
+        let face = self.area_cdf.partition_point(|&a| a < target).min(self.area_cdf.len() - 1);
+        random_point(&self.mesh.vertices(face), sampler) - *origin
+    }
+
+    fn can_sample(&self) -> bool {
+        true
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.mesh.material.is_emissive()
+    }
+}
+
+fn mesh_bvh(mesh: &Arc<MeshData>, flat: bool) -> LinearBvh {
+    let mut faces: Vec<Arc<dyn Hittable>> = (0..mesh.indices.len())
+        .map(|face| Arc::new(MeshTriangle { mesh: Arc::clone(mesh), face, flat }) as Arc<dyn Hittable>)
+        .collect();
+    LinearBvh::new(&mut faces, 0.0, 1.0)
+}
+
+// Möller–Trumbore. Without per-vertex uvs the barycentric coordinates are
+// used as (u, v); per-vertex normals are interpolated for smooth shading
+// while the geometric normal still decides the front face. A normal map
+// perturbs the shading normal in the tangent frame given by the uvs.
+fn hit_triangle(
+    vertices: &[Point3; 3],
+    attributes: &VertexAttributes,
+    material: &Arc<dyn Material>,
+    r: &Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Option<HitRecord> {
+    let [v0, v1, v2] = *vertices;
+    let edge1 = v1 - v0;
+    let edge2 = v2 - v0;
+
+    // The determinant grows with the ray direction and both edges, so the
+    // parallel test is relative to their lengths and works at any scale.
+    let pvec = cross(&r.direction, &edge2);
+    let det = dot(&edge1, &pvec);
+    let scale = (r.direction.length_squared() * edge1.length_squared() * edge2.length_squared()).sqrt();
+    if det.abs() <= 1e-12 * scale {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+
+    let tvec = r.origin - v0;
+    let b1 = dot(&tvec, &pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let qvec = cross(&tvec, &edge1);
+    let b2 = dot(&r.direction, &qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = dot(&edge2, &qvec) * inv_det;
+    if t < t_min || t > t_max {
+        return None;
+    }
+
+    let b0 = 1.0 - b1 - b2;
+    let (u, v) = match attributes.uvs {
+        Some(uv) => (
+            b0 * uv[0].0 + b1 * uv[1].0 + b2 * uv[2].0,
+            b0 * uv[0].1 + b1 * uv[1].1 + b2 * uv[2].1,
+        ),
+        None => (b1, b2),
+    };
+
+    let mut rec = HitRecord {
+        t,
+        p: r.at(t),
+        u,
+        v,
+        normal: Vec3::ZERO,
+        front_face: false,
+        color: attributes.colors.map(|c| b0 * c[0] + b1 * c[1] + b2 * c[2]),
+        material: Arc::clone(material),
+    };
+    let geometric = cross(&edge1, &edge2).unit_vector();
+    rec.set_face_normal(r, geometric);
+
+    let mut shading = geometric;
+    if let Some(n) = attributes.normals {
+        let interpolated = b0 * n[0] + b1 * n[1] + b2 * n[2];
+        if interpolated.length_squared() > 0.0 {
+            shading = interpolated.unit_vector();
+        }
+    }
+    if let (Some(map), Some(uv)) = (attributes.normal_map, attributes.uvs) {
+        shading = apply_normal_map(map, shading, edge1, edge2, &uv, &rec);
+    }
+    rec.normal = if rec.front_face { shading } else { -shading };
+
+    Some(rec)
+}
+
+// Tangent and bitangent follow the uv gradients across the face; both are
+// re-orthogonalized against the (possibly interpolated) normal first.
+fn apply_normal_map(map: &NormalMap, normal: Vec3, edge1: Vec3, edge2: Vec3, uv: &[(f64, f64); 3], rec: &HitRecord) -> Vec3 {
+    let (du1, dv1) = (uv[1].0 - uv[0].0, uv[1].1 - uv[0].1);
+    let (du2, dv2) = (uv[2].0 - uv[0].0, uv[2].1 - uv[0].1);
+    let det = du1 * dv2 - du2 * dv1;
+    if det.abs() < 1e-12 {
+        return normal;
+    }
+
+    let tangent = (dv2 * edge1 - dv1 * edge2) / det;
+    let bitangent = (du1 * edge2 - du2 * edge1) / det;
+    let tangent = tangent - dot(&normal, &tangent) * normal;
+    if tangent.length_squared() < 1e-24 {
+        return normal;
+    }
+    let tangent = tangent.unit_vector();
+    let handedness = if dot(&cross(&normal, &tangent), &bitangent) < 0.0 { -1.0 } else { 1.0 };
+    let bitangent = handedness * cross(&normal, &tangent);
+
+    let c = map.texture.value_at(rec);
+    let x = (2.0 * c.x - 1.0) * map.scale;
+    let y = (2.0 * c.y - 1.0) * map.scale;
+    let z = 2.0 * c.z - 1.0;
+    let mapped = x * tangent + y * bitangent + z * normal;
+    if mapped.length_squared() > 0.0 { mapped.unit_vector() } else { normal }
+}
+
+fn triangle_area(vertices: &[Point3; 3]) -> f64 {
+    0.5 * cross(&(vertices[1] - vertices[0]), &(vertices[2] - vertices[0])).length()
+}
+
+fn triangle_box(vertices: &[Point3; 3]) -> Aabb {
+    // Pad so axis-aligned triangles still have a box of non-zero width.
+    let mut min = Point3::new(INFINITY, INFINITY, INFINITY);
+    let mut max = Point3::new(-INFINITY, -INFINITY, -INFINITY);
+    for v in vertices {
+        for a in 0..3 {
+            min[a] = min[a].min(v[a] - 0.0001);
+            max[a] = max[a].max(v[a] + 0.0001);
+        }
+    }
+    Aabb::new(min, max)
+}
+
+// Uniform point on the triangle.
+fn random_point(vertices: &[Point3; 3], sampler: &mut Sampler) -> Point3 {
+    let s = sampler.random_double().sqrt();
+    let r2 = sampler.random_double();
+    (1.0 - s) * vertices[0] + (s * (1.0 - r2)) * vertices[1] + (s * r2) * vertices[2]
+}
+
+// Solid angle density of sampling a surface uniformly by area. A ray can
+// cross a mesh several times, and each crossing is a point the area sampling
+// could have picked, so all of them contribute. `hit` must report geometric
+// normals: the density is per unit of the real surface, not the shading one.
+fn surface_pdf_value(mut hit: impl FnMut(&Ray, f64) -> Option<HitRecord>, area: f64, origin: &Point3, direction: &Vec3) -> f64 {
+    let mut pdf = 0.0;
+    let mut t_min = 0.001;
+
+    while let Some(rec) = hit(&Ray::new(*origin, *direction), t_min) {
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = dot(direction, &rec.normal).abs() / direction.length();
+        if cosine > 0.0 {
+            pdf += distance_squared / (cosine * area);
+        }
+        t_min = rec.t + 0.0001;
+    }
+
+    pdf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+
+    // The unit square in z = 0 as two faces, optionally with normals that
+    // lean far away from the geometric one.
+    fn square(normals: Vec<Vec3>) -> TriangleMesh {
+        TriangleMesh::new(MeshData {
+            positions: vec![
+                Point3::new(0.0, 0.0, 0.0),
+                Point3::new(1.0, 0.0, 0.0),
+                Point3::new(1.0, 1.0, 0.0),
+                Point3::new(0.0, 1.0, 0.0),
+            ],
+            normals,
+            uvs: Vec::new(),
+            colors: Vec::new(),
+            indices: vec![[0, 1, 2], [0, 2, 3]],
+            material: Arc::new(Lambertian::new_from_color(Color::new(0.5, 0.5, 0.5))),
+            normal_map: None,
+        })
+    }
+
+    fn gray() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new_from_color(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    fn unit_triangle(scale: f64) -> Triangle {
+        Triangle::new(Point3::new(0.0, 0.0, 0.0), Point3::new(scale, 0.0, 0.0), Point3::new(0.0, scale, 0.0), gray())
+    }
+
+    // Straight down at (x, y) from one unit above the z = 0 plane.
+    fn down(x: f64, y: f64) -> Ray {
+        Ray::new(Point3::new(x, y, 1.0), Vec3::new(0.0, 0.0, -1.0))
+    }
+
+    #[test]
+    fn hits_inside_and_misses_outside() {
+        let mut sampler = Sampler::new(0);
+        let triangle = unit_triangle(1.0);
+        let rec = triangle.hit(&down(0.2, 0.3), 0.001, INFINITY, &mut sampler).unwrap();
+        assert_eq!(rec.t, 1.0);
+        assert_eq!(rec.p, Point3::new(0.2, 0.3, 0.0));
+        assert!(rec.front_face);
+        assert_eq!(rec.normal, Vec3::new(0.0, 0.0, 1.0));
+
+        // From below, the normal turns to face the ray.
+        let rec = triangle.hit(&Ray::new(Point3::new(0.2, 0.3, -1.0), Vec3::new(0.0, 0.0, 1.0)), 0.001, INFINITY, &mut sampler).unwrap();
+        assert!(!rec.front_face);
+        assert_eq!(rec.normal, Vec3::new(0.0, 0.0, -1.0));
+
+        for (x, y) in [(0.6, 0.6), (-0.1, 0.5), (0.5, -0.1), (1.1, 0.0)] {
+            assert!(triangle.hit(&down(x, y), 0.001, INFINITY, &mut sampler).is_none(), "({}, {})", x, y);
+        }
+        // Parallel to the plane, pointing away, and beyond t_max.
+        assert!(triangle.hit(&Ray::new(Point3::new(-1.0, 0.2, 0.0), Vec3::new(1.0, 0.0, 0.0)), 0.001, INFINITY, &mut sampler).is_none());
+        assert!(triangle.hit(&Ray::new(Point3::new(0.2, 0.2, 1.0), Vec3::new(0.0, 0.0, 1.0)), 0.001, INFINITY, &mut sampler).is_none());
+        assert!(triangle.hit(&down(0.2, 0.2), 0.001, 0.5, &mut sampler).is_none());
+    }
+
+    #[test]
+    fn edges_and_corners_count_as_hits() {
+        let mut sampler = Sampler::new(0);
+        let triangle = unit_triangle(1.0);
+        for (x, y) in [(0.5, 0.0), (0.0, 0.5), (0.5, 0.5), (0.0, 0.0), (1.0, 0.0), (0.0, 1.0)] {
+            let rec = triangle.hit(&down(x, y), 0.001, INFINITY, &mut sampler);
+            assert!(rec.is_some_and(|rec| rec.t == 1.0), "({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn uvs_interpolate_with_the_barycentric_weights() {
+        let mut sampler = Sampler::new(0);
+        let mut triangle = Triangle::new(Point3::new(1.0, 0.0, 0.0), Point3::new(3.0, 0.0, 1.0), Point3::new(1.0, 2.0, 0.0), gray());
+        let [v0, v1, v2] = triangle.vertices;
+        let p = v0 + 0.3 * (v1 - v0) + 0.5 * (v2 - v0);
+        let r = Ray::new(p + Vec3::new(-1.0, 0.2, 3.0), Vec3::new(1.0, -0.2, -3.0));
+
+        // Without uvs the hit reports the weights of v1 and v2.
+        let rec = triangle.hit(&r, 0.001, INFINITY, &mut sampler).unwrap();
+        assert!((rec.u - 0.3).abs() < 1e-12 && (rec.v - 0.5).abs() < 1e-12);
+
+        triangle.uvs = Some([(0.1, 0.2), (0.9, 0.3), (0.4, 0.8)]);
+        let rec = triangle.hit(&r, 0.001, INFINITY, &mut sampler).unwrap();
+        assert!((rec.u - (0.2 * 0.1 + 0.3 * 0.9 + 0.5 * 0.4)).abs() < 1e-12);
+        assert!((rec.v - (0.2 * 0.2 + 0.3 * 0.3 + 0.5 * 0.8)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn tiny_and_huge_triangles_are_hit() {
+        let mut sampler = Sampler::new(0);
+        for scale in [1e-7, 1e7] {
+            let triangle = unit_triangle(scale);
+            let rec = triangle.hit(&down(0.25 * scale, 0.25 * scale), 0.001, INFINITY, &mut sampler);
+            assert!(rec.is_some_and(|rec| rec.t == 1.0), "scale {}", scale);
+        }
+    }
+
+    #[test]
+    fn pdf_ignores_shading_normals() {
+        let mut sampler = Sampler::new(0);
+        let leaning = Vec3::new(1.0, 0.5, 0.3).unit_vector();
+        let flat = square(Vec::new());
+        let smooth = square(vec![leaning; 4]);
+
+        let origin = Point3::new(0.2, 0.3, 2.0);
+        let direction = Point3::new(0.6, 0.7, 0.0) - origin;
+        // distance² / (cos area) with the square's own normal.
+        let cosine = direction.z.abs() / direction.length();
+        let expected = direction.length_squared() / cosine;
+
+        assert!((flat.pdf_value(&origin, &direction, &mut sampler) - expected).abs() < 1e-9);
+        assert!((smooth.pdf_value(&origin, &direction, &mut sampler) - expected).abs() < 1e-9);
+        // Hits still shade with the leaning normal.
+        let rec = smooth.hit(&Ray::new(origin, direction), 0.001, INFINITY, &mut sampler).unwrap();
+        assert!((rec.normal - leaning).length() < 1e-9);
+    }
+
+    #[test]
+    fn single_triangle_pdf_ignores_shading_normals() {
+        let mut sampler = Sampler::new(0);
+        let material = Arc::new(Lambertian::new_from_color(Color::new(0.5, 0.5, 0.5)));
+        let mut triangle = Triangle::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0), Point3::new(0.0, 1.0, 0.0), material);
+        let origin = Point3::new(0.1, 0.2, 1.0);
+        let direction = Vec3::new(0.1, 0.0, -1.0);
+        let flat = triangle.pdf_value(&origin, &direction, &mut sampler);
+        triangle.normals = Some([Vec3::new(0.6, 0.0, 0.8); 3]);
+        assert!(flat > 0.0);
+        assert!((triangle.pdf_value(&origin, &direction, &mut sampler) - flat).abs() < 1e-9);
+    }
+}
@@ -1,6 +1,6 @@
 use std::ops::{Add, AddAssign, Div, Index, IndexMut, Mul, MulAssign, Neg, Sub};
 
-use crate::rtweekend::{random_double, random_double_range, PI};
+use crate::rtweekend::{PI, Sampler};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Vec3 {
@@ -26,12 +26,16 @@ impl Vec3 {
         *self / self.length()
     }
 
-    pub fn random() -> Vec3 {
-        Vec3::new(random_double(), random_double(), random_double())
+    pub fn random(sampler: &mut Sampler) -> Vec3 {
+        Vec3::new(sampler.random_double(), sampler.random_double(), sampler.random_double())
     }
 
-    pub fn random_range(min: f64, max: f64) -> Vec3 {
-        Vec3::new(random_double_range(min, max), random_double_range(min, max), random_double_range(min, max))
+    pub fn random_range(min: f64, max: f64, sampler: &mut Sampler) -> Vec3 {
+        Vec3::new(
+            sampler.random_double_range(min, max),
+            sampler.random_double_range(min, max),
+            sampler.random_double_range(min, max),
+        )
     }
 
     pub fn near_zero(&self) -> bool {
@@ -245,23 +249,12 @@ pub fn unit_vector(v: &Vec3) -> Vec3 {
     *v / v.length()
 }
 
-pub fn random_in_unit_sphere() -> Vec3 {
-    loop {
-        let p = Vec3::random_range(-1.0, 1.0);
-        let len_sq = p.length_squared();
-        if len_sq < 1e-12 || len_sq >= 1.0 {
-            continue;
-        }
-        return p;
-    }
-}
-
-pub fn random_unit_vector() -> Vec3 {
+pub fn random_unit_vector(sampler: &mut Sampler) -> Vec3 {
    loop {
        let p = Vec3::new(
-            random_double_range(-1.0, 1.0),
-            random_double_range(-1.0, 1.0),
-            random_double_range(-1.0, 1.0),
+            sampler.random_double_range(-1.0, 1.0),
+            sampler.random_double_range(-1.0, 1.0),
+            sampler.random_double_range(-1.0, 1.0),
         );
         let len_sq = p.length_squared();
         if 1e-160 < len_sq && len_sq <= 1.0 {
@@ -270,17 +263,9 @@ pub fn random_unit_vector() -> Vec3 {
    }
 }
 
-pub fn random_in_hemisphere(normal: &Vec3) -> Vec3 {
-    let in_unit_sphere: Vec3 = random_in_unit_sphere();
-    if dot(&in_unit_sphere, normal) > 0.0 { // In the same hempishere as normal
-       return in_unit_sphere;
-    }
-    -in_unit_sphere
-}
-
-pub fn random_cosine_direction() -> Vec3 {
-    let r1 = random_double();
-    let r2 = random_double();
+pub fn random_cosine_direction(sampler: &mut Sampler) -> Vec3 {
+    let r1 = sampler.random_double();
+    let r2 = sampler.random_double();
     let z = (1.0 - r2).sqrt();
 
     let phi = 2.0 * PI * r1;
@@ -301,9 +286,9 @@ pub fn refract(uv: &Vec3, n: &Vec3, etai_over_etat: f64) -> Vec3 {
     r_out_perp + r_out_parallel
 }
 
-pub fn random_in_unit_disk() -> Vec3 {
+pub fn random_in_unit_disk(sampler: &mut Sampler) -> Vec3 {
     loop {
-        let p = Vec3::new(random_double_range(-1.0, 1.0), random_double_range(-1.0, 1.0), 0.0);
+        let p = Vec3::new(sampler.random_double_range(-1.0, 1.0), sampler.random_double_range(-1.0, 1.0), 0.0);
         if p.length_squared() >= 1.0 {
             continue;
         }
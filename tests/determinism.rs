@@ -0,0 +1,29 @@
+// Renders are a pure function of the scene, options and seed: the thread
+// count must not change a single bit, which image regression tests rely on.
+
+use std::process::Command;
+
+fn render(args: &[&str]) -> Vec<u8> {
+    let output = Command::new(env!("CARGO_BIN_EXE_Rust-Ray-Tracing-Tutorial"))
+        .args(["--scene", "cornell_box", "--width", "48", "--height", "48", "--spp", "8"])
+        .args(args)
+        .output()
+        .expect("could not run the renderer");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    output.stdout
+}
+
+#[test]
+fn thread_count_does_not_change_the_image() {
+    let single = render(&["--threads", "1"]);
+    let many = render(&["--threads", "4"]);
+    assert!(single.starts_with(b"P3"));
+    assert!(single == many, "images rendered with 1 and 4 threads differ");
+}
+
+#[test]
+fn seed_changes_the_image() {
+    let a = render(&["--threads", "2", "--seed", "1"]);
+    let b = render(&["--threads", "2", "--seed", "2"]);
+    assert!(a != b);
+}